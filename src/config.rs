@@ -4,6 +4,16 @@ use std::time::Duration;
 use crate::serialization::SerializationOptions;
 use crate::storage::StorageConfig;
 
+/// How the indexer chooses the leaf index for an incoming commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeafPlacement {
+    /// Leaves are appended in arrival order
+    #[default]
+    AppendOrder,
+    /// Leaves are placed at the commitment's on-chain `commitment_index`
+    ByCommitmentIndex,
+}
+
 /// Main configuration for the indexer
 #[derive(Debug, Clone)]
 pub struct IndexerConfig {
@@ -13,6 +23,8 @@ pub struct IndexerConfig {
     pub serialization: SerializationOptions,
     /// Whether to enable debug logging
     pub debug: bool,
+    /// Leaf placement policy for inserted commitments
+    pub leaf_placement: LeafPlacement,
 }
 
 impl IndexerConfig {
@@ -22,6 +34,7 @@ impl IndexerConfig {
             storage: StorageConfig::new(data_dir),
             serialization: SerializationOptions::default(),
             debug: false,
+            leaf_placement: LeafPlacement::default(),
         }
     }
 
@@ -34,6 +47,7 @@ impl IndexerConfig {
                 .with_wal(false), // Disable WAL for speed
             serialization: SerializationOptions::fastest(),
             debug: true,
+            leaf_placement: LeafPlacement::default(),
         }
     }
 
@@ -44,6 +58,7 @@ impl IndexerConfig {
                 .with_cache_size(4 * 1024 * 1024), // 4MB cache
             serialization: SerializationOptions::balanced(),
             debug: false,
+            leaf_placement: LeafPlacement::default(),
         }
     }
 
@@ -59,6 +74,12 @@ impl IndexerConfig {
         self
     }
 
+    /// Set the leaf placement policy
+    pub fn with_leaf_placement(mut self, placement: LeafPlacement) -> Self {
+        self.leaf_placement = placement;
+        self
+    }
+
     /// Enable or disable debug mode
     pub fn with_debug(mut self, debug: bool) -> Self {
         self.debug = debug;
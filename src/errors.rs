@@ -21,6 +21,10 @@ pub enum IndexerError {
     StorageError(String),
     /// Feature not yet implemented
     NotImplemented(String),
+    /// A commitment's nullifier has already been used
+    NullifierAlreadyUsed,
+    /// An operation would push the tree past its memory cap (in bytes)
+    MemoryLimitExceeded(usize),
 }
 
 impl fmt::Display for IndexerError {
@@ -35,6 +39,10 @@ impl fmt::Display for IndexerError {
             IndexerError::InvalidData(msg) => write!(f, "Invalid data: {msg}"),
             IndexerError::StorageError(msg) => write!(f, "Storage error: {msg}"),
             IndexerError::NotImplemented(msg) => write!(f, "Feature not implemented: {msg}"),
+            IndexerError::NullifierAlreadyUsed => write!(f, "Nullifier has already been used"),
+            IndexerError::MemoryLimitExceeded(limit) => {
+                write!(f, "Memory limit of {limit} bytes exceeded")
+            }
         }
     }
 }
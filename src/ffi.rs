@@ -0,0 +1,97 @@
+//! C ABI bindings for proof verification
+//!
+//! Enabled with the `ffi` feature. Functions here take raw pointers and
+//! plain integers only so they can be called from C without any Rust types.
+
+use crate::tree::MerkleProof;
+use crate::utils::Hash;
+
+/// Verify a Merkle proof from raw buffers.
+///
+/// `leaf` and `root` point to 32-byte hashes and `siblings` points to
+/// `sibling_count` contiguous 32-byte hashes ordered from the leaf level up.
+/// Returns `false` if any required pointer is null.
+///
+/// # Safety
+///
+/// `leaf` and `root` must be valid for reads of 32 bytes, and `siblings`
+/// must be valid for reads of `sibling_count * 32` bytes (it may be null
+/// only when `sibling_count` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn imt_verify_proof(
+    leaf: *const u8,
+    leaf_index: usize,
+    siblings: *const u8,
+    sibling_count: usize,
+    root: *const u8,
+) -> bool {
+    if leaf.is_null() || root.is_null() || (siblings.is_null() && sibling_count > 0) {
+        return false;
+    }
+
+    let leaf = read_hash(leaf);
+    let root = read_hash(root);
+    let siblings: Vec<Hash> = (0..sibling_count)
+        .map(|i| read_hash(siblings.add(i * 32)))
+        .collect();
+
+    MerkleProof::verify_proof(&leaf, leaf_index, &siblings, &root)
+}
+
+/// Copy a 32-byte hash out of a raw pointer
+unsafe fn read_hash(ptr: *const u8) -> Hash {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(std::slice::from_raw_parts(ptr, 32));
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    #[test]
+    fn test_ffi_verify_proof() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"a").unwrap();
+        tree.append(b"b").unwrap();
+        tree.append(b"c").unwrap();
+
+        let root = tree.root();
+        let proof = tree.prove(2).unwrap();
+        let flat: Vec<u8> = proof.siblings.concat();
+
+        let valid = unsafe {
+            imt_verify_proof(
+                proof.leaf.as_ptr(),
+                proof.leaf_index,
+                flat.as_ptr(),
+                proof.siblings.len(),
+                root.as_ptr(),
+            )
+        };
+        assert!(valid);
+
+        let wrong_index = unsafe {
+            imt_verify_proof(
+                proof.leaf.as_ptr(),
+                1,
+                flat.as_ptr(),
+                proof.siblings.len(),
+                root.as_ptr(),
+            )
+        };
+        assert!(!wrong_index);
+
+        let null_root = unsafe {
+            imt_verify_proof(
+                proof.leaf.as_ptr(),
+                proof.leaf_index,
+                flat.as_ptr(),
+                proof.siblings.len(),
+                std::ptr::null(),
+            )
+        };
+        assert!(!null_root);
+    }
+}
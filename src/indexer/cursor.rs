@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use crate::errors::IndexerResult;
+
+/// High-water mark of fully processed slots, persisted to a small file.
+///
+/// A slot counts as processed once an event from a later slot arrives, so
+/// several transactions sharing a slot are all handled before it is marked.
+#[derive(Debug)]
+pub struct SlotCursor {
+    processed: Option<u64>,
+    current: Option<u64>,
+    path: PathBuf,
+}
+
+impl SlotCursor {
+    /// Open the cursor stored at `path`, starting fresh if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let processed = if path.exists() {
+            bincode::deserialize(&std::fs::read(&path)?)?
+        } else {
+            None
+        };
+        Ok(Self {
+            processed,
+            current: None,
+            path,
+        })
+    }
+
+    /// Last slot known to be fully processed
+    pub fn processed_slot(&self) -> Option<u64> {
+        self.processed
+    }
+
+    /// Check whether events from `slot` were already handled
+    pub fn is_processed(&self, slot: u64) -> bool {
+        self.processed.is_some_and(|processed| slot <= processed)
+    }
+
+    /// Record that an event from `slot` was handled, persisting the
+    /// high-water mark when an earlier slot becomes complete
+    pub fn observe(&mut self, slot: u64) -> IndexerResult<()> {
+        match self.current {
+            Some(current) if slot > current => {
+                self.processed = Some(self.processed.map_or(current, |p| p.max(current)));
+                self.current = Some(slot);
+                self.save()
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.current = Some(slot);
+                Ok(())
+            }
+        }
+    }
+
+    /// Write the high-water mark to its file
+    pub fn save(&self) -> IndexerResult<()> {
+        let data = bincode::serialize(&self.processed)?;
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
@@ -1,16 +1,135 @@
+use std::time::Duration;
+
 use anyhow::Result;
-use futures_util::StreamExt;
+use futures_util::{Stream, StreamExt};
 use helius_laserstream::{
-    grpc::{CommitmentLevel, SubscribeRequest, SubscribeRequestFilterTransactions},
-    subscribe, LaserstreamConfig,
+    grpc::{
+        CommitmentLevel, SubscribeRequest, SubscribeRequestFilterTransactions, SubscribeUpdate,
+    },
+    subscribe, ChannelOptions, LaserstreamConfig, LaserstreamError,
 };
 use serde_json::Value;
 
+/// Default number of consecutive read timeouts before giving up
+const DEFAULT_MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
+/// Default delay before the first reconnect after a stream error
+const DEFAULT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// Default cap on the delay between reconnects
+const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
 #[derive(Clone, Debug)]
 pub struct LaserstreamConfigWrapper {
     pub endpoint: String,
     pub api_key: String,
     pub program_ids: Vec<String>,
+    /// Timeout for establishing the gRPC connection
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time to wait for the next update before resubscribing
+    pub read_timeout: Option<Duration>,
+    /// Consecutive read timeouts tolerated before `run` fails
+    pub max_reconnect_attempts: u32,
+    /// Delay before the first reconnect after a stream error, doubled on
+    /// each further consecutive error
+    pub backoff_base: Duration,
+    /// Longest delay between reconnects
+    pub backoff_max: Duration,
+    /// Consecutive stream errors tolerated before `run` fails (`None` retries forever)
+    pub max_stream_retries: Option<u32>,
+}
+
+impl LaserstreamConfigWrapper {
+    /// Create a config with no timeouts
+    pub fn new(endpoint: String, api_key: String, program_ids: Vec<String>) -> Self {
+        Self {
+            endpoint,
+            api_key,
+            program_ids,
+            connect_timeout: None,
+            read_timeout: None,
+            max_reconnect_attempts: DEFAULT_MAX_RECONNECT_ATTEMPTS,
+            backoff_base: DEFAULT_BACKOFF_BASE,
+            backoff_max: DEFAULT_BACKOFF_MAX,
+            max_stream_retries: None,
+        }
+    }
+
+    /// Set the connection timeout
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the read timeout; a stalled stream is treated as transient and resubscribed
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how many consecutive read timeouts are tolerated
+    pub fn with_max_reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.max_reconnect_attempts = attempts;
+        self
+    }
+
+    /// Set the exponential backoff between reconnects after stream errors
+    pub fn with_reconnect_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff_base = base;
+        self.backoff_max = max;
+        self
+    }
+
+    /// Set how many consecutive stream errors are tolerated (`None` retries forever)
+    pub fn with_max_stream_retries(mut self, retries: Option<u32>) -> Self {
+        self.max_stream_retries = retries;
+        self
+    }
+
+    /// Delay before reconnecting after `failures` consecutive stream errors
+    fn backoff_delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.backoff_base
+            .saturating_mul(factor)
+            .min(self.backoff_max)
+    }
+
+    /// Check the settings that no reconnect can fix
+    fn validate(&self) -> Result<()> {
+        if self.endpoint.is_empty() {
+            return Err(anyhow::anyhow!("Laserstream endpoint is empty"));
+        }
+        if self.backoff_base > self.backoff_max {
+            return Err(anyhow::anyhow!(
+                "Laserstream backoff base {:?} exceeds its maximum {:?}",
+                self.backoff_base,
+                self.backoff_max
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build the SDK config, passing the connect timeout through to the
+    /// gRPC channel.
+    ///
+    /// `read_timeout` and `max_reconnect_attempts` are enforced by `run`
+    /// itself: the SDK's per-RPC deadline would cut off a healthy
+    /// long-lived stream, and its reconnect limit counts connection
+    /// failures rather than stalled reads.
+    pub fn to_laserstream_config(&self) -> LaserstreamConfig {
+        let channel_options = ChannelOptions {
+            connect_timeout_secs: self.connect_timeout.map(duration_to_secs),
+            ..Default::default()
+        };
+        LaserstreamConfig::new(self.endpoint.clone(), self.api_key.clone())
+            .with_channel_options(channel_options)
+    }
+}
+
+/// Round a duration up to whole seconds, never below one
+fn duration_to_secs(duration: Duration) -> u64 {
+    let secs = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+    secs.max(1)
 }
 
 pub struct LaserstreamEvent {
@@ -26,13 +145,13 @@ impl LaserstreamClient {
         Self { config }
     }
 
-    pub async fn run<F>(&self, mut on_event: F) -> Result<()>
+    pub async fn run<F>(&self, on_event: F) -> Result<()>
     where
         F: FnMut(LaserstreamEvent) + Send + 'static,
     {
-        // Build Laserstream config
-        let ls_config =
-            LaserstreamConfig::new(self.config.endpoint.clone(), self.config.api_key.clone());
+        // Build Laserstream config; a bad config is fatal rather than retried
+        self.config.validate()?;
+        let ls_config = self.config.to_laserstream_config();
 
         // Build subscription request for transactions
         let request = SubscribeRequest {
@@ -60,41 +179,260 @@ impl LaserstreamClient {
             self.config.endpoint
         );
 
-        // Subscribe and get stream
-        let (stream, _handle) = subscribe(ls_config, request);
-        tokio::pin!(stream);
-
-        // Process updates
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(update) => {
-                    // Access the update_oneof field which contains the actual update data
-                    match &update.update_oneof {
-                        // Serialize transaction updates to JSON and forward
-                        Some(
-                            helius_laserstream::grpc::subscribe_update::UpdateOneof::Transaction(
-                                tx,
-                            ),
-                        ) => {
-                            // Build a minimal JSON representation without requiring Serialize on SDK types
-                            let json = serde_json::json!({
-                                "type": "transaction",
-                                "debug": format!("{:?}", tx),
-                            });
-                            on_event(LaserstreamEvent { value: json });
+        // Subscribe (again after each read timeout or stream error) and process updates
+        self.drive(|| subscribe(ls_config.clone(), request.clone()).0, on_event)
+            .await
+    }
+
+    /// Consume updates from streams produced by `open`, resubscribing whenever
+    /// no update arrives within the configured read timeout, or after an
+    /// exponential backoff when the stream yields an error.
+    async fn drive<S, O, F>(&self, mut open: O, mut on_event: F) -> Result<()>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, LaserstreamError>>,
+        O: FnMut() -> S,
+        F: FnMut(LaserstreamEvent),
+    {
+        let mut timeouts = 0;
+        let mut failures = 0u32;
+
+        loop {
+            let stream = open();
+            tokio::pin!(stream);
+
+            loop {
+                let next = match self.config.read_timeout {
+                    Some(limit) => match tokio::time::timeout(limit, stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            timeouts += 1;
+                            if timeouts > self.config.max_reconnect_attempts {
+                                return Err(anyhow::anyhow!(
+                                    "Laserstream read timed out {timeouts} times in a row"
+                                ));
+                            }
+                            log::warn!(
+                                "No Laserstream update within {limit:?}, resubscribing (attempt {timeouts})"
+                            );
+                            break;
                         }
-                        _ => {
-                            log::debug!("Received non-transaction update");
+                    },
+                    None => stream.next().await,
+                };
+
+                let Some(result) = next else {
+                    return Ok(());
+                };
+                match result {
+                    Ok(update) => {
+                        timeouts = 0;
+                        failures = 0;
+                        Self::handle_update(update, &mut on_event);
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        if self
+                            .config
+                            .max_stream_retries
+                            .is_some_and(|max| failures > max)
+                        {
+                            log::error!("Laserstream error: {}", e);
+                            return Err(anyhow::anyhow!(
+                                "Laserstream error after {failures} consecutive failures: {e}"
+                            ));
                         }
+                        let delay = self.config.backoff_delay(failures);
+                        log::warn!(
+                            "Laserstream error: {e}; reconnecting in {delay:?} (attempt {failures})"
+                        );
+                        tokio::time::sleep(delay).await;
+                        break;
                     }
                 }
-                Err(e) => {
-                    log::error!("Laserstream error: {}", e);
-                    return Err(anyhow::anyhow!("Laserstream error: {}", e));
-                }
             }
         }
+    }
 
-        Ok(())
+    /// Forward a single update to the event callback
+    fn handle_update<F>(update: SubscribeUpdate, on_event: &mut F)
+    where
+        F: FnMut(LaserstreamEvent),
+    {
+        // Access the update_oneof field which contains the actual update data
+        match &update.update_oneof {
+            // Serialize transaction updates to JSON and forward
+            Some(helius_laserstream::grpc::subscribe_update::UpdateOneof::Transaction(tx)) => {
+                // Build a minimal JSON representation without requiring Serialize on SDK types
+                let json = serde_json::json!({
+                    "type": "transaction",
+                    "debug": format!("{:?}", tx),
+                });
+                on_event(LaserstreamEvent { value: json });
+            }
+            _ => {
+                log::debug!("Received non-transaction update");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream::{self, BoxStream};
+    use helius_laserstream::grpc::{subscribe_update::UpdateOneof, SubscribeUpdateTransaction};
+
+    type MockStream = BoxStream<'static, std::result::Result<SubscribeUpdate, LaserstreamError>>;
+
+    fn config() -> LaserstreamConfigWrapper {
+        LaserstreamConfigWrapper::new(
+            "http://localhost:1".to_string(),
+            "key".to_string(),
+            vec!["program".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_timeouts_passed_to_channel_options() {
+        let ls_config = config()
+            .with_connect_timeout(Duration::from_millis(2500))
+            .with_read_timeout(Duration::from_millis(1500))
+            .with_max_reconnect_attempts(4)
+            .to_laserstream_config();
+
+        assert_eq!(ls_config.channel_options.connect_timeout_secs, Some(3));
+        // Read stalls are handled by `drive`, not by SDK deadlines or retries
+        assert_eq!(ls_config.channel_options.timeout_secs, None);
+        assert_eq!(ls_config.max_reconnect_attempts, None);
+
+        let defaults = config().to_laserstream_config();
+        assert_eq!(defaults.channel_options.connect_timeout_secs, None);
+        assert_eq!(defaults.channel_options.timeout_secs, None);
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_triggers_resubscribe() {
+        let client = LaserstreamClient::new(config().with_read_timeout(Duration::from_millis(20)));
+        let mut subscriptions = 0;
+        let mut events = 0;
+
+        let result = client
+            .drive(
+                || -> MockStream {
+                    subscriptions += 1;
+                    if subscriptions == 1 {
+                        // A hung connection that never yields
+                        stream::pending().boxed()
+                    } else {
+                        let update = SubscribeUpdate {
+                            update_oneof: Some(UpdateOneof::Transaction(
+                                SubscribeUpdateTransaction::default(),
+                            )),
+                            ..Default::default()
+                        };
+                        stream::iter(vec![Ok(update)]).boxed()
+                    }
+                },
+                |_| events += 1,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(subscriptions, 2);
+        assert_eq!(events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_gives_up_after_max_attempts() {
+        let client = LaserstreamClient::new(
+            config()
+                .with_read_timeout(Duration::from_millis(5))
+                .with_max_reconnect_attempts(2),
+        );
+        let mut subscriptions = 0;
+
+        let result = client
+            .drive(
+                || -> MockStream {
+                    subscriptions += 1;
+                    stream::pending().boxed()
+                },
+                |_| {},
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(subscriptions, 3);
+    }
+
+    fn transaction_update() -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Transaction(
+                SubscribeUpdateTransaction::default(),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors_reconnect_with_backoff() {
+        let client = LaserstreamClient::new(
+            config().with_reconnect_backoff(Duration::from_millis(1), Duration::from_millis(4)),
+        );
+        let mut subscriptions = 0;
+        let mut events = 0;
+
+        let result = client
+            .drive(
+                || -> MockStream {
+                    subscriptions += 1;
+                    if subscriptions <= 3 {
+                        stream::iter(vec![Err(LaserstreamError::StreamEnded)]).boxed()
+                    } else {
+                        stream::iter(vec![Ok(transaction_update()), Ok(transaction_update())])
+                            .boxed()
+                    }
+                },
+                |_| events += 1,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(subscriptions, 4);
+        assert_eq!(events, 2);
+
+        let delays: Vec<_> = (1..=4).map(|n| client.config.backoff_delay(n)).collect();
+        assert_eq!(delays, [1, 2, 4, 4].map(Duration::from_millis).to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors_give_up_after_max_retries() {
+        let client = LaserstreamClient::new(
+            config()
+                .with_reconnect_backoff(Duration::from_millis(1), Duration::from_millis(1))
+                .with_max_stream_retries(Some(2)),
+        );
+        let mut subscriptions = 0;
+
+        let result = client
+            .drive(
+                || -> MockStream {
+                    subscriptions += 1;
+                    stream::iter(vec![Err(LaserstreamError::StreamEnded)]).boxed()
+                },
+                |_| {},
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(subscriptions, 3);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_is_fatal() {
+        let client = LaserstreamClient::new(
+            config().with_reconnect_backoff(Duration::from_secs(2), Duration::from_secs(1)),
+        );
+        assert!(client.run(|_| {}).await.is_err());
     }
 }
@@ -1,5 +1,8 @@
+pub mod cursor;
 pub mod events;
 pub mod laserstream_client;
 pub mod pipeline;
+pub mod root_poster;
 pub mod serializers;
 pub mod service;
+pub mod signatures;
@@ -1,34 +1,124 @@
-use crate::errors::IndexerResult;
+use crate::errors::{IndexerError, IndexerResult};
 use crate::storage::merkle_service::MerkleTreeService;
+use crate::tree::Commitment;
 use serde_json::Value;
+use std::collections::BTreeMap;
 
+use super::cursor::SlotCursor;
 use super::events::{EventRegistry, EVENT_SPECS};
 use super::serializers::SerializerRegistry;
+use super::signatures::SeenSignatures;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine as _;
 
+/// Transaction signatures remembered for deduplication by default
+pub const DEFAULT_SEEN_SIGNATURES: usize = 10_000;
+
+/// What the pipeline did with an incoming event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventOutcome {
+    /// The event carried nothing for our programs
+    Ignored,
+    /// Event data for our program was found but not inserted
+    Skipped(String),
+    /// An out-of-order commitment was held until the gap before it fills
+    Buffered(u64),
+    /// A commitment was inserted at this leaf index
+    Inserted(usize),
+}
+
+impl EventOutcome {
+    /// Combine outcomes from several log lines of one transaction.
+    /// An insert takes precedence over buffering, then skipping, then ignoring.
+    fn merge(self, other: EventOutcome) -> EventOutcome {
+        match (self, other) {
+            (_, inserted @ EventOutcome::Inserted(_)) => inserted,
+            (inserted @ EventOutcome::Inserted(_), _) => inserted,
+            (_, buffered @ EventOutcome::Buffered(_)) => buffered,
+            (buffered @ EventOutcome::Buffered(_), _) => buffered,
+            (skipped @ EventOutcome::Skipped(_), _) => skipped,
+            (EventOutcome::Ignored, other) => other,
+        }
+    }
+}
+
 pub struct IndexerPipeline {
     registry: SerializerRegistry,
     merkle: MerkleTreeService,
-    program_id: String, // Filter events for this program
+    program_ids: Vec<String>, // Filter events for these programs
     events: EventRegistry,
+    // Out-of-order commitments keyed by commitment_index (capacity 0 = disabled)
+    pending: BTreeMap<u64, Commitment>,
+    pending_capacity: usize,
+    next_commitment_index: u64,
+    // Skips events from slots already processed before a reconnect
+    slot_cursor: Option<SlotCursor>,
+    // Recently handled transactions, so redeliveries are not applied twice
+    seen_signatures: SeenSignatures,
+    // Slot with inserts not yet checkpointed; recorded once a later slot starts
+    unchecked_slot: Option<u64>,
 }
 
 impl IndexerPipeline {
     pub fn new(
         registry: SerializerRegistry,
         merkle: MerkleTreeService,
-        program_id: String,
+        program_ids: Vec<String>,
     ) -> Self {
         Self {
             registry,
             merkle,
-            program_id,
+            program_ids,
             events: EventRegistry::new(EVENT_SPECS),
+            pending: BTreeMap::new(),
+            pending_capacity: 0,
+            next_commitment_index: 0,
+            slot_cursor: None,
+            seen_signatures: SeenSignatures::new(DEFAULT_SEEN_SIGNATURES),
+            unchecked_slot: None,
         }
     }
 
-    pub fn handle_event(&mut self, evt: Value) -> IndexerResult<()> {
+    /// Hold up to `capacity` out-of-order commitments, inserting them by
+    /// `commitment_index` once the gap before them fills.
+    ///
+    /// Ordering resumes from the current tree size. Commitments arriving
+    /// while the buffer is full are dropped with a warning.
+    pub fn with_reorder_buffer(mut self, capacity: usize) -> Self {
+        self.pending_capacity = capacity;
+        self.next_commitment_index = self.merkle.len() as u64;
+        self
+    }
+
+    /// Skip events from slots at or below the cursor's persisted high-water
+    /// mark, so a replay after reconnect is not processed twice.
+    pub fn with_slot_cursor(mut self, cursor: SlotCursor) -> Self {
+        self.slot_cursor = Some(cursor);
+        self
+    }
+
+    /// Remember up to `capacity` transaction signatures (default
+    /// `DEFAULT_SEEN_SIGNATURES`) and skip events whose transaction was
+    /// already handled. The least recently seen signature is forgotten
+    /// first; a capacity of 0 disables deduplication.
+    pub fn with_signature_dedup(mut self, capacity: usize) -> Self {
+        self.seen_signatures = SeenSignatures::new(capacity);
+        self
+    }
+
+    /// Process one Laserstream event. When a transaction carries several
+    /// events, the last insert (or else the first skip) is reported.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(signature = evt
+                .pointer("/result/transaction/signature")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default())
+        )
+    )]
+    pub fn handle_event(&mut self, evt: Value) -> IndexerResult<EventOutcome> {
         // Laserstream format:
         // result: {
         //   transaction: { signature, slot, meta: { logMessages: [...] } },
@@ -41,68 +131,659 @@ impl IndexerPipeline {
             .and_then(|tx| tx.get("meta"));
 
         if tx_meta.is_none() {
-            return Ok(());
+            return Ok(EventOutcome::Ignored);
+        }
+
+        let slot = evt
+            .pointer("/result/slot")
+            .or_else(|| evt.pointer("/result/transaction/slot"))
+            .and_then(Value::as_u64);
+        if let (Some(cursor), Some(slot)) = (&self.slot_cursor, slot) {
+            if cursor.is_processed(slot) {
+                log::debug!("skipping replayed event from slot {slot}");
+                return Ok(EventOutcome::Skipped(format!(
+                    "slot {slot} already processed"
+                )));
+            }
+        }
+
+        let signature = evt
+            .pointer("/result/transaction/signature")
+            .and_then(Value::as_str);
+        if let Some(signature) = signature {
+            if self.seen_signatures.contains(signature) {
+                log::debug!("skipping redelivered transaction {signature}");
+                return Ok(EventOutcome::Skipped(format!(
+                    "transaction {signature} already processed"
+                )));
+            }
+        }
+
+        // A later slot has started, so the previous one is complete
+        if let Some(slot) = slot {
+            if self.unchecked_slot.is_some_and(|pending| pending < slot) {
+                self.checkpoint_slot()?;
+            }
         }
 
         // Extract log messages (events are emitted here)
-        if let Some(logs) = tx_meta
+        let outcome = match tx_meta
             .and_then(|meta| meta.get("logMessages"))
             .and_then(|logs| logs.as_array())
         {
-            self.process_logs(logs)?;
+            Some(logs) => self.process_logs(logs)?,
+            None => EventOutcome::Ignored,
+        };
+
+        // Checkpoint the slot once it ends so a reorg past it can be rolled back
+        if let (EventOutcome::Inserted(_), Some(slot)) = (&outcome, slot) {
+            self.unchecked_slot = self.unchecked_slot.max(Some(slot));
+        }
+        if let (Some(cursor), Some(slot)) = (self.slot_cursor.as_mut(), slot) {
+            cursor.observe(slot)?;
         }
+        if let Some(signature) = signature {
+            self.seen_signatures.insert(signature);
+        }
+        Ok(outcome)
+    }
 
+    /// Checkpoint the slot whose inserts are not yet checkpointed, e.g.
+    /// before shutting down. Slots are otherwise checkpointed once an event
+    /// from a later slot arrives.
+    pub fn checkpoint_slot(&mut self) -> IndexerResult<()> {
+        if let Some(slot) = self.unchecked_slot.take() {
+            self.merkle.checkpoint(slot)?;
+        }
         Ok(())
     }
 
-    fn process_logs(&mut self, logs: &[Value]) -> IndexerResult<()> {
-        // Look for our program's event format: "Program data: <base64_data>"
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(lines = logs.len())))]
+    fn process_logs(&mut self, logs: &[Value]) -> IndexerResult<EventOutcome> {
+        let mut outcome = EventOutcome::Ignored;
+
+        // Look for our programs' event format: "Program data: <program_id><base64_data>"
         for log in logs {
             if let Some(log_str) = log.as_str() {
                 // Look for our event marker
                 if let Some(event_data) = log_str.strip_prefix("Program data: ") {
-                    if let Some(event_data_trimmed) = event_data.strip_prefix(&self.program_id) {
+                    if let Some((program_id, event_data_trimmed)) = self.match_program(event_data) {
+                        log::debug!("event data from program {program_id}");
                         // Decode the event data from base64
                         match BASE64_STANDARD.decode(event_data_trimmed.trim()) {
                             Ok(bytes) => {
                                 log::debug!("decoded event bytes: {} bytes", bytes.len());
-                                self.process_event_bytes(&bytes)?;
+                                outcome = outcome.merge(self.process_event_bytes(&bytes)?);
                             }
                             Err(e) => {
                                 log::warn!("failed to decode base64 event data: {e}");
+                                outcome = outcome.merge(EventOutcome::Skipped(format!(
+                                    "invalid base64 event data: {e}"
+                                )));
                             }
                         }
                     }
                 }
             }
         }
-        Ok(())
+        Ok(outcome)
+    }
+
+    /// Find the program whose id prefixes `event_data`, returning it and the
+    /// data that follows. The longest id wins if several match.
+    fn match_program<'a>(&self, event_data: &'a str) -> Option<(&str, &'a str)> {
+        self.program_ids
+            .iter()
+            .filter_map(|program_id| {
+                event_data
+                    .strip_prefix(program_id.as_str())
+                    .map(|rest| (program_id.as_str(), rest))
+            })
+            .max_by_key(|(program_id, _)| program_id.len())
     }
 
-    fn process_event_bytes(&mut self, bytes: &[u8]) -> IndexerResult<()> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(event = tracing::field::Empty))
+    )]
+    fn process_event_bytes(&mut self, bytes: &[u8]) -> IndexerResult<EventOutcome> {
         // First, try identify against declarative event specs
         if let Some(parsed) = self.events.parse(bytes)? {
             log::info!("identified event: {}", parsed.spec.name);
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("event", parsed.spec.name);
             // If you want to route some events directly to commitments, implement conversion here
             // Fallback to registry-based parsing for commitment extraction
         }
 
         // Try to deserialize into a Commitment via registry
         match self.registry.parse(bytes) {
+            Ok(Some(commitment)) if self.pending_capacity > 0 => self.insert_in_order(commitment),
             Ok(Some(commitment)) => {
                 log::info!("deserialized commitment, inserting to tree");
-                self.merkle.insert_commitment(&commitment)?;
-                self.merkle.save()?;
-                Ok(())
+                match self.insert_unspent(&commitment)? {
+                    Some(index) => {
+                        self.merkle.save()?;
+                        Ok(EventOutcome::Inserted(index))
+                    }
+                    None => Ok(EventOutcome::Skipped("nullifier already used".to_string())),
+                }
             }
             Ok(None) => {
                 log::debug!("no matching serializer for event bytes");
-                Ok(())
+                Ok(EventOutcome::Skipped(
+                    "no matching serializer for event bytes".to_string(),
+                ))
             }
             Err(e) => {
                 log::warn!("failed to parse event bytes: {e}");
-                Ok(())
+                Ok(EventOutcome::Skipped(format!(
+                    "failed to parse event bytes: {e}"
+                )))
             }
         }
     }
+
+    /// Insert a commitment once every lower `commitment_index` has been
+    /// inserted, buffering it until then
+    fn insert_in_order(&mut self, commitment: Commitment) -> IndexerResult<EventOutcome> {
+        let commitment_index = commitment.commitment_index;
+        if commitment_index < self.next_commitment_index
+            || (commitment_index > self.next_commitment_index
+                && self.pending.contains_key(&commitment_index))
+        {
+            log::warn!("dropping duplicate commitment {commitment_index}");
+            return Ok(EventOutcome::Skipped(format!(
+                "commitment {commitment_index} already seen"
+            )));
+        }
+
+        if commitment_index > self.next_commitment_index {
+            if self.pending.len() >= self.pending_capacity {
+                log::warn!(
+                    "reorder buffer full ({} entries), dropping commitment {commitment_index}",
+                    self.pending.len()
+                );
+                return Ok(EventOutcome::Skipped(format!(
+                    "reorder buffer full, dropped commitment {commitment_index}"
+                )));
+            }
+            log::debug!(
+                "buffering commitment {commitment_index}, waiting for {}",
+                self.next_commitment_index
+            );
+            self.pending.insert(commitment_index, commitment);
+            return Ok(EventOutcome::Buffered(commitment_index));
+        }
+
+        // The gap is closed: insert this commitment and any that were waiting
+        // on it. Each stays buffered until its insert succeeds, so a failed
+        // insert is retried when the commitment is delivered again.
+        self.pending.insert(commitment_index, commitment);
+        let mut last = None;
+        while let Some(next) = self.pending.get(&self.next_commitment_index).cloned() {
+            last = self.insert_unspent(&next)?.or(last);
+            self.pending.remove(&self.next_commitment_index);
+            self.next_commitment_index += 1;
+        }
+        self.merkle.save()?;
+        Ok(match last {
+            Some(index) => EventOutcome::Inserted(index),
+            None => EventOutcome::Skipped("nullifier already used".to_string()),
+        })
+    }
+
+    /// Insert a commitment, or return `None` if its nullifier was already used
+    fn insert_unspent(&mut self, commitment: &Commitment) -> IndexerResult<Option<usize>> {
+        match self.merkle.insert_commitment(commitment) {
+            Ok(index) => Ok(Some(index)),
+            Err(IndexerError::NullifierAlreadyUsed) => {
+                log::warn!(
+                    "dropping commitment {} with a spent nullifier",
+                    commitment.commitment_index
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::serializers::InstructionSerializer;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    const PROGRAM_ID: &str = "TestProgram1111";
+    const OTHER_PROGRAM_ID: &str = "OtherProgram2222";
+    const DISCRIMINATOR: [u8; 8] = [9, 9, 9, 9, 9, 9, 9, 9];
+
+    /// Parses a commitment encoded with `to_bytes` after an 8-byte discriminator
+    struct RawCommitmentSerializer;
+
+    impl InstructionSerializer for RawCommitmentSerializer {
+        fn serialize(&self, ix_data: &[u8]) -> IndexerResult<Option<Commitment>> {
+            if ix_data.len() < 8 {
+                return Err(IndexerError::InvalidData("missing discriminator".into()));
+            }
+            Commitment::from_bytes(&ix_data[8..]).map(Some)
+        }
+    }
+
+    fn pipeline(dir: &TempDir) -> IndexerPipeline {
+        let mut registry = SerializerRegistry::new();
+        registry.register(DISCRIMINATOR.to_vec(), Box::new(RawCommitmentSerializer));
+        let merkle = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+        IndexerPipeline::new(
+            registry,
+            merkle,
+            vec![PROGRAM_ID.to_string(), OTHER_PROGRAM_ID.to_string()],
+        )
+    }
+
+    fn event_with_logs(logs: Vec<String>) -> Value {
+        json!({ "result": { "transaction": { "meta": { "logMessages": logs } } } })
+    }
+
+    fn program_data(bytes: &[u8]) -> String {
+        format!(
+            "Program data: {PROGRAM_ID}{}",
+            BASE64_STANDARD.encode(bytes)
+        )
+    }
+
+    #[test]
+    fn test_outcome_ignored() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        let no_meta = json!({ "result": {} });
+        assert_eq!(
+            pipeline.handle_event(no_meta).unwrap(),
+            EventOutcome::Ignored
+        );
+
+        let other_logs = event_with_logs(vec!["Program log: hello".to_string()]);
+        assert_eq!(
+            pipeline.handle_event(other_logs).unwrap(),
+            EventOutcome::Ignored
+        );
+    }
+
+    #[test]
+    fn test_outcome_skipped() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        let unknown = event_with_logs(vec![program_data(&[1, 2, 3, 4, 5, 6, 7, 8, 0])]);
+        assert!(matches!(
+            pipeline.handle_event(unknown).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+
+        let mut truncated = DISCRIMINATOR.to_vec();
+        truncated.extend_from_slice(&[0u8; 10]);
+        let malformed = event_with_logs(vec![program_data(&truncated)]);
+        assert!(matches!(
+            pipeline.handle_event(malformed).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+        assert!(pipeline.merkle.is_empty());
+    }
+
+    #[test]
+    fn test_outcome_inserted() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        for expected in 0..2 {
+            let commitment = Commitment::new(
+                1,
+                expected as u64,
+                [1u8; 32],
+                [2u8; 32],
+                [expected as u8; 32],
+            );
+            let mut bytes = DISCRIMINATOR.to_vec();
+            bytes.extend(commitment.to_bytes());
+
+            let outcome = pipeline
+                .handle_event(event_with_logs(vec![program_data(&bytes)]))
+                .unwrap();
+            assert_eq!(outcome, EventOutcome::Inserted(expected));
+        }
+        assert_eq!(pipeline.merkle.len(), 2);
+    }
+
+    #[test]
+    fn test_spent_nullifier_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        let event = |commitment_index: u64| {
+            let commitment = Commitment::new(1, commitment_index, [1u8; 32], [2u8; 32], [3u8; 32]);
+            let mut bytes = DISCRIMINATOR.to_vec();
+            bytes.extend(commitment.to_bytes());
+            event_with_logs(vec![program_data(&bytes)])
+        };
+
+        assert_eq!(
+            pipeline.handle_event(event(0)).unwrap(),
+            EventOutcome::Inserted(0)
+        );
+        assert!(matches!(
+            pipeline.handle_event(event(1)).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+        assert_eq!(pipeline.merkle.len(), 1);
+    }
+
+    fn commitment_event(commitment_index: u64) -> Value {
+        let commitment = Commitment::new(
+            1,
+            commitment_index,
+            [commitment_index as u8; 32],
+            [2u8; 32],
+            [commitment_index as u8 + 100; 32],
+        );
+        let mut bytes = DISCRIMINATOR.to_vec();
+        bytes.extend(commitment.to_bytes());
+        event_with_logs(vec![program_data(&bytes)])
+    }
+
+    #[test]
+    fn test_reorder_buffer_applies_in_order() {
+        let dir = TempDir::new().unwrap();
+        let mut reordered = pipeline(&dir).with_reorder_buffer(4);
+
+        let outcomes: Vec<_> = [2u64, 1, 0]
+            .into_iter()
+            .map(|i| reordered.handle_event(commitment_event(i)).unwrap())
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                EventOutcome::Buffered(2),
+                EventOutcome::Buffered(1),
+                EventOutcome::Inserted(2),
+            ]
+        );
+
+        let in_order_dir = TempDir::new().unwrap();
+        let mut in_order = pipeline(&in_order_dir);
+        for i in 0..3 {
+            in_order.handle_event(commitment_event(i)).unwrap();
+        }
+        assert_eq!(reordered.merkle.root(), in_order.merkle.root());
+    }
+
+    #[test]
+    fn test_reorder_buffer_drops_when_full() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir).with_reorder_buffer(1);
+
+        assert_eq!(
+            pipeline.handle_event(commitment_event(1)).unwrap(),
+            EventOutcome::Buffered(1)
+        );
+        assert!(matches!(
+            pipeline.handle_event(commitment_event(2)).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+        assert_eq!(
+            pipeline.handle_event(commitment_event(0)).unwrap(),
+            EventOutcome::Inserted(1)
+        );
+        assert_eq!(pipeline.merkle.len(), 2);
+    }
+
+    #[test]
+    fn test_reorder_buffer_keeps_commitment_when_insert_fails() {
+        let dir = TempDir::new().unwrap();
+        let mut registry = SerializerRegistry::new();
+        registry.register(DISCRIMINATOR.to_vec(), Box::new(RawCommitmentSerializer));
+        // Room for two leaves, so the third insert fails
+        let merkle = MerkleTreeService::new(dir.path(), Some(1)).unwrap();
+        let mut pipeline = IndexerPipeline::new(registry, merkle, vec![PROGRAM_ID.to_string()])
+            .with_reorder_buffer(4);
+
+        for i in [2u64, 1] {
+            assert_eq!(
+                pipeline.handle_event(commitment_event(i)).unwrap(),
+                EventOutcome::Buffered(i)
+            );
+        }
+        assert!(pipeline.handle_event(commitment_event(0)).is_err());
+        assert_eq!(pipeline.merkle.len(), 2);
+        assert_eq!(pipeline.next_commitment_index, 2);
+        assert!(pipeline.pending.contains_key(&2));
+
+        // Redelivery retries the insert instead of dropping it as a duplicate
+        assert!(pipeline.handle_event(commitment_event(2)).is_err());
+        assert!(pipeline.pending.contains_key(&2));
+    }
+
+    #[test]
+    fn test_events_from_every_program_are_inserted() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        let line = |program_id: &str, commitment_index: u64| {
+            let commitment = Commitment::new(
+                1,
+                commitment_index,
+                [commitment_index as u8; 32],
+                [2u8; 32],
+                [commitment_index as u8 + 100; 32],
+            );
+            let mut bytes = DISCRIMINATOR.to_vec();
+            bytes.extend(commitment.to_bytes());
+            format!(
+                "Program data: {program_id}{}",
+                BASE64_STANDARD.encode(bytes)
+            )
+        };
+
+        assert_eq!(
+            pipeline
+                .handle_event(event_with_logs(vec![line(PROGRAM_ID, 0)]))
+                .unwrap(),
+            EventOutcome::Inserted(0)
+        );
+        assert_eq!(
+            pipeline
+                .handle_event(event_with_logs(vec![line(OTHER_PROGRAM_ID, 1)]))
+                .unwrap(),
+            EventOutcome::Inserted(1)
+        );
+        assert_eq!(
+            pipeline
+                .handle_event(event_with_logs(vec![line("UnknownProgram", 2)]))
+                .unwrap(),
+            EventOutcome::Ignored
+        );
+        assert_eq!(pipeline.merkle.len(), 2);
+    }
+
+    #[test]
+    fn test_redelivered_transaction_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        let signed = |signature: &str, commitment_index: u64| {
+            let mut evt = commitment_event(commitment_index);
+            evt["result"]["transaction"]["signature"] = json!(signature);
+            evt
+        };
+
+        assert_eq!(
+            pipeline.handle_event(signed("sig1", 0)).unwrap(),
+            EventOutcome::Inserted(0)
+        );
+        assert!(matches!(
+            pipeline.handle_event(signed("sig1", 0)).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+        assert_eq!(pipeline.merkle.len(), 1);
+
+        assert_eq!(
+            pipeline.handle_event(signed("sig2", 1)).unwrap(),
+            EventOutcome::Inserted(1)
+        );
+        assert_eq!(pipeline.merkle.len(), 2);
+    }
+
+    fn slot_event(slot: u64, commitment_index: u64) -> Value {
+        let mut evt = commitment_event(commitment_index);
+        evt["result"]["slot"] = json!(slot);
+        evt
+    }
+
+    #[test]
+    fn test_slot_cursor_skips_replay() {
+        let dir = TempDir::new().unwrap();
+        let cursor_path = dir.path().join("cursor.dat");
+        let mut pipeline = pipeline(&dir).with_slot_cursor(SlotCursor::open(&cursor_path).unwrap());
+
+        assert_eq!(
+            pipeline.handle_event(slot_event(100, 0)).unwrap(),
+            EventOutcome::Inserted(0)
+        );
+        // Another transaction in the same slot is still processed
+        assert_eq!(
+            pipeline.handle_event(slot_event(100, 1)).unwrap(),
+            EventOutcome::Inserted(1)
+        );
+        assert_eq!(
+            pipeline.handle_event(slot_event(101, 2)).unwrap(),
+            EventOutcome::Inserted(2)
+        );
+
+        // Reconnect replays slot 100
+        assert!(matches!(
+            pipeline.handle_event(slot_event(100, 0)).unwrap(),
+            EventOutcome::Skipped(_)
+        ));
+        assert_eq!(pipeline.merkle.len(), 3);
+
+        // The high-water slot survives a restart
+        let reopened = SlotCursor::open(&cursor_path).unwrap();
+        assert_eq!(reopened.processed_slot(), Some(100));
+        assert!(reopened.is_processed(100));
+        assert!(!reopened.is_processed(101));
+    }
+
+    #[test]
+    fn test_inserts_checkpoint_their_slot() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+
+        pipeline.handle_event(slot_event(100, 0)).unwrap();
+        pipeline.handle_event(slot_event(101, 1)).unwrap();
+        pipeline.handle_event(slot_event(101, 2)).unwrap();
+        let second_root = pipeline.merkle.root();
+        pipeline.handle_event(slot_event(102, 3)).unwrap();
+
+        let slots = |pipeline: &IndexerPipeline| -> Vec<u64> {
+            pipeline
+                .merkle
+                .checkpoints()
+                .iter()
+                .map(|checkpoint| checkpoint.slot)
+                .collect()
+        };
+        // Slot 102 is still open, and 101 was checkpointed once after both inserts
+        assert_eq!(slots(&pipeline), vec![100, 101]);
+        assert_eq!(pipeline.merkle.checkpoints()[1].next_index, 3);
+
+        pipeline.checkpoint_slot().unwrap();
+        assert_eq!(slots(&pipeline), vec![100, 101, 102]);
+
+        let restored = pipeline.merkle.rollback_to_slot(101).unwrap();
+        assert_eq!(restored.next_index, 3);
+        assert_eq!(pipeline.merkle.len(), 3);
+        assert_eq!(pipeline.merkle.root(), second_root);
+    }
+
+    /// Span name and its string fields
+    #[cfg(feature = "tracing")]
+    type RecordedSpan = (String, Vec<(String, String)>);
+
+    /// Records the name and string fields of every span created
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: std::sync::Arc<std::sync::Mutex<Vec<RecordedSpan>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            struct Fields(Vec<(String, String)>);
+            impl tracing::field::Visit for Fields {
+                fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                    self.0.push((field.name().to_string(), value.to_string()));
+                }
+                fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+            }
+
+            let mut fields = Fields(Vec::new());
+            attrs.record(&mut fields);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((attrs.metadata().name().to_string(), fields.0));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, _: &tracing::Event<'_>) {}
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_span_per_event() {
+        let dir = TempDir::new().unwrap();
+        let mut pipeline = pipeline(&dir);
+        let recorder = SpanRecorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            for signature in ["sig1", "sig2"] {
+                let evt = json!({ "result": { "transaction": {
+                    "signature": signature,
+                    "meta": { "logMessages": ["Program log: hello"] }
+                } } });
+                pipeline.handle_event(evt).unwrap();
+            }
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let handled: Vec<_> = spans
+            .iter()
+            .filter(|(name, _)| name == "handle_event")
+            .collect();
+        assert_eq!(handled.len(), 2);
+        assert_eq!(
+            handled[0].1,
+            vec![("signature".to_string(), "sig1".to_string())]
+        );
+        assert_eq!(
+            handled[1].1,
+            vec![("signature".to_string(), "sig2".to_string())]
+        );
+        assert_eq!(
+            spans
+                .iter()
+                .filter(|(name, _)| name == "process_logs")
+                .count(),
+            2
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use crate::errors::IndexerResult;
+use crate::storage::merkle_service::MerkleTreeService;
+use crate::tree::Commitment;
+use crate::utils::Hash;
+
+/// Callback receiving `(leaf_count, root)` when a root is posted
+pub type RootCallback = Box<dyn FnMut(usize, Hash) + Send>;
+
+/// Batches root updates from a `MerkleTreeService` for posting on-chain.
+///
+/// The callback fires after every `cadence` inserts, or from `poll_idle`
+/// once inserts have been pending for the idle timeout. A root equal to the
+/// last one posted is never posted twice in a row.
+pub struct RootPoster {
+    service: MerkleTreeService,
+    callback: RootCallback,
+    cadence: usize,
+    idle_timeout: Option<Duration>,
+    pending: usize,
+    last_insert: Instant,
+    last_posted: Option<Hash>,
+}
+
+impl RootPoster {
+    /// Post after every `cadence` inserts (a cadence of 0 is treated as 1)
+    pub fn new(service: MerkleTreeService, cadence: usize, callback: RootCallback) -> Self {
+        Self {
+            service,
+            callback,
+            cadence: cadence.max(1),
+            idle_timeout: None,
+            pending: 0,
+            last_insert: Instant::now(),
+            last_posted: None,
+        }
+    }
+
+    /// Also post pending inserts once no insert has happened for `timeout`
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Insert a commitment, posting the root if the cadence is reached
+    pub fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<usize> {
+        let index = self.service.insert_commitment(commitment)?;
+        self.pending += 1;
+        self.last_insert = Instant::now();
+
+        if self.pending >= self.cadence {
+            self.flush();
+        }
+        Ok(index)
+    }
+
+    /// Post pending inserts if the idle timeout has elapsed.
+    /// Returns whether the callback fired.
+    pub fn poll_idle(&mut self) -> bool {
+        match self.idle_timeout {
+            Some(timeout) if self.pending > 0 && self.last_insert.elapsed() >= timeout => {
+                self.flush()
+            }
+            _ => false,
+        }
+    }
+
+    /// Post the current root now unless it was already posted.
+    /// Returns whether the callback fired.
+    pub fn flush(&mut self) -> bool {
+        self.pending = 0;
+        let root = self.service.root();
+        if self.last_posted == Some(root) {
+            return false;
+        }
+
+        (self.callback)(self.service.len(), root);
+        self.last_posted = Some(root);
+        true
+    }
+
+    /// Get the wrapped service
+    pub fn service(&self) -> &MerkleTreeService {
+        &self.service
+    }
+
+    /// Get the wrapped service mutably; inserts made through it are not
+    /// counted toward the cadence
+    pub fn service_mut(&mut self) -> &mut MerkleTreeService {
+        &mut self.service
+    }
+
+    /// Unwrap the service
+    pub fn into_inner(self) -> MerkleTreeService {
+        self.service
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    type Posted = Arc<Mutex<Vec<(usize, Hash)>>>;
+
+    fn commitment(i: u64) -> Commitment {
+        Commitment::new(1, i, [i as u8; 32], [2u8; 32], [i as u8 + 3; 32])
+    }
+
+    fn recording_poster(dir: &TempDir, cadence: usize) -> (RootPoster, Posted) {
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let sink = posted.clone();
+        let service = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+        let poster = RootPoster::new(
+            service,
+            cadence,
+            Box::new(move |len, root| sink.lock().unwrap().push((len, root))),
+        );
+        (poster, posted)
+    }
+
+    #[test]
+    fn test_posts_every_cadence_inserts() {
+        let dir = TempDir::new().unwrap();
+        let (mut poster, posted) = recording_poster(&dir, 5);
+
+        let reference_dir = TempDir::new().unwrap();
+        let mut reference = MerkleTreeService::new(reference_dir.path(), Some(8)).unwrap();
+        let mut expected = Vec::new();
+
+        for i in 0..10 {
+            poster.insert_commitment(&commitment(i)).unwrap();
+            reference.insert_commitment(&commitment(i)).unwrap();
+            if (i + 1) % 5 == 0 {
+                expected.push((reference.len(), reference.root()));
+            }
+        }
+
+        assert_eq!(*posted.lock().unwrap(), expected);
+
+        // Nothing changed since the last post, so flushing is a no-op
+        assert!(!poster.flush());
+        assert_eq!(posted.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_posts_when_idle() {
+        let dir = TempDir::new().unwrap();
+        let (poster, posted) = recording_poster(&dir, 100);
+        let mut poster = poster.with_idle_timeout(Duration::ZERO);
+
+        assert!(!poster.poll_idle());
+        poster.insert_commitment(&commitment(0)).unwrap();
+        assert!(poster.poll_idle());
+        assert!(!poster.poll_idle());
+        assert_eq!(posted.lock().unwrap().len(), 1);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::errors::{IndexerError, IndexerResult};
-use crate::tree::Commitment;
+use crate::tree::{Commitment, MerkleProof};
+use crate::utils::{DomainSeparators, Hash, HashAlgorithm};
 use borsh::BorshDeserialize;
 // use serde::Deserialize; // kept commented unless needed by user types
 use std::collections::HashMap;
@@ -78,3 +79,126 @@ impl Default for SerializerRegistry {
         Self::new()
     }
 }
+
+/// Check that the commitment carried by raw event bytes is a member of the
+/// tree with the trusted `root`.
+///
+/// The bytes are parsed with `registry` and the commitment's leaf hash under
+/// the tree's `hasher` and `separators` must match the proof's leaf. Bytes
+/// no serializer recognizes are an error.
+pub fn verify_event_membership(
+    event_bytes: &[u8],
+    registry: &SerializerRegistry,
+    proof: &MerkleProof,
+    root: &Hash,
+    hasher: HashAlgorithm,
+    separators: &DomainSeparators,
+) -> IndexerResult<bool> {
+    let commitment = registry.parse(event_bytes)?.ok_or_else(|| {
+        IndexerError::InvalidData("no serializer matches the event bytes".to_string())
+    })?;
+    Ok(proof.leaf == commitment.leaf_hash(hasher, separators)
+        && proof.verify_with_hashing(root, hasher, separators))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    const DISCRIMINATOR: [u8; 8] = [7; 8];
+
+    /// Parses a commitment encoded with `to_bytes` after an 8-byte discriminator
+    struct RawCommitmentSerializer;
+
+    impl InstructionSerializer for RawCommitmentSerializer {
+        fn serialize(&self, ix_data: &[u8]) -> IndexerResult<Option<Commitment>> {
+            Commitment::from_bytes(&ix_data[8..]).map(Some)
+        }
+    }
+
+    fn event(commitment: &Commitment) -> Vec<u8> {
+        let mut bytes = DISCRIMINATOR.to_vec();
+        bytes.extend_from_slice(&commitment.to_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_verify_event_membership() {
+        let mut registry = SerializerRegistry::new();
+        registry.register(DISCRIMINATOR.to_vec(), Box::new(RawCommitmentSerializer));
+
+        let commitments: Vec<Commitment> = (0..4u8)
+            .map(|i| {
+                Commitment::builder()
+                    .commitment_index(i as u64)
+                    .random_secret([i; 32])
+                    .nullifier([i + 10; 32])
+                    .build()
+            })
+            .collect();
+        let member = event(&commitments[2]);
+        let other = event(&commitments[1]);
+
+        let custom = DomainSeparators::new(7, 9).unwrap();
+        for (hasher, separators) in [
+            (HashAlgorithm::Poseidon, DomainSeparators::default()),
+            (HashAlgorithm::Poseidon, custom),
+            (HashAlgorithm::Sha256, DomainSeparators::default()),
+        ] {
+            let mut tree = IncrementalMerkleTree::builder()
+                .depth(8)
+                .hasher(hasher)
+                .domain_separators(separators)
+                .build()
+                .unwrap();
+            for commitment in &commitments {
+                tree.insert_commitment(commitment).unwrap();
+            }
+            let root = tree.root();
+            let proof = tree.prove(2).unwrap();
+            let verify = |bytes: &[u8], root: &Hash| {
+                verify_event_membership(bytes, &registry, &proof, root, hasher, &separators)
+            };
+
+            assert!(verify(&member, &root).unwrap());
+
+            // A proof for a different leaf, or a stale root, does not vouch for it
+            assert!(!verify(&other, &root).unwrap());
+            assert!(!verify(&member, &[0u8; 32]).unwrap());
+
+            assert!(verify(&[1, 2, 3], &root).is_err());
+        }
+    }
+
+    #[test]
+    fn test_borsh_commitment_serializer_in_registry() {
+        let commitment = Commitment::builder()
+            .commitment_index(9)
+            .random_secret([1u8; 32])
+            .nullifier([2u8; 32])
+            .build();
+        let data = commitment.to_borsh_vec();
+
+        // The Borsh serializer parses the whole instruction data, so the
+        // discriminator is the leading bytes of the commitment itself
+        let mut registry = SerializerRegistry::new();
+        registry.register(
+            data[..8].to_vec(),
+            Box::new(BorshCommitmentSerializer::<Commitment>::new()),
+        );
+
+        let parsed = registry.parse(&data).unwrap().unwrap();
+        assert_eq!(parsed.to_bytes(), commitment.to_bytes());
+        assert_eq!(
+            parsed.leaf_hash(HashAlgorithm::Poseidon, &DomainSeparators::default()),
+            commitment.leaf_hash(HashAlgorithm::Poseidon, &DomainSeparators::default())
+        );
+
+        let truncated = &data[..data.len() - 1];
+        assert!(matches!(
+            registry.parse(truncated),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+}
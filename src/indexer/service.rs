@@ -1,4 +1,6 @@
+use crate::config::IndexerConfig;
 use crate::errors::IndexerResult;
+use crate::indexer::cursor::SlotCursor;
 use crate::indexer::laserstream_client::{LaserstreamClient, LaserstreamConfigWrapper};
 use crate::indexer::pipeline::IndexerPipeline;
 use crate::indexer::serializers::SerializerRegistry;
@@ -19,17 +21,52 @@ impl IndexService {
         registry: SerializerRegistry,
     ) -> IndexerResult<Self> {
         let merkle = MerkleTreeService::new(data_dir, depth)?; // creates or opens existing tree
-                                                               // Use first program ID for filtering events
-        let program_id = program_ids
-            .first()
-            .cloned()
-            .unwrap_or_else(|| "".to_string());
-        let pipeline = IndexerPipeline::new(registry, merkle, program_id);
-        let client = LaserstreamClient::new(LaserstreamConfigWrapper {
-            endpoint: laserstream_endpoint,
-            api_key: laserstream_api_key,
+        Self::with_merkle(
+            merkle,
+            std::path::Path::new(data_dir),
+            laserstream_endpoint,
+            laserstream_api_key,
             program_ids,
-        });
+            registry,
+        )
+    }
+
+    /// Create the service from an `IndexerConfig`, opening the tree with its
+    /// storage settings and leaf placement
+    pub fn from_config(
+        config: &IndexerConfig,
+        laserstream_endpoint: String,
+        laserstream_api_key: String,
+        program_ids: Vec<String>,
+        registry: SerializerRegistry,
+    ) -> IndexerResult<Self> {
+        let merkle = MerkleTreeService::from_config(config)?;
+        Self::with_merkle(
+            merkle,
+            &config.storage.data_dir,
+            laserstream_endpoint,
+            laserstream_api_key,
+            program_ids,
+            registry,
+        )
+    }
+
+    fn with_merkle(
+        merkle: MerkleTreeService,
+        data_dir: &std::path::Path,
+        laserstream_endpoint: String,
+        laserstream_api_key: String,
+        program_ids: Vec<String>,
+        registry: SerializerRegistry,
+    ) -> IndexerResult<Self> {
+        let cursor = SlotCursor::open(data_dir.join("slot_cursor.dat"))?;
+        let pipeline =
+            IndexerPipeline::new(registry, merkle, program_ids.clone()).with_slot_cursor(cursor);
+        let client = LaserstreamClient::new(LaserstreamConfigWrapper::new(
+            laserstream_endpoint,
+            laserstream_api_key,
+            program_ids,
+        ));
         Ok(Self { pipeline, client })
     }
 
@@ -37,10 +74,9 @@ impl IndexService {
         let mut pipeline = self.pipeline;
         let result = self
             .client
-            .run(move |evt| {
-                if let Err(e) = pipeline.handle_event(evt.value) {
-                    log::error!("pipeline error: {e}");
-                }
+            .run(move |evt| match pipeline.handle_event(evt.value) {
+                Ok(outcome) => log::debug!("event outcome: {outcome:?}"),
+                Err(e) => log::error!("pipeline error: {e}"),
             })
             .await;
         if let Err(e) = result {
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+/// A remembered signature and its neighbours in the recency list
+#[derive(Debug)]
+struct Slot {
+    signature: String,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// Bounded set of recently handled transaction signatures.
+///
+/// Seeing a signature again makes it the most recently used, and the least
+/// recently used one is evicted once `capacity` is exceeded. A capacity of
+/// zero remembers nothing. Recency is tracked with a doubly linked list
+/// threaded through a fixed set of slots, so lookups, touches and
+/// evictions are all O(1) and memory never exceeds `capacity` entries.
+#[derive(Debug)]
+pub struct SeenSignatures {
+    capacity: usize,
+    // Signature -> its slot
+    index: HashMap<String, usize>,
+    slots: Vec<Slot>,
+    // Least recently used slot
+    head: Option<usize>,
+    // Most recently used slot
+    tail: Option<usize>,
+}
+
+impl SeenSignatures {
+    /// Create a set remembering up to `capacity` signatures
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            index: HashMap::new(),
+            slots: Vec::new(),
+            head: None,
+            tail: None,
+        }
+    }
+
+    /// Check whether `signature` was recorded, marking it recently used
+    pub fn contains(&mut self, signature: &str) -> bool {
+        let Some(&slot) = self.index.get(signature) else {
+            return false;
+        };
+        self.unlink(slot);
+        self.push_back(slot);
+        true
+    }
+
+    /// Record `signature` as handled
+    pub fn insert(&mut self, signature: &str) {
+        if self.capacity == 0 || self.contains(signature) {
+            return;
+        }
+
+        let slot = if self.slots.len() < self.capacity {
+            self.slots.push(Slot {
+                signature: signature.to_string(),
+                prev: None,
+                next: None,
+            });
+            self.slots.len() - 1
+        } else {
+            // Reuse the least recently used slot
+            let lru = self.head.expect("full set has a head");
+            self.unlink(lru);
+            let evicted = std::mem::replace(&mut self.slots[lru].signature, signature.to_string());
+            self.index.remove(&evicted);
+            lru
+        };
+        self.index.insert(signature.to_string(), slot);
+        self.push_back(slot);
+    }
+
+    /// Number of signatures remembered
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Check if no signatures are remembered
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Detach a slot from the recency list
+    fn unlink(&mut self, slot: usize) {
+        let (prev, next) = (self.slots[slot].prev, self.slots[slot].next);
+        match prev {
+            Some(prev) => self.slots[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.slots[next].prev = prev,
+            None => self.tail = prev,
+        }
+        self.slots[slot].prev = None;
+        self.slots[slot].next = None;
+    }
+
+    /// Append a detached slot as the most recently used
+    fn push_back(&mut self, slot: usize) {
+        self.slots[slot].prev = self.tail;
+        match self.tail {
+            Some(tail) => self.slots[tail].next = Some(slot),
+            None => self.head = Some(slot),
+        }
+        self.tail = Some(slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut seen = SeenSignatures::new(2);
+        seen.insert("a");
+        seen.insert("b");
+
+        // "a" becomes most recently used, so "b" is evicted next
+        assert!(seen.contains("a"));
+        seen.insert("c");
+        assert_eq!(seen.len(), 2);
+        assert!(!seen.contains("b"));
+        assert!(seen.contains("a"));
+        assert!(seen.contains("c"));
+
+        // Redelivering a small working set never grows the set
+        for _ in 0..100 {
+            assert!(seen.contains("a"));
+            seen.insert("c");
+        }
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen.slots.len(), 2);
+
+        let mut disabled = SeenSignatures::new(0);
+        disabled.insert("a");
+        assert!(!disabled.contains("a"));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_working_set_below_capacity_stays_bounded() {
+        let mut seen = SeenSignatures::new(100);
+        for round in 0..1000 {
+            let signature = format!("sig{}", round % 3);
+            if !seen.contains(&signature) {
+                seen.insert(&signature);
+            }
+        }
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen.slots.len(), 3);
+
+        // Filling past capacity evicts in least recently used order
+        for i in 0..150 {
+            seen.insert(&format!("new{i}"));
+        }
+        assert_eq!(seen.len(), 100);
+        assert!(!seen.contains("sig0"));
+        assert!(!seen.contains("new49"));
+        assert!(seen.contains("new50"));
+    }
+}
@@ -5,6 +5,8 @@
 
 pub mod config;
 pub mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod indexer;
 pub mod serialization;
 pub mod storage;
@@ -12,11 +14,11 @@ pub mod tree;
 pub mod utils;
 
 // Re-export commonly used types
-pub use config::IndexerConfig;
+pub use config::{IndexerConfig, LeafPlacement};
 pub use errors::{IndexerError, IndexerResult};
 pub use storage::{PersistentMerkleTree, StorageConfig};
 pub use tree::{Commitment, IncrementalMerkleTree, MerkleProof};
-pub use utils::Hash;
+pub use utils::{Hash, HashAlgorithm};
 
 // Legacy API compatibility functions
 use tree::IncrementalMerkleTree as Tree;
@@ -138,7 +138,7 @@ fn run_merkle_proof_example() -> Result<(), Box<dyn std::error::Error>> {
     let proof_json = serde_json::json!({
         "leaf_index": proof.leaf_index,
         "leaf": hex::encode(proof.leaf),
-        "siblings": proof.siblings.iter().map(|s| hex::encode(s)).collect::<Vec<_>>(),
+        "siblings": proof.siblings.iter().map(hex::encode).collect::<Vec<_>>(),
         "sibling_count": proof.siblings.len()
     });
     std::fs::write(proof_file, serde_json::to_string_pretty(&proof_json)?)?;
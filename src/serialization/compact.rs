@@ -1,11 +1,10 @@
-use flate2::{read::GzDecoder, write::GzEncoder, Compression};
-use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::header::{read_header, write_header};
 use crate::serialization::{SerializationFormat, SerializationOptions};
 use crate::tree::{IncrementalMerkleTree, SerializableTree};
-use crate::utils::Hash;
+use crate::utils::{Hash, HashAlgorithm};
 
 /// Compact representation of a Merkle tree that only stores non-empty leaves
 #[derive(Serialize, Deserialize)]
@@ -45,61 +44,169 @@ impl CompactTree {
     }
 }
 
+/// Snapshot body: the leaves followed by the algorithm that hashed them
+#[derive(Serialize)]
+struct TaggedLeavesRef<'a> {
+    leaves: &'a [Hash],
+    hash_algorithm: HashAlgorithm,
+}
+
+#[derive(Deserialize)]
+struct TaggedLeaves {
+    leaves: Vec<Hash>,
+    hash_algorithm: HashAlgorithm,
+}
+
 /// Serialize a tree using the most compact representation
 pub fn serialize_tree_optimized(
     tree: &IncrementalMerkleTree,
     options: &SerializationOptions,
 ) -> IndexerResult<Vec<u8>> {
-    // Directly serialize the serializable part of the tree
-    let serializable_tree = &tree.serializable;
-
-    let serialized = match options.format {
-        SerializationFormat::Bincode => bincode::serialize(serializable_tree)
-            .map_err(|e| IndexerError::SerializationError(e.to_string()))?,
-        SerializationFormat::MessagePack => rmp_serde::to_vec(serializable_tree)
-            .map_err(|e| IndexerError::SerializationError(e.to_string()))?,
-        SerializationFormat::Postcard => {
-            let mut buffer = vec![0u8; 4 * 1024 * 1024]; // 4MB buffer
-            let slice = postcard::to_slice(serializable_tree, &mut buffer)
-                .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
-            slice.to_vec()
-        }
+    // Serialize the leaves, tagged with the tree's hash algorithm
+    let serializable_tree = &TaggedLeavesRef {
+        leaves: tree.leaf_slice(),
+        hash_algorithm: tree.hasher(),
     };
 
-    if options.compress {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(options.compression_level));
-        encoder.write_all(&serialized)?;
-        Ok(encoder.finish()?)
-    } else {
-        Ok(serialized)
+    let serialized = encode(serializable_tree, &options.format)?;
+
+    let mut data = Vec::new();
+    write_header(&mut data, options);
+    data.extend_from_slice(&options.compression.compress(&serialized)?);
+    Ok(data)
+}
+
+/// Encode `value` in the given format
+pub(crate) fn encode<T: Serialize>(
+    value: &T,
+    format: &SerializationFormat,
+) -> IndexerResult<Vec<u8>> {
+    match format {
+        SerializationFormat::Bincode => {
+            bincode::serialize(value).map_err(|e| IndexerError::SerializationError(e.to_string()))
+        }
+        SerializationFormat::MessagePack => {
+            rmp_serde::to_vec(value).map_err(|e| IndexerError::SerializationError(e.to_string()))
+        }
+        // Grows as needed rather than failing once a fixed buffer fills up
+        SerializationFormat::Postcard => postcard::to_extend(value, Vec::new())
+            .map_err(|e| IndexerError::SerializationError(e.to_string())),
+    }
+}
+
+/// Decode `data` as `T` in the given format
+pub(crate) fn decode<T: DeserializeOwned>(
+    data: &[u8],
+    format: &SerializationFormat,
+) -> IndexerResult<T> {
+    match format {
+        SerializationFormat::Bincode => {
+            bincode::deserialize(data).map_err(|e| IndexerError::SerializationError(e.to_string()))
+        }
+        SerializationFormat::MessagePack => {
+            rmp_serde::from_slice(data).map_err(|e| IndexerError::SerializationError(e.to_string()))
+        }
+        SerializationFormat::Postcard => {
+            postcard::from_bytes(data).map_err(|e| IndexerError::SerializationError(e.to_string()))
+        }
     }
 }
 
-/// Deserialize a tree from optimized format
+/// Deserialize a tree from optimized format.
+///
+/// The format and compression are read from the data's header, so only
+/// `options.hash_algorithm` needs to match how the tree was written. Fails
+/// with `InvalidData` on a missing or unsupported header, or if the tree
+/// was built with a different hash algorithm.
 pub fn deserialize_tree_optimized(
     data: &[u8],
     options: &SerializationOptions,
 ) -> IndexerResult<IncrementalMerkleTree> {
-    let decompressed = if options.compress {
-        let mut decoder = GzDecoder::new(data);
-        let mut decompressed = Vec::new();
-        decoder.read_to_end(&mut decompressed)?;
-        decompressed
-    } else {
-        data.to_vec()
-    };
+    let (options, data) = read_header(data, options)?;
+    let decompressed = options.compression.decompress(data)?;
+
+    let tagged: TaggedLeaves = decode(&decompressed, &options.format)?;
+    if tagged.hash_algorithm != options.hash_algorithm {
+        return Err(IndexerError::InvalidData(format!(
+            "snapshot was built with {:?} hashing but {:?} is expected",
+            tagged.hash_algorithm, options.hash_algorithm
+        )));
+    }
 
-    let serializable_tree: SerializableTree = match options.format {
-        SerializationFormat::Bincode => bincode::deserialize(&decompressed)
-            .map_err(|e| IndexerError::SerializationError(e.to_string()))?,
-        SerializationFormat::MessagePack => rmp_serde::from_slice(&decompressed)
-            .map_err(|e| IndexerError::SerializationError(e.to_string()))?,
-        SerializationFormat::Postcard => postcard::from_bytes(&decompressed)
-            .map_err(|e| IndexerError::SerializationError(e.to_string()))?,
+    let serializable_tree = SerializableTree {
+        leaves: tagged.leaves,
     };
+    Ok(
+        IncrementalMerkleTree::from_serializable(serializable_tree, 20)
+            .with_hasher(tagged.hash_algorithm),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::Compression;
+
+    #[test]
+    fn test_round_trip_with_each_compression() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..2_000u32 {
+            tree.append(&(i % 50).to_le_bytes()).unwrap();
+        }
+
+        let mut sizes = Vec::new();
+        for compression in [
+            Compression::None,
+            Compression::Gzip(6),
+            Compression::Zstd(3),
+        ] {
+            let options = SerializationOptions::fastest().with_compression(compression);
+            let data = serialize_tree_optimized(&tree, &options).unwrap();
+            let loaded = deserialize_tree_optimized(&data, &options).unwrap();
+            assert_eq!(loaded.len(), tree.len());
+            assert_eq!(loaded.root(), tree.root());
+            sizes.push(data.len());
+        }
+        assert!(sizes[1] < sizes[0] && sizes[2] < sizes[0]);
 
-    Ok(IncrementalMerkleTree::from_serializable(
-        serializable_tree,
-        20,
-    ))
+        // Zstd data loads with gzip options: the algorithm is in the data
+        let zstd = SerializationOptions::fastest().with_compression(Compression::Zstd(3));
+        let data = serialize_tree_optimized(&tree, &zstd).unwrap();
+        let loaded = deserialize_tree_optimized(&data, &SerializationOptions::balanced()).unwrap();
+        assert_eq!(loaded.root(), tree.root());
+    }
+
+    #[test]
+    fn test_header_overrides_mismatched_options() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..100u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        let written = SerializationOptions::smallest().with_compression(Compression::Zstd(3));
+        let data = serialize_tree_optimized(&tree, &written).unwrap();
+        assert_eq!(&data[..4], b"IMTR");
+
+        for mismatched in [
+            SerializationOptions::fastest(),
+            SerializationOptions::new(SerializationFormat::MessagePack, true, 1),
+        ] {
+            let loaded = deserialize_tree_optimized(&data, &mismatched).unwrap();
+            assert_eq!(loaded.root(), tree.root());
+        }
+
+        let mut corrupted = data.clone();
+        corrupted[0] = b'X';
+        assert!(matches!(
+            deserialize_tree_optimized(&corrupted, &written),
+            Err(IndexerError::InvalidData(_))
+        ));
+
+        let mut future = data;
+        future[6] = 2;
+        assert!(matches!(
+            deserialize_tree_optimized(&future, &written),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
 }
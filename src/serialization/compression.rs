@@ -0,0 +1,123 @@
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::errors::{IndexerError, IndexerResult};
+
+/// Leading bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Leading bytes of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression applied to serialized data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Store data as encoded
+    None,
+    /// Gzip with a level from 0 to 9
+    Gzip(u32),
+    /// Zstd with a level from 1 to 22
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Whether data is compressed at all
+    pub fn is_enabled(self) -> bool {
+        self != Compression::None
+    }
+
+    /// Compress `data` in one go
+    pub(crate) fn compress(self, data: &[u8]) -> IndexerResult<Vec<u8>> {
+        let mut compressed = Vec::new();
+        self.write_with(&mut compressed, |writer| Ok(writer.write_all(data)?))?;
+        Ok(compressed)
+    }
+
+    /// Decompress `data` written by `compress`
+    pub(crate) fn decompress(self, data: &[u8]) -> IndexerResult<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        self.reader(data)?.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// Run `body` against a writer that compresses into `writer`, then
+    /// finish the compressed stream
+    pub(crate) fn write_with<W: Write>(
+        self,
+        writer: W,
+        body: impl FnOnce(&mut dyn Write) -> IndexerResult<()>,
+    ) -> IndexerResult<()> {
+        let mut writer = writer;
+        match self {
+            Compression::None => body(&mut writer)?,
+            Compression::Gzip(level) => {
+                let mut encoder = GzEncoder::new(&mut writer, flate2::Compression::new(level));
+                body(&mut encoder)?;
+                encoder.finish()?;
+            }
+            Compression::Zstd(level) => {
+                let mut encoder = zstd::Encoder::new(&mut writer, level)?;
+                body(&mut encoder)?;
+                encoder.finish()?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Wrap `reader` in the matching decompressor.
+    ///
+    /// Gzip and zstd streams are told apart by their magic bytes, so data
+    /// written with either algorithm can be read back whenever compression
+    /// is enabled.
+    pub(crate) fn reader<'a, R: Read + 'a>(self, reader: R) -> IndexerResult<Box<dyn Read + 'a>> {
+        if !self.is_enabled() {
+            return Ok(Box::new(reader));
+        }
+
+        let mut reader = BufReader::new(reader);
+        let head = reader.fill_buf()?;
+        if head.starts_with(&ZSTD_MAGIC) {
+            Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+        } else if head.starts_with(&GZIP_MAGIC) {
+            Ok(Box::new(GzDecoder::new(reader)))
+        } else {
+            Err(IndexerError::InvalidData(
+                "data is neither gzip nor zstd compressed".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_detection() {
+        let data: Vec<u8> = (0..10_000u32)
+            .flat_map(|i| (i % 97).to_le_bytes())
+            .collect();
+
+        for compression in [
+            Compression::None,
+            Compression::Gzip(6),
+            Compression::Zstd(3),
+        ] {
+            let compressed = compression.compress(&data).unwrap();
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+            if compression.is_enabled() {
+                assert!(compressed.len() < data.len());
+            }
+        }
+
+        // The algorithm is read from the data, not the options
+        let zstd = Compression::Zstd(3).compress(&data).unwrap();
+        assert_eq!(Compression::Gzip(6).decompress(&zstd).unwrap(), data);
+
+        assert!(matches!(
+            Compression::Zstd(3).decompress(&data),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+}
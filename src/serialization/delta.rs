@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::compact::{decode, encode};
+use crate::serialization::header::{read_header, write_header};
+use crate::serialization::SerializationOptions;
+use crate::tree::IncrementalMerkleTree;
+use crate::utils::Hash;
+
+/// Leaf changes that take a tree from one state to a later one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TreeDelta {
+    /// Leaf count of the tree the delta applies to
+    pub base_len: usize,
+    /// Leaf count after applying the delta
+    pub new_len: usize,
+    /// Changed or appended leaves as (index, hash) pairs, in index order
+    pub changes: Vec<(usize, Hash)>,
+}
+
+impl TreeDelta {
+    /// Compute the delta from `old` to `new`.
+    ///
+    /// Trees only grow, so `new` must have at least as many leaves as `old`
+    /// and the same depth.
+    pub fn between(
+        old: &IncrementalMerkleTree,
+        new: &IncrementalMerkleTree,
+    ) -> IndexerResult<Self> {
+        if old.depth() != new.depth() {
+            return Err(IndexerError::InvalidData(format!(
+                "cannot diff trees of different depths: {} and {}",
+                old.depth(),
+                new.depth()
+            )));
+        }
+        if new.len() < old.len() {
+            return Err(IndexerError::InvalidData(format!(
+                "new tree has fewer leaves ({}) than the base ({})",
+                new.len(),
+                old.len()
+            )));
+        }
+
+        let changes = (0..new.len())
+            .filter_map(|i| {
+                let hash = new.get_leaf_hash(i)?;
+                (old.get_leaf_hash(i) != Some(hash)).then_some((i, hash))
+            })
+            .collect();
+
+        Ok(Self {
+            base_len: old.len(),
+            new_len: new.len(),
+            changes,
+        })
+    }
+
+    /// Check if the delta changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.base_len == self.new_len
+    }
+}
+
+/// Serialize a tree delta
+pub fn serialize_tree_delta(
+    delta: &TreeDelta,
+    options: &SerializationOptions,
+) -> IndexerResult<Vec<u8>> {
+    let serialized = encode(delta, &options.format)?;
+
+    let mut data = Vec::new();
+    write_header(&mut data, options);
+    data.extend_from_slice(&options.compression.compress(&serialized)?);
+    Ok(data)
+}
+
+/// Deserialize a tree delta written by `serialize_tree_delta`.
+///
+/// The format and compression are read from the data's header rather than
+/// taken from `options`.
+pub fn deserialize_tree_delta(
+    data: &[u8],
+    options: &SerializationOptions,
+) -> IndexerResult<TreeDelta> {
+    let (options, data) = read_header(data, options)?;
+    let decompressed = options.compression.decompress(data)?;
+
+    decode(&decompressed, &options.format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::Compression;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let mut old = IncrementalMerkleTree::new(8);
+        old.append(b"a").unwrap();
+        old.append(b"b").unwrap();
+        let mut new = old.clone();
+        new.update(0, b"c").unwrap();
+        new.append(b"d").unwrap();
+
+        let delta = TreeDelta::between(&old, &new).unwrap();
+        assert_eq!(delta.base_len, 2);
+        assert_eq!(delta.new_len, 3);
+        assert_eq!(delta.changes.len(), 2);
+
+        for options in [
+            SerializationOptions::fastest(),
+            SerializationOptions::smallest(),
+            SerializationOptions::balanced(),
+            SerializationOptions::fastest().with_compression(Compression::Zstd(3)),
+        ] {
+            let bytes = serialize_tree_delta(&delta, &options).unwrap();
+            assert_eq!(deserialize_tree_delta(&bytes, &options).unwrap(), delta);
+        }
+
+        // The header, not the caller's options, decides how to decode
+        let bytes = serialize_tree_delta(&delta, &SerializationOptions::smallest()).unwrap();
+        let decoded = deserialize_tree_delta(&bytes, &SerializationOptions::fastest()).unwrap();
+        assert_eq!(decoded, delta);
+        assert!(matches!(
+            deserialize_tree_delta(&bytes[1..], &SerializationOptions::smallest()),
+            Err(IndexerError::InvalidData(_))
+        ));
+
+        let mut replica = old.clone();
+        replica.apply_delta(&delta).unwrap();
+        assert_eq!(replica.root(), new.root());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::{Compression, SerializationFormat, SerializationOptions};
+
+/// Leading bytes of every serialized tree and delta
+const MAGIC: [u8; 4] = *b"IMTR";
+
+/// Version of the layout that follows the header
+const FORMAT_VERSION: u16 = 1;
+
+/// Magic, format tag, compression tag and u16 LE version
+const HEADER_LEN: usize = 8;
+
+/// Start a blob with the header describing how `options` encode its body
+pub(crate) fn write_header(out: &mut Vec<u8>, options: &SerializationOptions) {
+    let format = match options.format {
+        SerializationFormat::Bincode => 0,
+        SerializationFormat::MessagePack => 1,
+        SerializationFormat::Postcard => 2,
+    };
+    let compression = match options.compression {
+        Compression::None => 0,
+        Compression::Gzip(_) => 1,
+        Compression::Zstd(_) => 2,
+    };
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&[format, compression]);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+}
+
+/// Split a blob into its body and the options it was written with.
+///
+/// Only the format and compression come from the header; everything else
+/// (such as the expected hash algorithm) is kept from `options`.
+pub(crate) fn read_header<'a>(
+    data: &'a [u8],
+    options: &SerializationOptions,
+) -> IndexerResult<(SerializationOptions, &'a [u8])> {
+    if data.len() < HEADER_LEN || data[..4] != MAGIC {
+        return Err(IndexerError::InvalidData(
+            "missing serialized tree header".to_string(),
+        ));
+    }
+
+    let version = u16::from_le_bytes([data[6], data[7]]);
+    if version != FORMAT_VERSION {
+        return Err(IndexerError::InvalidData(format!(
+            "unsupported serialization version {version}"
+        )));
+    }
+    let format = match data[4] {
+        0 => SerializationFormat::Bincode,
+        1 => SerializationFormat::MessagePack,
+        2 => SerializationFormat::Postcard,
+        tag => {
+            return Err(IndexerError::InvalidData(format!(
+                "unknown serialization format tag {tag}"
+            )))
+        }
+    };
+    // Levels only matter when compressing
+    let compression = match data[5] {
+        0 => Compression::None,
+        1 => Compression::Gzip(0),
+        2 => Compression::Zstd(0),
+        tag => {
+            return Err(IndexerError::InvalidData(format!(
+                "unknown compression tag {tag}"
+            )))
+        }
+    };
+
+    let options = SerializationOptions {
+        format,
+        compression,
+        ..options.clone()
+    };
+    Ok((options, &data[HEADER_LEN..]))
+}
@@ -1,7 +1,20 @@
 pub mod compact;
+pub mod compression;
+pub mod delta;
 pub mod formats;
+mod header;
 pub mod options;
+pub mod proof_batch;
+pub mod proof_stream;
+pub mod snapshot;
+pub mod tree_stream;
 
 pub use compact::*;
+pub use compression::*;
+pub use delta::*;
 pub use formats::*;
 pub use options::*;
+pub use proof_batch::*;
+pub use proof_stream::*;
+pub use snapshot::*;
+pub use tree_stream::*;
@@ -1,32 +1,50 @@
-use super::SerializationFormat;
+use super::{Compression, SerializationFormat};
+use crate::utils::HashAlgorithm;
 
 /// Configuration options for serialization
 #[derive(Debug, Clone)]
 pub struct SerializationOptions {
     /// Serialization format to use
     pub format: SerializationFormat,
-    /// Whether to compress the serialized data
-    pub compress: bool,
-    /// Compression level (0-9, where 9 is highest compression)
-    pub compression_level: u32,
+    /// Compression applied to the serialized data
+    pub compression: Compression,
+    /// Hash algorithm a loaded tree must have been built with
+    pub hash_algorithm: HashAlgorithm,
 }
 
 impl SerializationOptions {
-    /// Create new serialization options
+    /// Create new serialization options, gzip-compressed at
+    /// `compression_level` (0-9) when `compress` is set
     pub fn new(format: SerializationFormat, compress: bool, compression_level: u32) -> Self {
         Self {
             format,
-            compress,
-            compression_level: compression_level.min(9),
+            compression: if compress {
+                Compression::Gzip(compression_level.min(9))
+            } else {
+                Compression::None
+            },
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 
+    /// Compress with `compression` instead
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Expect loaded trees to use `hash_algorithm` (Poseidon by default)
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
     /// Create options for fastest serialization
     pub fn fastest() -> Self {
         Self {
             format: SerializationFormat::Bincode,
-            compress: false,
-            compression_level: 0,
+            compression: Compression::None,
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 
@@ -34,8 +52,8 @@ impl SerializationOptions {
     pub fn smallest() -> Self {
         Self {
             format: SerializationFormat::Postcard,
-            compress: true,
-            compression_level: 9,
+            compression: Compression::Gzip(9),
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 
@@ -43,8 +61,8 @@ impl SerializationOptions {
     pub fn balanced() -> Self {
         Self {
             format: SerializationFormat::Bincode,
-            compress: true,
-            compression_level: 6,
+            compression: Compression::Gzip(6),
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 }
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::compact::{decode, encode};
+use crate::serialization::SerializationOptions;
+use crate::tree::{CompactMerkleProof, MerkleProof};
+use crate::utils::Hash;
+
+/// Framed batch of proofs sharing one tree depth
+#[derive(Serialize, Deserialize)]
+struct ProofBatch {
+    /// Number of proofs in the batch
+    count: u32,
+    /// Sibling count of every proof in the batch
+    depth: u32,
+    proofs: Vec<CompactMerkleProof>,
+}
+
+/// Serialize proofs into one framed message.
+///
+/// All proofs must have the same depth. Each is stored compressed, so
+/// siblings equal to the zero hash of their level are recorded as a bit
+/// and rebuilt on deserialization. `zero_hashes` are those of the tree
+/// that produced the proofs, e.g. `tree.zero_hashes()`.
+pub fn serialize_proof_batch(
+    proofs: &[MerkleProof],
+    zero_hashes: &[Hash],
+    options: &SerializationOptions,
+) -> IndexerResult<Vec<u8>> {
+    let depth = proofs.first().map_or(0, |proof| proof.siblings.len());
+    let packed = proofs
+        .iter()
+        .map(|proof| {
+            if proof.siblings.len() != depth {
+                return Err(IndexerError::InvalidData(format!(
+                    "proof for leaf {} has depth {}, expected {depth}",
+                    proof.leaf_index,
+                    proof.siblings.len()
+                )));
+            }
+            proof.compress(zero_hashes)
+        })
+        .collect::<IndexerResult<Vec<_>>>()?;
+
+    let batch = ProofBatch {
+        count: proofs.len() as u32,
+        depth: depth as u32,
+        proofs: packed,
+    };
+
+    let serialized = encode(&batch, &options.format)?;
+    options.compression.compress(&serialized)
+}
+
+/// Deserialize proofs written by `serialize_proof_batch` with the same
+/// `zero_hashes`
+pub fn deserialize_proof_batch(
+    data: &[u8],
+    zero_hashes: &[Hash],
+    options: &SerializationOptions,
+) -> IndexerResult<Vec<MerkleProof>> {
+    let decompressed = options.compression.decompress(data)?;
+
+    let batch: ProofBatch = decode(&decompressed, &options.format)?;
+
+    let depth = batch.depth as usize;
+    if batch.count as usize != batch.proofs.len() {
+        return Err(IndexerError::InvalidData(
+            "corrupt proof batch header".to_string(),
+        ));
+    }
+
+    batch
+        .proofs
+        .iter()
+        .map(|compact| {
+            if compact.full_depth() != depth {
+                return Err(IndexerError::InvalidData(format!(
+                    "proof for leaf {} has depth {}, expected {depth}",
+                    compact.leaf_index,
+                    compact.full_depth()
+                )));
+            }
+            compact.decompress(zero_hashes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    #[test]
+    fn test_proof_batch_round_trip() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        for i in 0..50u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root = tree.root();
+        let proofs: Vec<_> = (0..50).map(|i| tree.prove(i).unwrap()).collect();
+
+        for options in [
+            SerializationOptions::fastest(),
+            SerializationOptions::smallest(),
+            SerializationOptions::balanced(),
+        ] {
+            let bytes = serialize_proof_batch(&proofs, tree.zero_hashes(), &options).unwrap();
+            let decoded = deserialize_proof_batch(&bytes, tree.zero_hashes(), &options).unwrap();
+
+            assert_eq!(decoded.len(), 50);
+            for (original, proof) in proofs.iter().zip(&decoded) {
+                assert_eq!(proof.leaf_index, original.leaf_index);
+                assert_eq!(proof.siblings, original.siblings);
+                assert!(proof.verify_against(&root, &proof.leaf));
+            }
+        }
+
+        // Most of a depth-16 proof over 50 leaves is zero siblings
+        let bytes = serialize_proof_batch(
+            &proofs,
+            tree.zero_hashes(),
+            &SerializationOptions::fastest(),
+        )
+        .unwrap();
+        assert!(bytes.len() < 50 * 16 * 32 / 2);
+    }
+
+    #[test]
+    fn test_proof_batch_rejects_mixed_depths() {
+        let mut small = IncrementalMerkleTree::new(4);
+        small.append(b"a").unwrap();
+        let mut large = IncrementalMerkleTree::new(8);
+        large.append(b"a").unwrap();
+
+        let proofs = vec![small.prove(0).unwrap(), large.prove(0).unwrap()];
+        assert!(serialize_proof_batch(
+            &proofs,
+            large.zero_hashes(),
+            &SerializationOptions::fastest()
+        )
+        .is_err());
+    }
+}
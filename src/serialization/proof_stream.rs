@@ -0,0 +1,187 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::compact::{decode, encode};
+use crate::serialization::{SerializationFormat, SerializationOptions};
+use crate::tree::MerkleProof;
+use crate::utils::Hash;
+
+/// Largest proof frame accepted when reading, well above a full-depth proof
+/// in any format
+const MAX_PROOF_FRAME_LEN: usize = 64 * 1024;
+
+/// On-the-wire fields of one streamed proof
+type ProofFields = (u64, Hash, Vec<Hash>);
+
+/// Write one proof as a u32 LE length followed by its encoded fields
+pub(crate) fn write_proof_frame(
+    writer: &mut dyn Write,
+    proof: &MerkleProof,
+    format: &SerializationFormat,
+) -> IndexerResult<()> {
+    let fields = (proof.leaf_index as u64, proof.leaf, &proof.siblings);
+    let encoded = encode(&fields, format)?;
+
+    writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
+    writer.write_all(&encoded)?;
+    Ok(())
+}
+
+/// Lazily read proofs written by `IncrementalMerkleTree::export_proofs`.
+///
+/// A truncated final frame, or compressed data in an unknown format,
+/// yields an `InvalidData` error, after which the iterator ends.
+pub fn read_proof_stream<'a, R: Read + 'a>(
+    reader: R,
+    options: &SerializationOptions,
+) -> impl Iterator<Item = IndexerResult<MerkleProof>> + 'a {
+    let (reader, pending) = match options.compression.reader(reader) {
+        Ok(reader) => (reader, None),
+        Err(e) => (Box::new(std::io::empty()) as Box<dyn Read + 'a>, Some(e)),
+    };
+    ProofStream {
+        reader,
+        format: options.format.clone(),
+        pending,
+        finished: false,
+    }
+}
+
+/// Iterator over length-prefixed proof frames
+struct ProofStream<'a> {
+    reader: Box<dyn Read + 'a>,
+    format: SerializationFormat,
+    // Error opening the stream, reported by the first call to `next`
+    pending: Option<IndexerError>,
+    finished: bool,
+}
+
+impl ProofStream<'_> {
+    /// Fill `buf` completely, returning the number of bytes read before EOF
+    fn read_full(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+
+    fn read_frame(&mut self) -> IndexerResult<Option<MerkleProof>> {
+        let mut len_bytes = [0u8; 4];
+        match self.read_full(&mut len_bytes)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => {
+                return Err(IndexerError::InvalidData(
+                    "truncated proof frame length".to_string(),
+                ))
+            }
+        }
+
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > MAX_PROOF_FRAME_LEN {
+            return Err(IndexerError::InvalidData(format!(
+                "proof frame of {len} bytes exceeds the {MAX_PROOF_FRAME_LEN} byte limit"
+            )));
+        }
+
+        let mut frame = vec![0u8; len];
+        if self.read_full(&mut frame)? < frame.len() {
+            return Err(IndexerError::InvalidData(
+                "truncated proof frame".to_string(),
+            ));
+        }
+
+        let (leaf_index, leaf, siblings): ProofFields = decode(&frame, &self.format)?;
+        Ok(Some(MerkleProof::new(leaf_index as usize, leaf, siblings)))
+    }
+}
+
+impl Iterator for ProofStream<'_> {
+    type Item = IndexerResult<MerkleProof>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        if let Some(e) = self.pending.take() {
+            self.finished = true;
+            return Some(Err(e));
+        }
+
+        let result = self.read_frame();
+        if !matches!(result, Ok(Some(_))) {
+            self.finished = true;
+        }
+        result.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    #[test]
+    fn test_export_proofs_round_trip() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        for i in 0..200u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root = tree.root();
+
+        for options in [
+            SerializationOptions::fastest(),
+            SerializationOptions::smallest(),
+            SerializationOptions::new(SerializationFormat::MessagePack, false, 0),
+        ] {
+            let mut buffer = Vec::new();
+            tree.export_proofs(&mut buffer, &options).unwrap();
+
+            let proofs: Vec<MerkleProof> = read_proof_stream(buffer.as_slice(), &options)
+                .collect::<IndexerResult<_>>()
+                .unwrap();
+            assert_eq!(proofs.len(), 200);
+            for index in [0, 1, 99, 150, 199] {
+                assert_eq!(proofs[index].leaf_index, index);
+                assert_eq!(proofs[index].siblings, tree.prove(index).unwrap().siblings);
+                assert!(proofs[index].verify(&root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_proof_stream_errors() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"a").unwrap();
+        tree.append(b"b").unwrap();
+        let options = SerializationOptions::fastest();
+
+        let mut buffer = Vec::new();
+        tree.export_proofs(&mut buffer, &options).unwrap();
+        buffer.truncate(buffer.len() - 5);
+
+        let results: Vec<_> = read_proof_stream(buffer.as_slice(), &options).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(IndexerError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_oversized_proof_frame_is_rejected() {
+        let options = SerializationOptions::fastest();
+        let mut buffer = u32::MAX.to_le_bytes().to_vec();
+        buffer.extend_from_slice(&[0u8; 16]);
+
+        let results: Vec<_> = read_proof_stream(buffer.as_slice(), &options).collect();
+        assert_eq!(results.len(), 1);
+        assert!(
+            matches!(&results[0], Err(IndexerError::InvalidData(msg)) if msg.contains("limit"))
+        );
+    }
+}
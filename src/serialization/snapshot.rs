@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::{
+    deserialize_tree_optimized, serialize_tree_optimized, SerializationOptions,
+};
+use crate::tree::{IncrementalMerkleTree, MerkleProof};
+
+/// Write a tree snapshot to `path` using the given serialization options
+pub fn save_snapshot(
+    tree: &IncrementalMerkleTree,
+    path: &Path,
+    options: &SerializationOptions,
+) -> IndexerResult<()> {
+    let data = serialize_tree_optimized(tree, options)?;
+    fs::write(path, data)?;
+    Ok(())
+}
+
+/// Load a tree snapshot written by `save_snapshot`
+pub fn load_snapshot(
+    path: &Path,
+    options: &SerializationOptions,
+) -> IndexerResult<IncrementalMerkleTree> {
+    let data = fs::read(path)?;
+    deserialize_tree_optimized(&data, options)
+}
+
+/// Re-encode the snapshot at `input` into `output` with different
+/// serialization options.
+///
+/// The re-encoded data is decoded again and checked to produce the same
+/// root before anything is written.
+pub fn transcode_snapshot(
+    input: &Path,
+    input_opts: &SerializationOptions,
+    output: &Path,
+    output_opts: &SerializationOptions,
+) -> IndexerResult<()> {
+    let tree = load_snapshot(input, input_opts)?;
+    let data = serialize_tree_optimized(&tree, output_opts)?;
+
+    let reloaded = deserialize_tree_optimized(&data, output_opts)?;
+    if reloaded.root() != tree.root() {
+        return Err(IndexerError::InvalidData(
+            "transcoded snapshot does not preserve the root".to_string(),
+        ));
+    }
+
+    fs::write(output, data)?;
+    Ok(())
+}
+
+/// Verify a batch of proofs against the root of the snapshot at `snapshot_path`.
+///
+/// The snapshot is loaded and its root computed once, and proofs are hashed
+/// with the snapshot tree's algorithm; the result holds one entry per
+/// proof, in order.
+pub fn verify_proof_set(
+    snapshot_path: &Path,
+    proofs: &[MerkleProof],
+    options: &SerializationOptions,
+) -> IndexerResult<Vec<bool>> {
+    let tree = load_snapshot(snapshot_path, options)?;
+    let (root, hasher, separators) = (tree.root(), tree.hasher(), tree.domain_separators());
+    Ok(proofs
+        .iter()
+        .map(|proof| proof.verify_with_hashing(&root, hasher, &separators))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::SerializationFormat;
+    use crate::utils::HashAlgorithm;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transcode_bincode_to_compressed_postcard() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("snapshot.bincode");
+        let output = dir.path().join("snapshot.postcard.gz");
+        let input_opts = SerializationOptions::new(SerializationFormat::Bincode, false, 0);
+        let output_opts = SerializationOptions::new(SerializationFormat::Postcard, true, 9);
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..50u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        save_snapshot(&tree, &input, &input_opts).unwrap();
+
+        transcode_snapshot(&input, &input_opts, &output, &output_opts).unwrap();
+
+        let reloaded = load_snapshot(&output, &output_opts).unwrap();
+        assert_eq!(reloaded.len(), tree.len());
+        assert_eq!(reloaded.root(), tree.root());
+
+        // The header records the output encoding, so the input options work too
+        let reloaded = load_snapshot(&output, &input_opts).unwrap();
+        assert_eq!(reloaded.root(), tree.root());
+    }
+
+    #[test]
+    fn test_rejects_snapshot_with_other_hash_algorithm() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("snapshot.bin");
+        let options = SerializationOptions::balanced();
+
+        let mut tree = IncrementalMerkleTree::new(20).with_hasher(HashAlgorithm::Sha256);
+        tree.append(b"leaf").unwrap();
+        save_snapshot(&tree, &path, &options).unwrap();
+
+        let err = load_snapshot(&path, &options).unwrap_err();
+        assert!(matches!(err, IndexerError::InvalidData(msg) if msg.contains("Sha256")));
+
+        let sha_options = options.with_hash_algorithm(HashAlgorithm::Sha256);
+        let loaded = load_snapshot(&path, &sha_options).unwrap();
+        assert_eq!(loaded.hasher(), HashAlgorithm::Sha256);
+        assert_eq!(loaded.root(), tree.root());
+    }
+
+    #[test]
+    fn test_verify_proof_set_against_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("snapshot.bin");
+        let options = SerializationOptions::balanced();
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..6u8 {
+            tree.append(&[i]).unwrap();
+        }
+        save_snapshot(&tree, &path, &options).unwrap();
+
+        let mut proofs: Vec<MerkleProof> =
+            (0..tree.len()).map(|i| tree.prove(i).unwrap()).collect();
+        let results = verify_proof_set(&path, &proofs, &options).unwrap();
+        assert_eq!(results, vec![true; 6]);
+
+        // A proof from a diverged tree is flagged individually
+        tree.append(b"later").unwrap();
+        proofs.push(tree.prove(6).unwrap());
+        let results = verify_proof_set(&path, &proofs, &options).unwrap();
+        assert_eq!(results.iter().filter(|ok| **ok).count(), 6);
+        assert!(!results[6]);
+    }
+
+    #[test]
+    fn test_verify_proof_set_against_sha256_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("snapshot.bin");
+        let options = SerializationOptions::balanced().with_hash_algorithm(HashAlgorithm::Sha256);
+
+        let mut tree = IncrementalMerkleTree::new(20).with_hasher(HashAlgorithm::Sha256);
+        for i in 0..6u8 {
+            tree.append(&[i]).unwrap();
+        }
+        save_snapshot(&tree, &path, &options).unwrap();
+
+        let mut proofs: Vec<MerkleProof> =
+            (0..tree.len()).map(|i| tree.prove(i).unwrap()).collect();
+        let results = verify_proof_set(&path, &proofs, &options).unwrap();
+        assert_eq!(results, vec![true; 6]);
+
+        // A Poseidon proof for the same leaves does not verify
+        let mut poseidon = IncrementalMerkleTree::new(20);
+        for i in 0..6u8 {
+            poseidon.append(&[i]).unwrap();
+        }
+        proofs.push(poseidon.prove(0).unwrap());
+        let results = verify_proof_set(&path, &proofs, &options).unwrap();
+        assert!(!results[6]);
+    }
+}
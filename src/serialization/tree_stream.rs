@@ -0,0 +1,194 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::compact::{decode, encode};
+use crate::serialization::{SerializationFormat, SerializationOptions};
+use crate::tree::{IncrementalMerkleTree, SerializableTree};
+use crate::utils::{Hash, HashAlgorithm};
+
+/// Leaves written per chunk frame (256 KiB of hashes)
+const STREAM_CHUNK_LEAVES: usize = 8192;
+
+/// Largest frame accepted when reading, well above any chunk we write
+const MAX_FRAME_LEN: usize = 4 * 1024 * 1024;
+
+/// First frame of a tree stream
+#[derive(Serialize, Deserialize)]
+struct TreeStreamHeader {
+    depth: u64,
+    len: u64,
+    hash_algorithm: HashAlgorithm,
+}
+
+/// Write a tree as a header frame followed by chunks of leaves.
+///
+/// Every frame is a u32 LE length followed by its body encoded in
+/// `options.format`, and the whole stream is compressed with
+/// `options.compression`. Only one chunk is encoded at a time, so
+/// memory use does not grow with the tree. Read it back with
+/// `deserialize_tree_from_reader`.
+pub fn serialize_tree_to_writer<W: Write>(
+    tree: &IncrementalMerkleTree,
+    options: &SerializationOptions,
+    writer: W,
+) -> IndexerResult<()> {
+    let leaves = tree.leaf_slice();
+    let header = TreeStreamHeader {
+        depth: tree.depth() as u64,
+        len: leaves.len() as u64,
+        hash_algorithm: tree.hasher(),
+    };
+    let write_frames = |writer: &mut dyn Write| -> IndexerResult<()> {
+        write_frame(writer, &header, &options.format)?;
+        for chunk in leaves.chunks(STREAM_CHUNK_LEAVES) {
+            write_frame(writer, &chunk, &options.format)?;
+        }
+        Ok(())
+    };
+
+    options.compression.write_with(writer, write_frames)
+}
+
+/// Read a tree written by `serialize_tree_to_writer`.
+///
+/// Fails with `InvalidData` if the stream is truncated or malformed, or if
+/// the tree was built with a different hash algorithm than
+/// `options.hash_algorithm`.
+pub fn deserialize_tree_from_reader<R: Read>(
+    reader: R,
+    options: &SerializationOptions,
+) -> IndexerResult<IncrementalMerkleTree> {
+    let mut reader = options.compression.reader(reader)?;
+
+    let header: TreeStreamHeader = read_frame(&mut reader, &options.format)?;
+    if header.hash_algorithm != options.hash_algorithm {
+        return Err(IndexerError::InvalidData(format!(
+            "stream was built with {:?} hashing but {:?} is expected",
+            header.hash_algorithm, options.hash_algorithm
+        )));
+    }
+    if header.depth >= usize::BITS as u64 || header.len > 1u64 << header.depth {
+        return Err(IndexerError::InvalidData(format!(
+            "invalid tree stream header: {} leaves at depth {}",
+            header.len, header.depth
+        )));
+    }
+
+    let len = header.len as usize;
+    let mut leaves = Vec::new();
+    while leaves.len() < len {
+        let chunk: Vec<Hash> = read_frame(&mut reader, &options.format)?;
+        if chunk.is_empty() || leaves.len() + chunk.len() > len {
+            return Err(IndexerError::InvalidData(format!(
+                "tree stream chunk does not fit the {len} leaves in its header"
+            )));
+        }
+        leaves.extend_from_slice(&chunk);
+    }
+
+    Ok(
+        IncrementalMerkleTree::from_serializable(
+            SerializableTree { leaves },
+            header.depth as usize,
+        )
+        .with_hasher(header.hash_algorithm),
+    )
+}
+
+/// Write `value` as a u32 LE length followed by its encoding
+fn write_frame<T: Serialize>(
+    writer: &mut dyn Write,
+    value: &T,
+    format: &SerializationFormat,
+) -> IndexerResult<()> {
+    let encoded = encode(value, format)?;
+    writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
+    writer.write_all(&encoded)?;
+    Ok(())
+}
+
+/// Read one frame written by `write_frame`
+fn read_frame<T: DeserializeOwned>(
+    reader: &mut dyn Read,
+    format: &SerializationFormat,
+) -> IndexerResult<T> {
+    let truncated = |e: std::io::Error| match e.kind() {
+        ErrorKind::UnexpectedEof => IndexerError::InvalidData("truncated tree stream".to_string()),
+        _ => IndexerError::from(e),
+    };
+
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes).map_err(truncated)?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(IndexerError::InvalidData(format!(
+            "tree stream frame of {len} bytes exceeds the {MAX_FRAME_LEN} byte limit"
+        )));
+    }
+
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame).map_err(truncated)?;
+    decode(&frame, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_large_tree_round_trip_through_file() {
+        // Far more leaves than fit in the old fixed 4MB Postcard buffer
+        let leaves: Vec<Hash> = (0..500_000u64)
+            .map(|i| {
+                let mut hash = [0u8; 32];
+                hash[..8].copy_from_slice(&(i + 1).to_le_bytes());
+                hash
+            })
+            .collect();
+        let tree = IncrementalMerkleTree::from_serializable(SerializableTree { leaves }, 20)
+            .with_hasher(HashAlgorithm::Sha256);
+        let root = tree.root();
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tree.stream");
+        let options = SerializationOptions::smallest().with_hash_algorithm(HashAlgorithm::Sha256);
+        let file = BufWriter::new(File::create(&path).unwrap());
+        serialize_tree_to_writer(&tree, &options, file).unwrap();
+
+        let file = BufReader::new(File::open(&path).unwrap());
+        let loaded = deserialize_tree_from_reader(file, &options).unwrap();
+        assert_eq!(loaded.len(), 500_000);
+        assert_eq!(loaded.depth(), 20);
+        assert_eq!(loaded.root(), root);
+    }
+
+    #[test]
+    fn test_truncated_or_mismatched_stream_errors() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        for i in 0..10u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let options = SerializationOptions::new(SerializationFormat::MessagePack, false, 0);
+
+        let mut buffer = Vec::new();
+        serialize_tree_to_writer(&tree, &options, &mut buffer).unwrap();
+        let loaded = deserialize_tree_from_reader(buffer.as_slice(), &options).unwrap();
+        assert_eq!(loaded.root(), tree.root());
+
+        let truncated = &buffer[..buffer.len() - 1];
+        assert!(matches!(
+            deserialize_tree_from_reader(truncated, &options),
+            Err(IndexerError::InvalidData(_))
+        ));
+
+        let sha_options = options.with_hash_algorithm(HashAlgorithm::Sha256);
+        assert!(matches!(
+            deserialize_tree_from_reader(buffer.as_slice(), &sha_options),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+}
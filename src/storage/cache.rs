@@ -1,13 +1,30 @@
 use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+/// Neighbours of a page in the recency list
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
 /// LRU cache for frequently accessed pages
+///
+/// Recency is tracked with a doubly linked list threaded through the page
+/// ids, so touching and evicting a page are both O(1).
 #[derive(Debug)]
 pub struct PageCache {
     pages: HashMap<usize, Arc<RwLock<Vec<u8>>>>,
     max_size: usize,
-    access_order: Vec<usize>,
+    links: HashMap<usize, Link>,
+    // Least recently used page
+    head: Option<usize>,
+    // Most recently used page
+    tail: Option<usize>,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl PageCache {
@@ -16,46 +33,92 @@ impl PageCache {
         Self {
             pages: HashMap::new(),
             max_size: max_pages,
-            access_order: Vec::new(),
+            links: HashMap::new(),
+            head: None,
+            tail: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
 
     /// Get a page from the cache, updating its access time
     pub fn get(&mut self, page_id: usize) -> Option<Arc<RwLock<Vec<u8>>>> {
-        if let Some(page) = self.pages.get(&page_id) {
-            // Move to end (most recently used)
-            self.access_order.retain(|&id| id != page_id);
-            self.access_order.push(page_id);
-            Some(page.clone())
-        } else {
-            None
-        }
+        let Some(page) = self.pages.get(&page_id).cloned() else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        // Move to end (most recently used)
+        self.unlink(page_id);
+        self.push_back(page_id);
+        Some(page)
     }
 
-    /// Insert a page into the cache, potentially evicting old pages
+    /// Insert a page into the cache, potentially evicting old pages.
+    ///
+    /// Re-inserting a cached page replaces its data and marks it most
+    /// recently used without evicting anything.
     pub fn insert(&mut self, page_id: usize, page_data: Vec<u8>) -> Arc<RwLock<Vec<u8>>> {
-        // Evict least recently used pages if necessary
-        while self.pages.len() >= self.max_size && !self.access_order.is_empty() {
-            let lru_page = self.access_order.remove(0);
-            self.pages.remove(&lru_page);
+        if self.pages.contains_key(&page_id) {
+            self.unlink(page_id);
+        } else {
+            // Evict least recently used pages if necessary
+            while self.pages.len() >= self.max_size {
+                let Some(lru_page) = self.head else {
+                    break;
+                };
+                self.unlink(lru_page);
+                self.pages.remove(&lru_page);
+            }
         }
 
         let page = Arc::new(RwLock::new(page_data));
         self.pages.insert(page_id, page.clone());
-        self.access_order.push(page_id);
+        self.push_back(page_id);
         page
     }
 
     /// Remove a page from the cache
     pub fn remove(&mut self, page_id: usize) -> Option<Arc<RwLock<Vec<u8>>>> {
-        self.access_order.retain(|&id| id != page_id);
+        self.unlink(page_id);
         self.pages.remove(&page_id)
     }
 
     /// Clear all pages from the cache
     pub fn clear(&mut self) {
         self.pages.clear();
-        self.access_order.clear();
+        self.links.clear();
+        self.head = None;
+        self.tail = None;
+    }
+
+    /// Detach a page from the recency list, if it is in it
+    fn unlink(&mut self, page_id: usize) {
+        let Some(link) = self.links.remove(&page_id) else {
+            return;
+        };
+        match link.prev {
+            Some(prev) => self.links.get_mut(&prev).expect("linked page").next = link.next,
+            None => self.head = link.next,
+        }
+        match link.next {
+            Some(next) => self.links.get_mut(&next).expect("linked page").prev = link.prev,
+            None => self.tail = link.prev,
+        }
+    }
+
+    /// Append a detached page as the most recently used
+    fn push_back(&mut self, page_id: usize) {
+        let link = Link {
+            prev: self.tail,
+            next: None,
+        };
+        match self.tail {
+            Some(tail) => self.links.get_mut(&tail).expect("linked page").next = Some(page_id),
+            None => self.head = Some(page_id),
+        }
+        self.tail = Some(page_id);
+        self.links.insert(page_id, link);
     }
 
     /// Get the current number of cached pages
@@ -70,10 +133,20 @@ impl PageCache {
 
     /// Get cache statistics
     pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
         CacheStats {
             current_pages: self.pages.len(),
             max_pages: self.max_size,
             utilization: self.pages.len() as f64 / self.max_size as f64,
+            hits,
+            misses,
+            hit_ratio: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
         }
     }
 
@@ -89,4 +162,113 @@ pub struct CacheStats {
     pub current_pages: usize,
     pub max_pages: usize,
     pub utilization: f64,
+    /// Lookups that found the page cached
+    pub hits: u64,
+    /// Lookups that had to go to disk
+    pub misses: u64,
+    /// Fraction of lookups that were hits (0.0 before any lookup)
+    pub hit_ratio: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference model of the original policy: a Vec in recency order
+    struct VecLru {
+        order: Vec<usize>,
+        max: usize,
+    }
+
+    impl VecLru {
+        fn get(&mut self, page_id: usize) -> bool {
+            let hit = self.order.contains(&page_id);
+            if hit {
+                self.order.retain(|&id| id != page_id);
+                self.order.push(page_id);
+            }
+            hit
+        }
+
+        fn insert(&mut self, page_id: usize) -> Option<usize> {
+            let evicted = (self.order.len() >= self.max).then(|| self.order.remove(0));
+            self.order.push(page_id);
+            evicted
+        }
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let mut cache = PageCache::new(3);
+        for page_id in 0..3 {
+            cache.insert(page_id, vec![page_id as u8]);
+        }
+
+        // 0 becomes most recently used, so 1 is evicted next
+        assert!(cache.get(0).is_some());
+        cache.insert(3, vec![3]);
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.len(), 3);
+
+        cache.insert(4, vec![4]);
+        assert!(cache.get(2).is_none());
+        for page_id in [0, 3, 4] {
+            assert!(cache.get(page_id).is_some());
+        }
+
+        assert!(cache.remove(3).is_some());
+        cache.insert(5, vec![5]);
+        assert_eq!(cache.len(), 3);
+        cache.insert(6, vec![6]);
+        assert!(cache.get(0).is_none());
+    }
+
+    #[test]
+    fn test_hit_and_miss_counts() {
+        let mut cache = PageCache::new(2);
+        assert_eq!(cache.stats().hit_ratio, 0.0);
+
+        assert!(cache.get(0).is_none());
+        cache.insert(0, vec![0]);
+        cache.insert(1, vec![1]);
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(0).is_some());
+        cache.insert(2, vec![2]);
+        assert!(cache.get(1).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 2);
+        assert!((stats.hit_ratio - 0.6).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_matches_reference_policy_over_many_accesses() {
+        let mut cache = PageCache::new(8);
+        let mut model = VecLru {
+            order: Vec::new(),
+            max: 8,
+        };
+
+        // Deterministic pseudo-random working set larger than the cache
+        let mut state = 0x2545_f491_u64;
+        for _ in 0..20_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let page_id = (state % 24) as usize;
+
+            let hit = cache.get(page_id).is_some();
+            assert_eq!(hit, model.get(page_id));
+            if !hit {
+                let evicted = model.insert(page_id);
+                cache.insert(page_id, Vec::new());
+                if let Some(evicted) = evicted {
+                    assert!(!cache.pages().contains_key(&evicted));
+                }
+            }
+            assert_eq!(cache.len(), model.order.len());
+        }
+    }
 }
@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::IndexerResult;
+use crate::utils::Hash;
+
+/// Slots behind the newest checkpoint kept for reorg rollback by default
+pub const DEFAULT_CHECKPOINT_WINDOW: u64 = 150;
+
+/// Tree state after all commitments from a slot were inserted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Solana slot the state was recorded at
+    pub slot: u64,
+    /// Number of leaves in the tree at that slot
+    pub next_index: usize,
+    /// Root of the tree at that slot
+    pub root: Hash,
+}
+
+/// Slot-ordered checkpoints, persisted to a sidecar file.
+///
+/// Only checkpoints within `window` slots of the newest one are kept, so
+/// the log stays bounded however long the indexer runs.
+#[derive(Debug)]
+pub struct CheckpointLog {
+    checkpoints: Vec<Checkpoint>,
+    window: u64,
+    path: PathBuf,
+}
+
+impl CheckpointLog {
+    /// Open the log stored at `path`, starting empty if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let checkpoints = if path.exists() {
+            let data = std::fs::read(&path)?;
+            if data.is_empty() {
+                Vec::new()
+            } else {
+                bincode::deserialize(&data)?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            checkpoints,
+            window: DEFAULT_CHECKPOINT_WINDOW,
+            path,
+        })
+    }
+
+    /// Keep checkpoints up to `slots` behind the newest one (default
+    /// `DEFAULT_CHECKPOINT_WINDOW`); older ones are pruned on `record`
+    pub fn with_window(mut self, slots: u64) -> Self {
+        self.window = slots;
+        self
+    }
+
+    /// Record a checkpoint, replacing any at the same or a later slot and
+    /// pruning those that fall outside the window
+    pub fn record(&mut self, checkpoint: Checkpoint) {
+        let oldest = checkpoint.slot.saturating_sub(self.window);
+        self.checkpoints
+            .retain(|existing| existing.slot < checkpoint.slot && existing.slot >= oldest);
+        self.checkpoints.push(checkpoint);
+    }
+
+    /// Latest checkpoint at or before `slot`
+    pub fn at_or_before(&self, slot: u64) -> Option<Checkpoint> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.slot <= slot)
+            .copied()
+    }
+
+    /// Drop every checkpoint after `slot`
+    pub fn truncate_after(&mut self, slot: u64) {
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.slot <= slot);
+    }
+
+    /// All checkpoints in slot order
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Write the log to its sidecar file
+    pub fn save(&self) -> IndexerResult<()> {
+        let data = bincode::serialize(&self.checkpoints)?;
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn checkpoint(slot: u64) -> Checkpoint {
+        Checkpoint {
+            slot,
+            next_index: slot as usize,
+            root: [slot as u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_record_prunes_outside_window() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("checkpoints.dat");
+        let mut log = CheckpointLog::open(&path).unwrap().with_window(10);
+
+        for slot in 0..100 {
+            log.record(checkpoint(slot));
+        }
+        let slots: Vec<u64> = log.checkpoints().iter().map(|c| c.slot).collect();
+        assert_eq!(slots, (89..100).collect::<Vec<_>>());
+        assert_eq!(log.at_or_before(50), None);
+
+        // Re-recording an earlier slot replaces it and everything after it
+        log.record(checkpoint(95));
+        assert_eq!(log.checkpoints().last().unwrap().slot, 95);
+        assert_eq!(log.checkpoints().len(), 7);
+
+        log.save().unwrap();
+        let reopened = CheckpointLog::open(&path).unwrap();
+        assert_eq!(reopened.checkpoints(), log.checkpoints());
+    }
+}
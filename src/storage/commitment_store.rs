@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::errors::IndexerResult;
+use crate::tree::Commitment;
+
+/// Inserted commitments keyed by leaf index, persisted to a sidecar file.
+///
+/// Trees only keep leaf hashes, so this is what lets storage hand back the
+/// commitment behind a leaf.
+#[derive(Debug)]
+pub struct CommitmentStore {
+    by_leaf: BTreeMap<usize, Commitment>,
+    // commitment_index -> leaf index
+    leaf_of: HashMap<u64, usize>,
+    path: PathBuf,
+}
+
+impl CommitmentStore {
+    /// Open the store at `path`, starting empty if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let by_leaf: BTreeMap<usize, Commitment> = if path.exists() {
+            let data = std::fs::read(&path)?;
+            if data.is_empty() {
+                BTreeMap::new()
+            } else {
+                bincode::deserialize(&data)?
+            }
+        } else {
+            BTreeMap::new()
+        };
+        let leaf_of = by_leaf
+            .iter()
+            .map(|(leaf, commitment)| (commitment.commitment_index, *leaf))
+            .collect();
+
+        Ok(Self {
+            by_leaf,
+            leaf_of,
+            path,
+        })
+    }
+
+    /// Take over the commitments held by `other`, keeping this store's own
+    /// sidecar file for later saves
+    pub fn replace_with(&mut self, other: CommitmentStore) {
+        self.by_leaf = other.by_leaf;
+        self.leaf_of = other.leaf_of;
+    }
+
+    /// Record the commitment stored at a leaf index
+    pub fn insert(&mut self, leaf_index: usize, commitment: Commitment) {
+        self.leaf_of.insert(commitment.commitment_index, leaf_index);
+        self.by_leaf.insert(leaf_index, commitment);
+    }
+
+    /// Remove every commitment at or after leaf index `len`, returning them
+    /// in leaf order.
+    ///
+    /// A `commitment_index` that a surviving leaf also holds keeps resolving
+    /// to the latest such leaf.
+    pub fn truncate(&mut self, len: usize) -> Vec<Commitment> {
+        let removed = self.by_leaf.split_off(&len);
+        let orphaned: HashSet<u64> = removed
+            .values()
+            .map(|commitment| commitment.commitment_index)
+            .filter(|index| self.leaf_of.get(index).is_some_and(|leaf| *leaf >= len))
+            .collect();
+        if !orphaned.is_empty() {
+            for index in &orphaned {
+                self.leaf_of.remove(index);
+            }
+            for (leaf, commitment) in &self.by_leaf {
+                if orphaned.contains(&commitment.commitment_index) {
+                    self.leaf_of.insert(commitment.commitment_index, *leaf);
+                }
+            }
+        }
+        removed.into_values().collect()
+    }
+
+    /// Get the commitment stored at a leaf index
+    pub fn get_by_leaf(&self, leaf_index: usize) -> Option<&Commitment> {
+        self.by_leaf.get(&leaf_index)
+    }
+
+    /// Get a commitment by its `commitment_index`
+    pub fn get(&self, commitment_index: u64) -> Option<&Commitment> {
+        self.leaf_of
+            .get(&commitment_index)
+            .and_then(|leaf| self.by_leaf.get(leaf))
+    }
+
+    /// Iterate `(leaf index, commitment)` pairs in leaf order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Commitment)> {
+        self.by_leaf
+            .iter()
+            .map(|(leaf, commitment)| (*leaf, commitment))
+    }
+
+    /// Number of stored commitments
+    pub fn len(&self) -> usize {
+        self.by_leaf.len()
+    }
+
+    /// Check if no commitments are stored
+    pub fn is_empty(&self) -> bool {
+        self.by_leaf.is_empty()
+    }
+
+    /// Write the store to its sidecar file
+    pub fn save(&self) -> IndexerResult<()> {
+        let data = bincode::serialize(&self.by_leaf)?;
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
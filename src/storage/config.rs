@@ -1,19 +1,67 @@
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::storage::RetryPolicy;
+use crate::tree::DEFAULT_TREE_DEPTH;
+use crate::utils::HashAlgorithm;
+
+/// Recent roots remembered by default, matching the on-chain root window
+pub const DEFAULT_ROOT_HISTORY_SIZE: usize = 100;
+
+/// How far [`PersistentMerkleTree::sync`](crate::storage::PersistentMerkleTree::sync)
+/// pushes pending writes before returning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurabilityLevel {
+    /// Hand writes to the OS and schedule mapped-region writeback without
+    /// waiting. Survives a process crash; an OS crash or power loss can
+    /// lose anything written since the last durable sync. The WAL is not
+    /// truncated on sync, so it grows until the tree is reopened.
+    None,
+    /// Write back the mapped region and flush file buffers, without
+    /// forcing the device to persist them. Survives a process crash; a
+    /// power loss can still lose recent writes.
+    #[default]
+    Flush,
+    /// Additionally fsync the data file, metadata and WAL. Once `sync`
+    /// returns, the tree survives an OS crash or power loss.
+    Fsync,
+}
+
 /// Configuration for persistent storage
 #[derive(Debug, Clone)]
 pub struct StorageConfig {
     /// Directory where data files are stored
     pub data_dir: PathBuf,
+    /// Tree depth; must match the depth of an existing tree
+    pub depth: usize,
     /// Size of the page cache in bytes
     pub cache_size: usize,
-    /// How often to sync data to disk
+    /// How often the background flusher syncs data to disk
     pub sync_interval: Duration,
+    /// Run a background thread that syncs every `sync_interval`
+    pub background_sync: bool,
     /// Whether to enable compression
     pub compression: bool,
     /// Whether to enable write-ahead logging
     pub enable_wal: bool,
+    /// Whether to memory-map the hot region of the data file
+    pub enable_mmap: bool,
+    /// Maximum accepted leaf data length in bytes (0 = unlimited)
+    pub max_leaf_size: usize,
+    /// Retry policy for page and metadata writes
+    pub retry_policy: RetryPolicy,
+    /// Store bare 32-byte leaves without an existence flag (new trees only)
+    pub pack_leaves: bool,
+    /// Best-effort sync when the tree is dropped
+    pub sync_on_drop: bool,
+    /// Hash algorithm for new trees
+    pub hash_algorithm: HashAlgorithm,
+    /// How durable `sync` makes pending writes
+    pub durability: DurabilityLevel,
+    /// Number of recent roots remembered for proof verification (0 disables)
+    pub root_history_size: usize,
+    /// Keep raw leaf data in a sidecar file (incremental tree storage only)
+    pub retain_leaf_data: bool,
 }
 
 impl StorageConfig {
@@ -21,13 +69,30 @@ impl StorageConfig {
     pub fn new<P: Into<PathBuf>>(data_dir: P) -> Self {
         Self {
             data_dir: data_dir.into(),
+            depth: DEFAULT_TREE_DEPTH,
             cache_size: 1024 * 1024, // 1MB default
             sync_interval: Duration::from_secs(5),
+            background_sync: false,
             compression: true,
             enable_wal: true,
+            enable_mmap: true,
+            max_leaf_size: 0,
+            retry_policy: RetryPolicy::default(),
+            pack_leaves: false,
+            sync_on_drop: false,
+            hash_algorithm: HashAlgorithm::default(),
+            durability: DurabilityLevel::default(),
+            root_history_size: DEFAULT_ROOT_HISTORY_SIZE,
+            retain_leaf_data: false,
         }
     }
 
+    /// Set the tree depth
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
     /// Set the cache size in bytes
     pub fn with_cache_size(mut self, cache_size: usize) -> Self {
         self.cache_size = cache_size;
@@ -40,6 +105,13 @@ impl StorageConfig {
         self
     }
 
+    /// Sync every `sync_interval` on a background thread, which is stopped
+    /// with a final sync when the tree is shut down or dropped
+    pub fn with_background_sync(mut self, enable: bool) -> Self {
+        self.background_sync = enable;
+        self
+    }
+
     /// Enable or disable compression
     pub fn with_compression(mut self, enable: bool) -> Self {
         self.compression = enable;
@@ -52,14 +124,84 @@ impl StorageConfig {
         self
     }
 
+    /// Enable or disable memory mapping; when disabled all reads and writes
+    /// go through the page cache with buffered file I/O
+    pub fn use_mmap(mut self, enable: bool) -> Self {
+        self.enable_mmap = enable;
+        self
+    }
+
+    /// Limit the size of leaf data accepted by the tree (0 = unlimited)
+    pub fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size;
+        self
+    }
+
+    /// Drop the per-leaf existence flag and treat an all-zero hash as an
+    /// empty slot, saving one byte per leaf. Only applies when creating a
+    /// tree; existing trees keep the layout recorded in their metadata.
+    pub fn with_pack_leaves(mut self, enable: bool) -> Self {
+        self.pack_leaves = enable;
+        self
+    }
+
+    /// Sync pending writes when the tree is dropped; failures are logged
+    pub fn with_sync_on_drop(mut self, enable: bool) -> Self {
+        self.sync_on_drop = enable;
+        self
+    }
+
+    /// Set the hash algorithm. Only applies when creating a tree; existing
+    /// trees keep the algorithm recorded in their metadata.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Set how durable `sync` makes pending writes
+    pub fn with_durability(mut self, durability: DurabilityLevel) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Set how many recent roots are remembered as known
+    pub fn with_root_history_size(mut self, size: usize) -> Self {
+        self.root_history_size = size;
+        self
+    }
+
+    /// Keep the raw data of inserted leaves so it can be read back. Applies
+    /// to storage opened with `create_tree_storage_from_config`.
+    pub fn with_leaf_data_retention(mut self, retain: bool) -> Self {
+        self.retain_leaf_data = retain;
+        self
+    }
+
+    /// Set the retry policy for disk writes
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
     /// Create configuration optimized for speed
     pub fn fast<P: Into<PathBuf>>(data_dir: P) -> Self {
         Self {
             data_dir: data_dir.into(),
+            depth: DEFAULT_TREE_DEPTH,
             cache_size: 4 * 1024 * 1024, // 4MB cache
             sync_interval: Duration::from_secs(10),
+            background_sync: false,
             compression: false,
             enable_wal: false,
+            enable_mmap: true,
+            max_leaf_size: 0,
+            retry_policy: RetryPolicy::default(),
+            pack_leaves: false,
+            sync_on_drop: false,
+            hash_algorithm: HashAlgorithm::default(),
+            durability: DurabilityLevel::default(),
+            root_history_size: DEFAULT_ROOT_HISTORY_SIZE,
+            retain_leaf_data: false,
         }
     }
 
@@ -67,10 +209,21 @@ impl StorageConfig {
     pub fn reliable<P: Into<PathBuf>>(data_dir: P) -> Self {
         Self {
             data_dir: data_dir.into(),
+            depth: DEFAULT_TREE_DEPTH,
             cache_size: 1024 * 1024, // 1MB cache
             sync_interval: Duration::from_secs(1),
+            background_sync: false,
             compression: true,
             enable_wal: true,
+            enable_mmap: true,
+            max_leaf_size: 0,
+            retry_policy: RetryPolicy::default(),
+            pack_leaves: false,
+            sync_on_drop: false,
+            hash_algorithm: HashAlgorithm::default(),
+            durability: DurabilityLevel::Fsync,
+            root_history_size: DEFAULT_ROOT_HISTORY_SIZE,
+            retain_leaf_data: false,
         }
     }
 }
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::IndexerResult;
+
+/// Raw leaf data keyed by leaf index, persisted to a sidecar file
+#[derive(Debug)]
+pub struct LeafDataStore {
+    data: BTreeMap<usize, Vec<u8>>,
+    path: PathBuf,
+}
+
+impl LeafDataStore {
+    /// Open the store at `path`, starting empty if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = if path.exists() {
+            let bytes = std::fs::read(&path)?;
+            if bytes.is_empty() {
+                BTreeMap::new()
+            } else {
+                bincode::deserialize(&bytes)?
+            }
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self { data, path })
+    }
+
+    /// Take over the data held by `other`, keeping this store's own sidecar
+    /// file for later saves
+    pub fn replace_with(&mut self, other: LeafDataStore) {
+        self.data = other.data;
+    }
+
+    /// Record the data stored at a leaf index
+    pub fn insert(&mut self, leaf_index: usize, leaf_data: Vec<u8>) {
+        self.data.insert(leaf_index, leaf_data);
+    }
+
+    /// Remove the data of every leaf at or after index `len`
+    pub fn truncate(&mut self, len: usize) {
+        self.data.retain(|index, _| *index < len);
+    }
+
+    /// Get the data stored at a leaf index
+    pub fn get(&self, leaf_index: usize) -> Option<&[u8]> {
+        self.data.get(&leaf_index).map(Vec::as_slice)
+    }
+
+    /// Number of leaves with retained data
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check if no leaf data is retained
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Write the store to its sidecar file
+    pub fn save(&self) -> IndexerResult<()> {
+        let bytes = bincode::serialize(&self.data)?;
+        std::fs::write(&self.path, bytes)?;
+        Ok(())
+    }
+}
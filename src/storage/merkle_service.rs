@@ -1,21 +1,94 @@
-use crate::errors::IndexerResult;
-use crate::storage::{create_tree_storage, TreeStorage, TreeType};
+use crate::config::{IndexerConfig, LeafPlacement};
+use crate::errors::{IndexerError, IndexerResult};
+use crate::storage::{
+    create_tree_storage, create_tree_storage_from_config, Checkpoint, CheckpointLog, TreeStorage,
+    TreeType,
+};
 use crate::tree::{Commitment, MerkleProof};
 use std::path::Path;
 
+/// Sidecar file holding the slot checkpoints
+const CHECKPOINTS_FILE: &str = "checkpoints.dat";
+
 /// Facade to simplify working with the Merkle tree storage from the indexer.
 pub struct MerkleTreeService {
     storage: Box<dyn TreeStorage + Send + 'static>,
+    placement: LeafPlacement,
+    checkpoints: CheckpointLog,
 }
 
 impl MerkleTreeService {
     pub fn new<P: AsRef<Path>>(data_dir: P, depth: Option<usize>) -> IndexerResult<Self> {
         let storage = create_tree_storage(TreeType::Incremental, data_dir.as_ref(), depth)?;
-        Ok(Self { storage })
+        Self::with_storage(storage, data_dir.as_ref())
+    }
+
+    /// Open the tree in the configured data directory with the configured
+    /// depth, leaf data retention and leaf placement
+    pub fn from_config(config: &IndexerConfig) -> IndexerResult<Self> {
+        let storage = create_tree_storage_from_config(TreeType::Incremental, &config.storage)?;
+        Ok(Self::with_storage(storage, &config.storage.data_dir)?
+            .with_placement(config.leaf_placement))
+    }
+
+    /// Wrap opened storage, loading the checkpoints kept beside it
+    fn with_storage(
+        storage: Box<dyn TreeStorage + Send + 'static>,
+        data_dir: &Path,
+    ) -> IndexerResult<Self> {
+        let checkpoints = CheckpointLog::open(data_dir.join(CHECKPOINTS_FILE))?;
+        Ok(Self {
+            storage,
+            placement: LeafPlacement::default(),
+            checkpoints,
+        })
+    }
+
+    /// Keep checkpoints up to `slots` behind the newest one, bounding how
+    /// far back `rollback_to_slot` can go
+    pub fn with_checkpoint_window(mut self, slots: u64) -> Self {
+        self.checkpoints = self.checkpoints.with_window(slots);
+        self
     }
 
-    pub fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<()> {
-        self.storage.insert_commitment(commitment)
+    /// Set how inserted commitments are assigned leaf indices
+    pub fn with_placement(mut self, placement: LeafPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Insert a commitment according to the placement policy, returning its leaf index.
+    ///
+    /// Fails with `NullifierAlreadyUsed` if a commitment with the same
+    /// nullifier was inserted before. Nullifiers are persisted with the
+    /// tree on `save` and reloaded on `new`.
+    pub fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<usize> {
+        if self.storage.contains_nullifier(&commitment.nullifier) {
+            return Err(IndexerError::NullifierAlreadyUsed);
+        }
+
+        match self.placement {
+            LeafPlacement::AppendOrder => self.storage.insert_commitment(commitment),
+            LeafPlacement::ByCommitmentIndex => self
+                .storage
+                .insert_commitment_at(commitment.commitment_index as usize, commitment),
+        }
+    }
+
+    /// Insert a commitment already encoded with `Commitment::to_bytes`.
+    ///
+    /// The bytes are decoded so the nullifier can be checked, then inserted
+    /// exactly like `insert_commitment`.
+    pub fn insert_commitment_bytes(&mut self, bytes: &[u8]) -> IndexerResult<usize> {
+        if bytes.len() != Commitment::SERIALIZED_SIZE {
+            return Err(IndexerError::InvalidData(format!(
+                "commitment bytes must be {} bytes, got {}",
+                Commitment::SERIALIZED_SIZE,
+                bytes.len()
+            )));
+        }
+
+        self.insert_commitment(&Commitment::from_bytes(bytes)?)
     }
 
     pub fn root(&self) -> [u8; 32] {
@@ -38,4 +111,239 @@ impl MerkleTreeService {
     pub fn prove(&self, index: usize) -> IndexerResult<MerkleProof> {
         self.storage.prove(index)
     }
+
+    /// Record the current tree state as the checkpoint for `slot`,
+    /// replacing any checkpoint at the same or a later slot and pruning
+    /// those outside the checkpoint window
+    pub fn checkpoint(&mut self, slot: u64) -> IndexerResult<()> {
+        self.checkpoints.record(Checkpoint {
+            slot,
+            next_index: self.len(),
+            root: self.root(),
+        });
+        self.checkpoints.save()
+    }
+
+    /// Recorded checkpoints in slot order
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        self.checkpoints.checkpoints()
+    }
+
+    /// Revert the tree to its state at the latest checkpoint at or before
+    /// `slot`, dropping leaves appended in later slots.
+    ///
+    /// Fails with `InvalidData`, leaving the tree untouched, if no such
+    /// checkpoint exists or the truncated tree would not reproduce the
+    /// checkpoint's root.
+    pub fn rollback_to_slot(&mut self, slot: u64) -> IndexerResult<Checkpoint> {
+        let checkpoint = self.checkpoints.at_or_before(slot).ok_or_else(|| {
+            IndexerError::InvalidData(format!("no checkpoint at or before slot {slot}"))
+        })?;
+
+        if self.storage.root_at(checkpoint.next_index)? != checkpoint.root {
+            return Err(IndexerError::InvalidData(format!(
+                "root after rollback to slot {} would not match its checkpoint",
+                checkpoint.slot
+            )));
+        }
+
+        self.storage.truncate(checkpoint.next_index)?;
+
+        self.checkpoints.truncate_after(checkpoint.slot);
+        self.storage.save()?;
+        self.checkpoints.save()?;
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commitments_out_of_order() -> Vec<Commitment> {
+        [2u64, 0, 1]
+            .iter()
+            .map(|&i| Commitment::new(1, i, [i as u8; 32], [2u8; 32], [i as u8 + 3; 32]))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_order_placement() {
+        let dir = TempDir::new().unwrap();
+        let mut service = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+
+        let commitments = commitments_out_of_order();
+        let indices: Vec<usize> = commitments
+            .iter()
+            .map(|c| service.insert_commitment(c).unwrap())
+            .collect();
+
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_insert_commitment_bytes_matches_insert_commitment() {
+        let commitment = Commitment::new(1, 5, [4u8; 32], [5u8; 32], [6u8; 32]);
+
+        let dir = TempDir::new().unwrap();
+        let mut from_struct = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+        from_struct.insert_commitment(&commitment).unwrap();
+
+        let bytes_dir = TempDir::new().unwrap();
+        let mut from_bytes = MerkleTreeService::new(bytes_dir.path(), Some(8)).unwrap();
+        let index = from_bytes
+            .insert_commitment_bytes(&commitment.to_bytes())
+            .unwrap();
+
+        assert_eq!(index, 0);
+        assert_eq!(
+            from_bytes.prove(0).unwrap().leaf,
+            from_struct.prove(0).unwrap().leaf
+        );
+        assert_eq!(from_bytes.root(), from_struct.root());
+
+        assert!(from_bytes.insert_commitment_bytes(&[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn test_by_commitment_index_placement() {
+        let dir = TempDir::new().unwrap();
+        let mut service = MerkleTreeService::new(dir.path(), Some(8))
+            .unwrap()
+            .with_placement(LeafPlacement::ByCommitmentIndex);
+
+        let commitments = commitments_out_of_order();
+        let indices: Vec<usize> = commitments
+            .iter()
+            .map(|c| service.insert_commitment(c).unwrap())
+            .collect();
+        assert_eq!(indices, vec![2, 0, 1]);
+        assert_eq!(service.len(), 3);
+
+        // The resulting tree matches one built in on-chain order
+        let ordered_dir = TempDir::new().unwrap();
+        let mut ordered = MerkleTreeService::new(ordered_dir.path(), Some(8)).unwrap();
+        let mut sorted = commitments.clone();
+        sorted.sort_by_key(|c| c.commitment_index);
+        for c in &sorted {
+            ordered.insert_commitment(c).unwrap();
+        }
+        assert_eq!(service.root(), ordered.root());
+
+        // A slot can only be filled once
+        let same_slot = Commitment::new(1, 2, [9u8; 32], [2u8; 32], [9u8; 32]);
+        assert!(matches!(
+            service.insert_commitment(&same_slot),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_config_applies_leaf_placement() {
+        let dir = TempDir::new().unwrap();
+        let config = IndexerConfig::new(dir.path())
+            .with_storage(crate::storage::StorageConfig::new(dir.path()).with_depth(8))
+            .with_leaf_placement(LeafPlacement::ByCommitmentIndex);
+        let mut service = MerkleTreeService::from_config(&config).unwrap();
+
+        let indices: Vec<usize> = commitments_out_of_order()
+            .iter()
+            .map(|c| service.insert_commitment(c).unwrap())
+            .collect();
+        assert_eq!(indices, vec![2, 0, 1]);
+        assert_eq!(service.prove(0).unwrap().siblings.len(), 8);
+    }
+
+    #[test]
+    fn test_rollback_to_slot() {
+        let dir = TempDir::new().unwrap();
+        let mut service = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+
+        let mut next = 0u8;
+        let mut insert = |service: &mut MerkleTreeService, count: u8| {
+            for _ in 0..count {
+                let commitment =
+                    Commitment::new(1, next as u64, [next; 32], [2u8; 32], [next + 100; 32]);
+                service.insert_commitment(&commitment).unwrap();
+                next += 1;
+            }
+        };
+
+        insert(&mut service, 2);
+        service.checkpoint(100).unwrap();
+        insert(&mut service, 3);
+        service.checkpoint(101).unwrap();
+        let second_root = service.root();
+        insert(&mut service, 1);
+        service.checkpoint(102).unwrap();
+        assert_eq!(service.len(), 6);
+
+        let restored = service.rollback_to_slot(101).unwrap();
+        assert_eq!(restored.slot, 101);
+        assert_eq!(restored.next_index, 5);
+        assert_eq!(service.len(), 5);
+        assert_eq!(service.root(), second_root);
+        assert_eq!(service.checkpoints().len(), 2);
+
+        // The rolled-back commitment can be inserted again
+        let replayed = Commitment::new(1, 5, [5u8; 32], [2u8; 32], [105u8; 32]);
+        assert_eq!(service.insert_commitment(&replayed).unwrap(), 5);
+
+        // A checkpoint the leaves do not reproduce is rejected up front
+        service.checkpoints.record(Checkpoint {
+            slot: 103,
+            next_index: 3,
+            root: [0u8; 32],
+        });
+        let root_before = service.root();
+        assert!(matches!(
+            service.rollback_to_slot(103),
+            Err(IndexerError::InvalidData(_))
+        ));
+        assert_eq!(service.len(), 6);
+        assert_eq!(service.root(), root_before);
+        assert!(service.storage.contains_nullifier(&[105u8; 32]));
+        service.checkpoints.truncate_after(101);
+        service.checkpoints.save().unwrap();
+
+        // Checkpoints survive a reopen
+        drop(service);
+        let mut reopened = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+        assert_eq!(reopened.checkpoints().len(), 2);
+        assert!(matches!(
+            reopened.rollback_to_slot(99),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_reused_nullifier() {
+        let dir = TempDir::new().unwrap();
+        let first = Commitment::new(1, 0, [1u8; 32], [2u8; 32], [3u8; 32]);
+        let reused = Commitment::new(1, 1, [4u8; 32], [5u8; 32], [3u8; 32]);
+        let distinct = Commitment::new(1, 1, [4u8; 32], [5u8; 32], [6u8; 32]);
+
+        {
+            let mut service = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+            service.insert_commitment(&first).unwrap();
+            assert!(matches!(
+                service.insert_commitment(&reused),
+                Err(IndexerError::NullifierAlreadyUsed)
+            ));
+            assert!(matches!(
+                service.insert_commitment_bytes(&reused.to_bytes()),
+                Err(IndexerError::NullifierAlreadyUsed)
+            ));
+            assert_eq!(service.len(), 1);
+            service.save().unwrap();
+        }
+
+        let mut reopened = MerkleTreeService::new(dir.path(), Some(8)).unwrap();
+        assert!(matches!(
+            reopened.insert_commitment(&reused),
+            Err(IndexerError::NullifierAlreadyUsed)
+        ));
+        assert_eq!(reopened.insert_commitment(&distinct).unwrap(), 1);
+    }
 }
@@ -1,7 +1,27 @@
 use serde::{Deserialize, Serialize};
 
 use crate::errors::{IndexerError, IndexerResult};
-use crate::utils::Hash;
+use crate::utils::{Hash, HashAlgorithm};
+
+/// How leaf slots are laid out in the data file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeafLayout {
+    /// One existence flag byte followed by the 32-byte hash
+    #[default]
+    Flagged,
+    /// Bare 32-byte hashes; an all-zero hash marks an empty slot
+    Packed,
+}
+
+/// First metadata version whose data file pages end in a CRC32 trailer
+pub const PAGE_CHECKSUM_VERSION: u32 = 2;
+
+/// First metadata version whose Poseidon trees hash leaves and nodes with
+/// domain tags; older Poseidon trees used untagged hashing
+pub const TAGGED_POSEIDON_VERSION: u32 = 3;
+
+/// Metadata version written for new trees
+pub const METADATA_VERSION: u32 = TAGGED_POSEIDON_VERSION;
 
 /// Metadata stored on disk for the persistent tree
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,24 +38,144 @@ pub struct TreeMetadata {
     pub last_sync: u64,
     /// Checksum for integrity verification
     pub checksum: u32,
+    /// Leaf slot layout of the data file
+    pub leaf_layout: LeafLayout,
+    /// Hash algorithm the tree was built with
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// Metadata as written before the hash algorithm was recorded
+#[derive(Deserialize)]
+struct LayoutTreeMetadata {
+    version: u32,
+    depth: usize,
+    next_index: usize,
+    root_hash: Hash,
+    last_sync: u64,
+    checksum: u32,
+    leaf_layout: LeafLayout,
+}
+
+/// Metadata as written before the leaf layout was recorded
+#[derive(Deserialize)]
+struct LegacyTreeMetadata {
+    version: u32,
+    depth: usize,
+    next_index: usize,
+    root_hash: Hash,
+    last_sync: u64,
+    checksum: u32,
 }
 
 impl TreeMetadata {
     /// Create new metadata
     pub fn new(depth: usize, next_index: usize, root_hash: Hash) -> Self {
         let mut metadata = Self {
-            version: 1,
+            version: METADATA_VERSION,
             depth,
             next_index,
             root_hash,
             last_sync: current_timestamp(),
             checksum: 0,
+            leaf_layout: LeafLayout::default(),
+            hash_algorithm: HashAlgorithm::default(),
         };
 
         metadata.update_checksum();
         metadata
     }
 
+    /// Set the leaf slot layout
+    pub fn with_leaf_layout(mut self, leaf_layout: LeafLayout) -> Self {
+        self.leaf_layout = leaf_layout;
+        self.update_checksum();
+        self
+    }
+
+    /// Set the hash algorithm
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self.update_checksum();
+        self
+    }
+
+    /// Whether each page of the data file carries a CRC32 of its slots.
+    /// Trees created before page checksums keep their unchecked layout.
+    pub fn has_page_checksums(&self) -> bool {
+        self.version >= PAGE_CHECKSUM_VERSION
+    }
+
+    /// Whether the stored hashes were produced by the current hashing of
+    /// `hash_algorithm`. Only Poseidon trees from before domain tags differ.
+    pub fn uses_current_hashing(&self) -> bool {
+        self.hash_algorithm != HashAlgorithm::Poseidon || self.version >= TAGGED_POSEIDON_VERSION
+    }
+
+    /// Decode metadata read from disk and verify its checksum.
+    ///
+    /// Files written before the leaf layout was recorded are read as
+    /// `LeafLayout::Flagged`, and files without a hash algorithm as
+    /// `HashAlgorithm::Poseidon`.
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        if let Ok(metadata) = bincode::deserialize::<TreeMetadata>(data) {
+            metadata.verify_checksum()?;
+            return Ok(metadata);
+        }
+
+        if let Ok(old) = bincode::deserialize::<LayoutTreeMetadata>(data) {
+            let data_for_checksum = (
+                old.version,
+                old.depth,
+                old.next_index,
+                old.root_hash,
+                old.last_sync,
+                old.leaf_layout,
+            );
+            let serialized = bincode::serialize(&data_for_checksum)
+                .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
+            if crc32fast::hash(&serialized) != old.checksum {
+                return Err(IndexerError::ChecksumError);
+            }
+
+            return Ok(Self {
+                version: old.version,
+                depth: old.depth,
+                next_index: old.next_index,
+                root_hash: old.root_hash,
+                last_sync: old.last_sync,
+                checksum: old.checksum,
+                leaf_layout: old.leaf_layout,
+                hash_algorithm: HashAlgorithm::Poseidon,
+            });
+        }
+
+        let legacy: LegacyTreeMetadata = bincode::deserialize(data)
+            .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
+        let data_for_checksum = (
+            legacy.version,
+            legacy.depth,
+            legacy.next_index,
+            legacy.root_hash,
+            legacy.last_sync,
+        );
+        let serialized = bincode::serialize(&data_for_checksum)
+            .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
+        if crc32fast::hash(&serialized) != legacy.checksum {
+            return Err(IndexerError::ChecksumError);
+        }
+
+        Ok(Self {
+            version: legacy.version,
+            depth: legacy.depth,
+            next_index: legacy.next_index,
+            root_hash: legacy.root_hash,
+            last_sync: legacy.last_sync,
+            checksum: legacy.checksum,
+            leaf_layout: LeafLayout::Flagged,
+            hash_algorithm: HashAlgorithm::Poseidon,
+        })
+    }
+
     /// Update the checksum based on current data
     pub fn update_checksum(&mut self) {
         self.last_sync = current_timestamp();
@@ -45,6 +185,8 @@ impl TreeMetadata {
             self.next_index,
             self.root_hash,
             self.last_sync,
+            self.leaf_layout,
+            self.hash_algorithm,
         );
         
         if let Ok(serialized) = bincode::serialize(&data_for_checksum) {
@@ -60,6 +202,8 @@ impl TreeMetadata {
             self.next_index,
             self.root_hash,
             self.last_sync,
+            self.leaf_layout,
+            self.hash_algorithm,
         );
         
         let serialized = bincode::serialize(&data_for_checksum)
@@ -89,3 +233,81 @@ fn current_timestamp() -> u64 {
         .unwrap_or_default()
         .as_secs()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_metadata_without_leaf_layout() {
+        let mut legacy = TreeMetadata::new(20, 7, [5u8; 32]);
+        let data_for_checksum = (
+            legacy.version,
+            legacy.depth,
+            legacy.next_index,
+            legacy.root_hash,
+            legacy.last_sync,
+        );
+        legacy.checksum = crc32fast::hash(&bincode::serialize(&data_for_checksum).unwrap());
+        let old_format = (
+            legacy.version,
+            legacy.depth,
+            legacy.next_index,
+            legacy.root_hash,
+            legacy.last_sync,
+            legacy.checksum,
+        );
+
+        let decoded = TreeMetadata::from_bytes(&bincode::serialize(&old_format).unwrap()).unwrap();
+        assert_eq!(decoded.next_index, 7);
+        assert_eq!(decoded.leaf_layout, LeafLayout::Flagged);
+
+        let packed = TreeMetadata::new(20, 3, [1u8; 32]).with_leaf_layout(LeafLayout::Packed);
+        let decoded = TreeMetadata::from_bytes(&bincode::serialize(&packed).unwrap()).unwrap();
+        assert_eq!(decoded.leaf_layout, LeafLayout::Packed);
+    }
+
+    #[test]
+    fn test_hash_algorithm_round_trip_and_default() {
+        let sha = TreeMetadata::new(20, 3, [1u8; 32]).with_hash_algorithm(HashAlgorithm::Sha256);
+        let decoded = TreeMetadata::from_bytes(&bincode::serialize(&sha).unwrap()).unwrap();
+        assert_eq!(decoded.hash_algorithm, HashAlgorithm::Sha256);
+
+        let mut old = TreeMetadata::new(20, 4, [2u8; 32]).with_leaf_layout(LeafLayout::Packed);
+        let data_for_checksum = (
+            old.version,
+            old.depth,
+            old.next_index,
+            old.root_hash,
+            old.last_sync,
+            old.leaf_layout,
+        );
+        old.checksum = crc32fast::hash(&bincode::serialize(&data_for_checksum).unwrap());
+        let old_format = (
+            old.version,
+            old.depth,
+            old.next_index,
+            old.root_hash,
+            old.last_sync,
+            old.checksum,
+            old.leaf_layout,
+        );
+
+        let decoded = TreeMetadata::from_bytes(&bincode::serialize(&old_format).unwrap()).unwrap();
+        assert_eq!(decoded.leaf_layout, LeafLayout::Packed);
+        assert_eq!(decoded.hash_algorithm, HashAlgorithm::Poseidon);
+    }
+
+    #[test]
+    fn test_untagged_poseidon_versions_are_flagged() {
+        let current = TreeMetadata::new(20, 1, [1u8; 32]);
+        assert!(current.uses_current_hashing());
+
+        let mut old = current.clone();
+        old.version = PAGE_CHECKSUM_VERSION;
+        assert!(!old.uses_current_hashing());
+        assert!(old
+            .with_hash_algorithm(HashAlgorithm::Sha256)
+            .uses_current_hashing());
+    }
+}
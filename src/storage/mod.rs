@@ -1,17 +1,29 @@
 pub mod cache;
+pub mod checkpoint;
+pub mod commitment_store;
 pub mod config;
+pub mod leaf_data;
+pub mod merkle_service;
 pub mod metadata;
+pub mod nullifier;
 pub mod persistent;
-pub mod merkle_service;
+pub mod retry;
+pub mod root_history;
 pub mod unified_tree_storage;
 pub mod wal;
 
 pub use cache::*;
+pub use checkpoint::{Checkpoint, CheckpointLog, DEFAULT_CHECKPOINT_WINDOW};
+pub use commitment_store::CommitmentStore;
 pub use config::*;
+pub use leaf_data::LeafDataStore;
 pub use metadata::*;
+pub use nullifier::NullifierIndex;
 pub use persistent::*;
+pub use retry::*;
+pub use root_history::RootHistory;
 pub use unified_tree_storage::{
-    create_tree_storage, IncrementalTreeMetadata, IncrementalTreeStorage, TreeStats, TreeStorage,
-    TreeType,
+    create_tree_storage, create_tree_storage_from_config, IncrementalTreeMetadata,
+    IncrementalTreeStorage, TreeStats, TreeStorage, TreeType,
 };
 pub use wal::*;
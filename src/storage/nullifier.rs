@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::errors::IndexerResult;
+use crate::utils::Hash;
+
+/// Set of seen nullifiers, persisted to a sidecar file
+#[derive(Debug)]
+pub struct NullifierIndex {
+    seen: HashSet<Hash>,
+    path: PathBuf,
+}
+
+impl NullifierIndex {
+    /// Open the index stored at `path`, starting empty if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let seen = Self::read_set(&path)?;
+        Ok(Self { seen, path })
+    }
+
+    /// Take over the nullifiers recorded in `other`, keeping this index's
+    /// own sidecar file for later saves
+    pub fn replace_with(&mut self, other: NullifierIndex) {
+        self.seen = other.seen;
+    }
+
+    fn read_set(path: &Path) -> IndexerResult<HashSet<Hash>> {
+        if !path.exists() {
+            return Ok(HashSet::new());
+        }
+        let data = std::fs::read(path)?;
+        if data.is_empty() {
+            return Ok(HashSet::new());
+        }
+        Ok(bincode::deserialize(&data)?)
+    }
+
+    /// Check whether a nullifier has been recorded
+    pub fn contains(&self, nullifier: &Hash) -> bool {
+        self.seen.contains(nullifier)
+    }
+
+    /// Record a nullifier, returning `false` if it was already present
+    pub fn insert(&mut self, nullifier: Hash) -> bool {
+        self.seen.insert(nullifier)
+    }
+
+    /// Forget a nullifier, returning `false` if it was not present
+    pub fn remove(&mut self, nullifier: &Hash) -> bool {
+        self.seen.remove(nullifier)
+    }
+
+    /// Number of recorded nullifiers
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Check if no nullifiers have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Write the index to its sidecar file
+    pub fn save(&self) -> IndexerResult<()> {
+        let data = bincode::serialize(&self.seen)?;
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
@@ -1,18 +1,313 @@
 use memmap2::{MmapMut, MmapOptions};
 use parking_lot::{Mutex, RwLock};
+use sha2::{Digest, Sha256};
 use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::sync::Arc;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
 
 use crate::errors::{IndexerError, IndexerResult};
-use crate::storage::{PageCache, StorageConfig, TreeMetadata, WalEntry, WriteAheadLog};
-use crate::tree::{IncrementalMerkleTree, MerkleProof, DEFAULT_TREE_DEPTH};
-use crate::utils::{internal::hash_bytes, Hash};
+use crate::serialization::TreeDelta;
+use crate::storage::{
+    CacheStats, DurabilityLevel, LeafLayout, PageCache, RetryPolicy, RootHistory, StorageConfig,
+    TreeMetadata, WalEntry, WriteAheadLog,
+};
+use crate::tree::{IncrementalMerkleTree, MerkleProof};
+use crate::utils::{Hash, HashAlgorithm};
 
 // Storage constants
 const LEAF_SIZE: usize = 32; // Hash size
 const PAGE_SIZE: usize = 4096; // 4KB pages
-const LEAVES_PER_PAGE: usize = PAGE_SIZE / (LEAF_SIZE + 1); // +1 for existence flag
+const PAGE_CHECKSUM_SIZE: usize = 4; // CRC32 trailer
+
+impl LeafLayout {
+    /// Bytes used by one leaf slot
+    fn slot_size(self) -> usize {
+        match self {
+            LeafLayout::Flagged => LEAF_SIZE + 1, // +1 for existence flag
+            LeafLayout::Packed => LEAF_SIZE,
+        }
+    }
+
+    /// Write a leaf hash into its slot
+    fn encode_slot(self, slot: &mut [u8], leaf_hash: &Hash) {
+        match self {
+            LeafLayout::Flagged => {
+                slot[0] = 1;
+                slot[1..1 + LEAF_SIZE].copy_from_slice(leaf_hash);
+            }
+            LeafLayout::Packed => slot[..LEAF_SIZE].copy_from_slice(leaf_hash),
+        }
+    }
+
+    /// Read the leaf hash in a slot, or `None` if the slot is empty
+    fn decode_slot(self, slot: &[u8]) -> Option<Hash> {
+        let mut hash = [0u8; 32];
+        match self {
+            LeafLayout::Flagged => {
+                if slot[0] != 1 {
+                    return None;
+                }
+                hash.copy_from_slice(&slot[1..1 + LEAF_SIZE]);
+            }
+            LeafLayout::Packed => {
+                hash.copy_from_slice(&slot[..LEAF_SIZE]);
+                if hash == [0u8; 32] {
+                    return None;
+                }
+            }
+        }
+        Some(hash)
+    }
+}
+
+/// Slot layout of the data file together with its page trailer
+#[derive(Debug, Clone, Copy)]
+struct PageLayout {
+    leaf_layout: LeafLayout,
+    // Whether the last bytes of each page hold a CRC32 of the rest
+    checksummed: bool,
+}
+
+impl PageLayout {
+    /// Layout of the data file described by `metadata`
+    fn of(metadata: &TreeMetadata) -> Self {
+        Self {
+            leaf_layout: metadata.leaf_layout,
+            checksummed: metadata.has_page_checksums(),
+        }
+    }
+
+    /// Bytes used by one leaf slot
+    fn slot_size(self) -> usize {
+        self.leaf_layout.slot_size()
+    }
+
+    /// Number of leaf slots in one page, leaving room for the trailer
+    fn leaves_per_page(self) -> usize {
+        self.usable_bytes() / self.slot_size()
+    }
+
+    /// Bytes of a page available to leaf slots
+    fn usable_bytes(self) -> usize {
+        if self.checksummed {
+            PAGE_SIZE - PAGE_CHECKSUM_SIZE
+        } else {
+            PAGE_SIZE
+        }
+    }
+
+    /// Write a leaf hash into its slot
+    fn encode_slot(self, slot: &mut [u8], leaf_hash: &Hash) {
+        self.leaf_layout.encode_slot(slot, leaf_hash)
+    }
+
+    /// Read the leaf hash in a slot, or `None` if the slot is empty
+    fn decode_slot(self, slot: &[u8]) -> Option<Hash> {
+        self.leaf_layout.decode_slot(slot)
+    }
+
+    /// Read the leaf hash at `index` from page-structured data, if populated
+    fn read_leaf(self, data: &[u8], index: usize) -> IndexerResult<Option<Hash>> {
+        let page_start = (index / self.leaves_per_page()) * PAGE_SIZE;
+        if let Some(page) = data.get(page_start..page_start + PAGE_SIZE) {
+            self.verify(page)?;
+        }
+        let leaf_start = page_start + (index % self.leaves_per_page()) * self.slot_size();
+        Ok(data
+            .get(leaf_start..leaf_start + self.slot_size())
+            .and_then(|slot| self.decode_slot(slot)))
+    }
+
+    /// Store the checksum of a full page in its trailer
+    fn seal(self, page: &mut [u8]) {
+        if !self.checksummed || page.len() < PAGE_SIZE {
+            return;
+        }
+        let (body, trailer) = page[..PAGE_SIZE].split_at_mut(self.usable_bytes());
+        trailer.copy_from_slice(&crc32fast::hash(body).to_le_bytes());
+    }
+
+    /// Check a page against its trailer.
+    ///
+    /// Short pages and pages that were never written (all zeroes) have no
+    /// checksum to verify.
+    fn verify(self, page: &[u8]) -> IndexerResult<()> {
+        if !self.checksummed || page.len() < PAGE_SIZE {
+            return Ok(());
+        }
+        let (body, trailer) = page[..PAGE_SIZE].split_at(self.usable_bytes());
+        if crc32fast::hash(body).to_le_bytes() == trailer {
+            return Ok(());
+        }
+        if page[..PAGE_SIZE].iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+        Err(IndexerError::ChecksumError)
+    }
+}
+
+/// Counters for writes to the leaf data file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// Number of write calls issued to the data file
+    pub data_writes: u64,
+    /// Total bytes written to the data file
+    pub bytes_written: u64,
+}
+
+/// Live counters behind `IoStats`
+#[derive(Debug, Default)]
+struct IoCounters {
+    data_writes: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl IoCounters {
+    /// Count one write to the data file
+    fn record(&self, bytes: usize) {
+        self.data_writes.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// Write a page to the data file, retrying transient IO failures
+fn write_page<F: Write + Seek>(
+    data_file: &Mutex<F>,
+    retry_policy: &RetryPolicy,
+    io: &IoCounters,
+    page_id: usize,
+    page: &Arc<RwLock<Vec<u8>>>,
+) -> IndexerResult<()> {
+    let offset = page_id * PAGE_SIZE;
+    let page_data = page.read();
+
+    retry_policy.run(|| {
+        let mut file = data_file.lock();
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.write_all(&page_data)?;
+        file.flush()?;
+        Ok(())
+    })?;
+    io.record(page_data.len());
+    Ok(())
+}
+
+/// Everything the sync path touches, cloned out of the tree so the
+/// background flusher can run it on its own thread
+#[derive(Clone)]
+struct SyncHandles {
+    metadata: Arc<RwLock<TreeMetadata>>,
+    data_file: Arc<Mutex<File>>,
+    metadata_file: Arc<Mutex<File>>,
+    wal: Option<WriteAheadLog>,
+    mmap: Option<Arc<Mutex<MmapMut>>>,
+    cache: Arc<Mutex<PageCache>>,
+    retry_policy: RetryPolicy,
+    durability: DurabilityLevel,
+    io: Arc<IoCounters>,
+    root_history: Arc<Mutex<RootHistory>>,
+}
+
+impl SyncHandles {
+    /// Flush pending writes as far as `durability` requires, then truncate
+    /// the WAL once the data file no longer depends on it
+    fn sync(&self) -> IndexerResult<()> {
+        // Writers hold the metadata lock from their WAL entry to their page
+        // write, so none can slip in between the flush and the truncation
+        let _metadata = self.metadata.write();
+
+        // Flush memory-mapped region
+        if let Some(mmap) = &self.mmap {
+            let mmap = mmap.lock();
+            let flushed = match self.durability {
+                DurabilityLevel::None => mmap.flush_async(),
+                DurabilityLevel::Flush | DurabilityLevel::Fsync => mmap.flush(),
+            };
+            flushed.map_err(|e| IndexerError::StorageError(e.to_string()))?;
+        }
+
+        // Flush all cached pages
+        let cache = self.cache.lock();
+        for (page_id, page) in cache.pages() {
+            write_page(
+                &self.data_file,
+                &self.retry_policy,
+                &self.io,
+                *page_id,
+                page,
+            )?;
+        }
+
+        // Flush files
+        {
+            let mut file = self.data_file.lock();
+            file.flush()?;
+        }
+
+        {
+            let mut file = self.metadata_file.lock();
+            file.flush()?;
+        }
+
+        if self.durability == DurabilityLevel::Fsync {
+            self.data_file.lock().sync_data()?;
+            self.metadata_file.lock().sync_data()?;
+        }
+
+        self.root_history.lock().save()?;
+
+        // Under `None` the mapped region may not be written back yet, so the
+        // WAL is kept until recovery on the next open replays and truncates it
+        if self.durability == DurabilityLevel::None {
+            return Ok(());
+        }
+
+        // Truncate WAL after successful sync
+        if let Some(wal) = &self.wal {
+            wal.truncate()?;
+            if self.durability == DurabilityLevel::Fsync {
+                wal.sync_data()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Thread that syncs the tree every `sync_interval`
+struct BackgroundSync {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl BackgroundSync {
+    fn spawn(handles: SyncHandles, interval: std::time::Duration) -> IndexerResult<Self> {
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name("imt-sync".to_string())
+            .spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    if let Err(e) = handles.sync() {
+                        log::error!("background sync failed: {e}");
+                    }
+                }
+            })?;
+        Ok(Self { stop, handle })
+    }
+
+    /// Signal the thread to stop and wait for it to exit
+    fn stop(self) {
+        // A send error means the thread has already exited
+        let _ = self.stop.send(());
+        if self.handle.join().is_err() {
+            log::error!("background sync thread panicked");
+        }
+    }
+}
 
 /// Persistent Merkle tree with file-based storage
 pub struct PersistentMerkleTree {
@@ -35,6 +330,24 @@ pub struct PersistentMerkleTree {
 
     // In-memory tree for root computation
     memory_tree: Arc<RwLock<IncrementalMerkleTree>>,
+
+    // Leaf slot and page trailer layout of the data file
+    layout: PageLayout,
+
+    // Hash algorithm recorded in the metadata
+    hash_algorithm: HashAlgorithm,
+
+    // Number of WAL entries replayed when the tree was opened
+    recovered_entries: usize,
+
+    // Data file write counters
+    io: Arc<IoCounters>,
+
+    // Recent roots, saved alongside the data on sync
+    root_history: Arc<Mutex<RootHistory>>,
+
+    // Periodic flusher, running when background sync is enabled
+    background_sync: Option<BackgroundSync>,
 }
 
 impl PersistentMerkleTree {
@@ -77,21 +390,30 @@ impl PersistentMerkleTree {
             None
         };
 
-        // Initialize zero hashes
-        let zero_hashes = Self::compute_zero_hashes();
-
         // Load or create metadata
-        let metadata = Self::load_or_create_metadata(&metadata_file, &zero_hashes)?;
+        let metadata = Self::load_or_create_metadata(&metadata_file, &config)?;
+        let layout = PageLayout::of(&metadata);
+        let hash_algorithm = metadata.hash_algorithm;
+
+        // Initialize zero hashes
+        let zero_hashes = hash_algorithm.zero_hashes(metadata.depth);
 
         // Initialize cache
         let cache_pages = config.cache_size / PAGE_SIZE;
         let cache = Arc::new(Mutex::new(PageCache::new(cache_pages)));
 
         // Create in-memory tree for root computation
-        let memory_tree = Arc::new(RwLock::new(IncrementalMerkleTree::new(20)));
+        let memory_tree = Arc::new(RwLock::new(
+            IncrementalMerkleTree::new(metadata.depth)
+                .with_hasher(hash_algorithm)
+                .with_max_leaf_size(config.max_leaf_size),
+        ));
 
         // Setup memory mapping for hot data
-        let mmap = Self::setup_memory_mapping(&data_file)?;
+        let mmap = Self::setup_memory_mapping(&data_file, config.enable_mmap)?;
+
+        let root_history =
+            RootHistory::open(config.data_dir.join("roots.dat"), config.root_history_size)?;
 
         let mut tree = Self {
             config,
@@ -103,57 +425,153 @@ impl PersistentMerkleTree {
             cache,
             zero_hashes,
             memory_tree,
+            layout,
+            hash_algorithm,
+            recovered_entries: 0,
+            io: Arc::new(IoCounters::default()),
+            root_history: Arc::new(Mutex::new(root_history)),
+            background_sync: None,
         };
 
         // Load existing data
         tree.load_existing_data()?;
 
+        // Replay anything left in the WAL by an unclean shutdown
+        tree.recovered_entries = tree.recover_from_wal()?;
+        check_root(&tree.metadata.read(), tree.memory_tree.read().root())?;
+
+        // Roots recorded after the last sync are lost on a crash, but the
+        // current one is always known
+        let root = tree.root();
+        tree.root_history.lock().record(root);
+
+        let interval = tree.config.sync_interval;
+        if tree.config.background_sync && !interval.is_zero() {
+            tree.background_sync = Some(BackgroundSync::spawn(tree.sync_handles(), interval)?);
+        }
+
         Ok(tree)
     }
 
-    /// Compute zero hashes for all levels
-    fn compute_zero_hashes() -> Vec<Hash> {
-        let mut zero_hashes = Vec::with_capacity(DEFAULT_TREE_DEPTH + 1);
+    /// Open an existing data directory without the ability to modify it.
+    ///
+    /// Files are opened and memory-mapped read-only and the WAL is never
+    /// opened, so entries left by an unclean shutdown are not replayed.
+    pub fn open_read_only(config: StorageConfig) -> IndexerResult<ReadOnlyPersistentTree> {
+        let data_file = File::open(config.data_dir.join("leaves.dat"))?;
+        let mut metadata_file = File::open(config.data_dir.join("metadata.dat"))?;
 
-        // Level 0: hash of single zero byte
-        let base_zero = hash_bytes(&[0u8]);
-        zero_hashes.push(base_zero);
-
-        // Higher levels: zero_{i+1} = hash_pair(zero_i, zero_i)
-        for i in 0..DEFAULT_TREE_DEPTH {
-            let next = crate::utils::internal::hash_pair(&zero_hashes[i], &zero_hashes[i]);
-            zero_hashes.push(next);
+        let mut buffer = Vec::new();
+        metadata_file.read_to_end(&mut buffer)?;
+        let metadata = Self::metadata_from_bytes(&buffer, &config)?;
+
+        let mut memory_tree =
+            IncrementalMerkleTree::new(metadata.depth).with_hasher(metadata.hash_algorithm);
+        memory_tree.set_next_index(metadata.next_index)?;
+
+        if data_file.metadata()?.len() > 0 {
+            let mmap = unsafe {
+                MmapOptions::new()
+                    .map(&data_file)
+                    .map_err(|e| IndexerError::StorageError(e.to_string()))?
+            };
+            let layout = PageLayout::of(&metadata);
+            for index in 0..metadata.next_index {
+                if let Some(leaf_hash) = layout.read_leaf(&mmap, index)? {
+                    memory_tree.set_leaf_hash(index, leaf_hash)?;
+                }
+            }
         }
 
-        zero_hashes
+        check_root(&metadata, memory_tree.root())?;
+
+        Ok(ReadOnlyPersistentTree {
+            metadata,
+            memory_tree,
+        })
+    }
+
+    /// Read the leaf count and root hash of the tree stored in `dir`
+    /// without opening it.
+    ///
+    /// Only the leading metadata fields are decoded and the checksum is
+    /// not verified, so this is meant for status reporting.
+    pub fn peek_metadata(dir: &Path) -> IndexerResult<(usize, Hash)> {
+        let file = File::open(dir.join("metadata.dat"))?;
+
+        // Field order of TreeMetadata: version, depth, next_index, root_hash, ...
+        let (_version, _depth, next_index, root_hash): (u32, usize, usize, Hash) =
+            bincode::deserialize_from(BufReader::new(file))
+                .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
+
+        Ok((next_index, root_hash))
     }
 
     /// Load or create metadata
     fn load_or_create_metadata(
         metadata_file: &Arc<Mutex<File>>,
-        zero_hashes: &[Hash],
+        config: &StorageConfig,
     ) -> IndexerResult<TreeMetadata> {
         let mut file = metadata_file.lock();
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
 
+        Self::metadata_from_bytes(&buffer, config)
+    }
+
+    /// Decode metadata read from disk, or create it for an empty file.
+    ///
+    /// The leaf layout and hash algorithm in `config` only apply to new
+    /// trees; existing files keep the settings recorded in their metadata.
+    /// The depth must match the recorded one.
+    fn metadata_from_bytes(buffer: &[u8], config: &StorageConfig) -> IndexerResult<TreeMetadata> {
         if buffer.is_empty() {
+            if config.depth == 0 || config.depth > 63 {
+                return Err(IndexerError::InvalidData(format!(
+                    "invalid tree depth: {}",
+                    config.depth
+                )));
+            }
+
             // Create new metadata
-            let root_hash = zero_hashes[DEFAULT_TREE_DEPTH];
-            Ok(TreeMetadata::new(DEFAULT_TREE_DEPTH, 0, root_hash))
-        } else {
-            // Load existing metadata
-            let metadata: TreeMetadata = bincode::deserialize(&buffer)
-                .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
-            metadata.verify_checksum()?;
-            Ok(metadata)
+            let layout = if config.pack_leaves {
+                LeafLayout::Packed
+            } else {
+                LeafLayout::Flagged
+            };
+            let root_hash = config.hash_algorithm.zero_hashes(config.depth)[config.depth];
+            return Ok(TreeMetadata::new(config.depth, 0, root_hash)
+                .with_leaf_layout(layout)
+                .with_hash_algorithm(config.hash_algorithm));
+        }
+
+        // Load existing metadata
+        let metadata = TreeMetadata::from_bytes(buffer)?;
+        if metadata.depth != config.depth {
+            return Err(IndexerError::InvalidData(format!(
+                "tree was created with depth {} but opened with depth {}",
+                metadata.depth, config.depth
+            )));
+        }
+        if !metadata.uses_current_hashing() {
+            return Err(IndexerError::InvalidData(format!(
+                "tree metadata version {} predates domain-tagged Poseidon hashing; \
+                 rebuild the tree from its leaf data",
+                metadata.version
+            )));
         }
+        Ok(metadata)
     }
 
-    /// Setup memory mapping for hot data
+    /// Setup memory mapping for hot data, or `None` when mmap is disabled
     fn setup_memory_mapping(
         data_file: &Arc<Mutex<File>>,
+        enabled: bool,
     ) -> IndexerResult<Option<Arc<Mutex<MmapMut>>>> {
+        if !enabled {
+            return Ok(None);
+        }
+
         let file = data_file.lock();
 
         // Ensure file has minimum size for memory mapping (1MB)
@@ -198,12 +616,61 @@ impl PersistentMerkleTree {
         Ok(())
     }
 
+    /// Replay WAL entries onto the data file and in-memory tree.
+    ///
+    /// Entries are applied in order, so later writes to the same index win.
+    /// Returns the number of entries replayed.
+    fn recover_from_wal(&mut self) -> IndexerResult<usize> {
+        let entries = match &self.wal {
+            Some(wal) => wal.read_entries()?,
+            None => return Ok(0),
+        };
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        let mut metadata = self.metadata.write();
+        for entry in &entries {
+            self.write_leaf_to_disk(entry.index, &entry.hash)?;
+            let mut memory_tree = self.memory_tree.write();
+            memory_tree.set_leaf_hash(entry.index, entry.hash)?;
+            metadata.next_index = metadata.next_index.max(entry.index + 1);
+        }
+
+        self.refresh_root(&mut metadata);
+        self.save_metadata(&metadata)?;
+        drop(metadata);
+
+        // Replayed entries are now on disk. Write them back synchronously
+        // even under `DurabilityLevel::None`, whose `sync` keeps the WAL, so
+        // the WAL is truncated and the next open has nothing to replay.
+        let mut handles = self.sync_handles();
+        if handles.durability == DurabilityLevel::None {
+            handles.durability = DurabilityLevel::Flush;
+        }
+        handles.sync()?;
+
+        log::info!("recovered {} WAL entries", entries.len());
+        Ok(entries.len())
+    }
+
+    /// Whether WAL entries were replayed when the tree was opened
+    pub fn was_recovered(&self) -> bool {
+        self.recovered_entries > 0
+    }
+
+    /// Number of WAL entries replayed when the tree was opened
+    pub fn recovered_entry_count(&self) -> usize {
+        self.recovered_entries
+    }
+
     /// Append a new leaf to the tree
     pub fn append(&mut self, leaf_data: &[u8]) -> IndexerResult<usize> {
-        let leaf_hash = hash_bytes(leaf_data);
+        self.memory_tree.read().check_leaf_size(leaf_data)?;
+        let leaf_hash = self.hash_algorithm.hash_leaf(leaf_data);
 
         let mut metadata = self.metadata.write();
-        if metadata.next_index >= (1 << DEFAULT_TREE_DEPTH) {
+        if metadata.next_index >= (1 << metadata.depth) {
             return Err(IndexerError::TreeFull);
         }
 
@@ -227,21 +694,134 @@ impl PersistentMerkleTree {
 
         // Update metadata
         metadata.next_index = index + 1;
-        metadata.root_hash = self.compute_root_hash();
+        self.refresh_root(&mut metadata);
         self.save_metadata(&metadata)?;
 
         Ok(index)
     }
 
+    /// Append many leaves at once, returning the index of the first.
+    ///
+    /// All affected pages are assembled in memory and written with a
+    /// single call, and the root and metadata are updated once at the end.
+    pub fn bulk_load<T: AsRef<[u8]>>(&mut self, leaves: &[T]) -> IndexerResult<usize> {
+        {
+            let memory_tree = self.memory_tree.read();
+            for leaf in leaves {
+                memory_tree.check_leaf_size(leaf.as_ref())?;
+            }
+        }
+        let hashes: Vec<Hash> = leaves
+            .iter()
+            .map(|leaf| self.hash_algorithm.hash_leaf(leaf.as_ref()))
+            .collect();
+
+        let mut metadata = self.metadata.write();
+        let start = metadata.next_index;
+        if start + hashes.len() > (1 << metadata.depth) {
+            return Err(IndexerError::TreeFull);
+        }
+        if hashes.is_empty() {
+            return Ok(start);
+        }
+
+        // Write to WAL first if enabled
+        if let Some(wal) = &self.wal {
+            for (offset, hash) in hashes.iter().enumerate() {
+                wal.write_entry(&WalEntry::new(start + offset, *hash))?;
+            }
+        }
+
+        self.write_leaves_contiguous(start, &hashes)?;
+
+        // Update in-memory tree
+        {
+            let mut memory_tree = self.memory_tree.write();
+            for (offset, hash) in hashes.iter().enumerate() {
+                memory_tree.set_leaf_hash(start + offset, *hash)?;
+            }
+        }
+
+        // Update metadata
+        metadata.next_index = start + hashes.len();
+        self.refresh_root(&mut metadata);
+        self.save_metadata(&metadata)?;
+
+        Ok(start)
+    }
+
+    /// Write consecutive leaves starting at `start` with one write covering
+    /// every page they touch
+    fn write_leaves_contiguous(&self, start: usize, hashes: &[Hash]) -> IndexerResult<()> {
+        let slot_size = self.layout.slot_size();
+        let leaves_per_page = self.layout.leaves_per_page();
+        let first_page = start / leaves_per_page;
+        let last_page = (start + hashes.len() - 1) / leaves_per_page;
+
+        let mut buffer = vec![0u8; (last_page - first_page + 1) * PAGE_SIZE];
+
+        // The first and last pages may hold leaves outside the batch
+        let mut cache = self.cache.lock();
+        for page_id in [first_page, last_page] {
+            let existing = match cache.get(page_id) {
+                Some(page) => page.read().clone(),
+                None => self.load_page_from_disk(page_id)?,
+            };
+            let page_start = (page_id - first_page) * PAGE_SIZE;
+            let len = existing.len().min(PAGE_SIZE);
+            buffer[page_start..page_start + len].copy_from_slice(&existing[..len]);
+        }
+
+        for (offset, hash) in hashes.iter().enumerate() {
+            let index = start + offset;
+            let page_start = (index / leaves_per_page - first_page) * PAGE_SIZE;
+            let leaf_start = page_start + (index % leaves_per_page) * slot_size;
+            self.layout
+                .encode_slot(&mut buffer[leaf_start..leaf_start + slot_size], hash);
+        }
+        for page in buffer.chunks_exact_mut(PAGE_SIZE) {
+            self.layout.seal(page);
+        }
+
+        self.config.retry_policy.run(|| {
+            let mut file = self.data_file.lock();
+            file.seek(SeekFrom::Start((first_page * PAGE_SIZE) as u64))?;
+            file.write_all(&buffer)?;
+            file.flush()?;
+            Ok(())
+        })?;
+        self.record_data_write(buffer.len());
+
+        // Cached copies of these pages are now stale
+        for page_id in first_page..=last_page {
+            cache.remove(page_id);
+        }
+
+        Ok(())
+    }
+
+    /// Count one write to the data file
+    fn record_data_write(&self, bytes: usize) {
+        self.io.record(bytes);
+    }
+
+    /// Write counters for the leaf data file since the tree was opened
+    pub fn io_stats(&self) -> IoStats {
+        IoStats {
+            data_writes: self.io.data_writes.load(Ordering::Relaxed),
+            bytes_written: self.io.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
     /// Update an existing leaf
     pub fn update(&mut self, index: usize, leaf_data: &[u8]) -> IndexerResult<()> {
-        let metadata = self.metadata.read();
+        let mut metadata = self.metadata.write();
         if index >= metadata.next_index {
             return Err(IndexerError::IndexOutOfBounds);
         }
-        drop(metadata);
 
-        let leaf_hash = hash_bytes(leaf_data);
+        self.memory_tree.read().check_leaf_size(leaf_data)?;
+        let leaf_hash = self.hash_algorithm.hash_leaf(leaf_data);
 
         // Write to WAL first if enabled
         if let Some(wal) = &self.wal {
@@ -259,8 +839,43 @@ impl PersistentMerkleTree {
         }
 
         // Update metadata
+        self.refresh_root(&mut metadata);
+        self.save_metadata(&metadata)?;
+
+        Ok(())
+    }
+
+    /// Apply a delta received from another tree, such as a leader this
+    /// tree follows.
+    ///
+    /// Every changed leaf goes through the WAL and data file like an
+    /// `update`, and the root and metadata are updated once at the end.
+    /// The tree must have exactly the delta's base leaf count.
+    pub fn apply_delta(&mut self, delta: &TreeDelta) -> IndexerResult<()> {
         let mut metadata = self.metadata.write();
-        metadata.root_hash = self.compute_root_hash();
+        if metadata.next_index != delta.base_len {
+            return Err(IndexerError::InvalidData(format!(
+                "delta expects {} leaves, tree has {}",
+                delta.base_len, metadata.next_index
+            )));
+        }
+
+        // Reject malformed deltas before anything is written, and change the
+        // in-memory tree only once the WAL and data file hold the delta
+        self.memory_tree.read().check_delta(delta)?;
+
+        if let Some(wal) = &self.wal {
+            for (index, hash) in &delta.changes {
+                wal.write_entry(&WalEntry::new(*index, *hash))?;
+            }
+        }
+        for (index, hash) in &delta.changes {
+            self.write_leaf_to_disk(*index, hash)?;
+        }
+
+        self.memory_tree.write().apply_delta(delta)?;
+        metadata.next_index = delta.new_len;
+        self.refresh_root(&mut metadata);
         self.save_metadata(&metadata)?;
 
         Ok(())
@@ -268,8 +883,9 @@ impl PersistentMerkleTree {
 
     /// Write a leaf to disk
     fn write_leaf_to_disk(&self, index: usize, leaf_hash: &Hash) -> IndexerResult<()> {
-        let page_id = index / LEAVES_PER_PAGE;
-        let page_offset = index % LEAVES_PER_PAGE;
+        let slot_size = self.layout.slot_size();
+        let page_id = index / self.layout.leaves_per_page();
+        let page_offset = index % self.layout.leaves_per_page();
 
         // Try to use memory mapping for hot pages
         if let Some(mmap) = &self.mmap {
@@ -277,11 +893,12 @@ impl PersistentMerkleTree {
             if page_id < mmap_pages {
                 let mut mmap = mmap.lock();
                 let page_start = page_id * PAGE_SIZE;
-                let leaf_start = page_start + page_offset * (LEAF_SIZE + 1);
+                let leaf_start = page_start + page_offset * slot_size;
 
-                // Write existence flag and hash
-                mmap[leaf_start] = 1;
-                mmap[leaf_start + 1..leaf_start + 1 + LEAF_SIZE].copy_from_slice(leaf_hash);
+                self.layout
+                    .encode_slot(&mut mmap[leaf_start..leaf_start + slot_size], leaf_hash);
+                self.layout
+                    .seal(&mut mmap[page_start..page_start + PAGE_SIZE]);
 
                 return Ok(());
             }
@@ -298,16 +915,17 @@ impl PersistentMerkleTree {
 
         {
             let mut page_data = page.write();
-            let leaf_start = page_offset * (LEAF_SIZE + 1);
+            let leaf_start = page_offset * slot_size;
 
-            // Ensure page is large enough
-            if page_data.len() < leaf_start + LEAF_SIZE + 1 {
+            // Ensure the page is full size so the trailer can be sealed
+            if page_data.len() < PAGE_SIZE {
                 page_data.resize(PAGE_SIZE, 0);
             }
 
-            // Write existence flag and hash
-            page_data[leaf_start] = 1;
-            page_data[leaf_start + 1..leaf_start + 1 + LEAF_SIZE].copy_from_slice(leaf_hash);
+            self.layout.encode_slot(
+                &mut page_data[leaf_start..leaf_start + slot_size],
+                leaf_hash,
+            );
         }
 
         // Write page back to disk
@@ -318,8 +936,9 @@ impl PersistentMerkleTree {
 
     /// Read a leaf from disk
     fn read_leaf_from_disk(&self, index: usize) -> IndexerResult<Option<Hash>> {
-        let page_id = index / LEAVES_PER_PAGE;
-        let page_offset = index % LEAVES_PER_PAGE;
+        let slot_size = self.layout.slot_size();
+        let page_id = index / self.layout.leaves_per_page();
+        let page_offset = index % self.layout.leaves_per_page();
 
         // Try memory mapping first
         if let Some(mmap) = &self.mmap {
@@ -327,15 +946,14 @@ impl PersistentMerkleTree {
             if page_id < mmap_pages {
                 let mmap = mmap.lock();
                 let page_start = page_id * PAGE_SIZE;
-                let leaf_start = page_start + page_offset * (LEAF_SIZE + 1);
-
-                if leaf_start + LEAF_SIZE < mmap.len() && mmap[leaf_start] == 1 {
-                    let mut hash = [0u8; 32];
-                    hash.copy_from_slice(&mmap[leaf_start + 1..leaf_start + 1 + LEAF_SIZE]);
-                    return Ok(Some(hash));
-                } else {
-                    return Ok(None);
+                let leaf_start = page_start + page_offset * slot_size;
+                if let Some(page) = mmap.get(page_start..page_start + PAGE_SIZE) {
+                    self.layout.verify(page)?;
                 }
+
+                return Ok(mmap
+                    .get(leaf_start..leaf_start + slot_size)
+                    .and_then(|slot| self.layout.decode_slot(slot)));
             }
         }
 
@@ -349,15 +967,11 @@ impl PersistentMerkleTree {
         };
 
         let page_data = page.read();
-        let leaf_start = page_offset * (LEAF_SIZE + 1);
+        let leaf_start = page_offset * slot_size;
 
-        if leaf_start + LEAF_SIZE < page_data.len() && page_data[leaf_start] == 1 {
-            let mut hash = [0u8; 32];
-            hash.copy_from_slice(&page_data[leaf_start + 1..leaf_start + 1 + LEAF_SIZE]);
-            Ok(Some(hash))
-        } else {
-            Ok(None)
-        }
+        Ok(page_data
+            .get(leaf_start..leaf_start + slot_size)
+            .and_then(|slot| self.layout.decode_slot(slot)))
     }
 
     /// Load a page from disk
@@ -370,20 +984,21 @@ impl PersistentMerkleTree {
         let bytes_read = file.read(&mut buffer)?;
 
         buffer.truncate(bytes_read);
+        self.layout.verify(&buffer)?;
         Ok(buffer)
     }
 
-    /// Write a page to disk
+    /// Seal a page with its checksum and write it to disk, retrying
+    /// transient IO failures
     fn write_page_to_disk(&self, page_id: usize, page: &Arc<RwLock<Vec<u8>>>) -> IndexerResult<()> {
-        let mut file = self.data_file.lock();
-        let offset = page_id * PAGE_SIZE;
-
-        file.seek(SeekFrom::Start(offset as u64))?;
-        let page_data = page.read();
-        file.write_all(&page_data)?;
-        file.flush()?;
-
-        Ok(())
+        self.layout.seal(&mut page.write());
+        write_page(
+            &self.data_file,
+            &self.config.retry_policy,
+            &self.io,
+            page_id,
+            page,
+        )
     }
 
     /// Compute the current root hash
@@ -392,21 +1007,28 @@ impl PersistentMerkleTree {
         memory_tree.root()
     }
 
-    /// Save metadata to disk
+    /// Store the current root in `metadata` and the root history
+    fn refresh_root(&self, metadata: &mut TreeMetadata) {
+        metadata.root_hash = self.compute_root_hash();
+        self.root_history.lock().record(metadata.root_hash);
+    }
+
+    /// Save metadata to disk, retrying transient IO failures
     fn save_metadata(&self, metadata: &TreeMetadata) -> IndexerResult<()> {
         let mut updated_metadata = metadata.clone();
         updated_metadata.update_checksum();
 
-        let mut file = self.metadata_file.lock();
-        file.seek(SeekFrom::Start(0))?;
-        file.set_len(0)?;
-
         let serialized = bincode::serialize(&updated_metadata)
             .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
-        file.write_all(&serialized)?;
-        file.flush()?;
 
-        Ok(())
+        self.config.retry_policy.run(|| {
+            let mut file = self.metadata_file.lock();
+            file.seek(SeekFrom::Start(0))?;
+            file.set_len(0)?;
+            file.write_all(&serialized)?;
+            file.flush()?;
+            Ok(())
+        })
     }
 
     /// Get current root hash
@@ -415,6 +1037,19 @@ impl PersistentMerkleTree {
         metadata.root_hash
     }
 
+    /// The last `root_history_size` roots the tree has had, oldest first.
+    ///
+    /// A root is recorded after every append, update and delta; `bulk_load`
+    /// records only the root it ends at. The history is saved on `sync`.
+    pub fn known_roots(&self) -> Vec<Hash> {
+        self.root_history.lock().roots()
+    }
+
+    /// Check whether `root` is one of the recent roots in `known_roots`
+    pub fn is_known_root(&self, root: &Hash) -> bool {
+        self.root_history.lock().contains(root)
+    }
+
     /// Get number of leaves
     pub fn len(&self) -> usize {
         let metadata = self.metadata.read();
@@ -426,49 +1061,245 @@ impl PersistentMerkleTree {
         self.len() == 0
     }
 
+    /// Occupancy and hit/miss counts of the page cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().stats()
+    }
+
+    /// Stored hash of the leaf at `index`, read through the mmap or page cache
+    pub fn leaf(&self, index: usize) -> IndexerResult<Option<Hash>> {
+        if index >= self.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        self.read_leaf_from_disk(index)
+    }
+
+    /// Get the hash algorithm the tree was built with
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
     /// Get zero hash for a given level
     pub fn zero_hash(&self, level: usize) -> Option<Hash> {
         self.zero_hashes.get(level).copied()
     }
 
-    /// Generate a Merkle proof for a leaf
-    pub fn prove(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+    /// Ratio of empty-but-allocated leaf slots to all allocated slots in the
+    /// data file, in `[0, 1]`.
+    ///
+    /// Preallocated space (such as the memory-mapped region) counts as
+    /// allocated, so a freshly created mmap-backed tree reports a high ratio.
+    pub fn fragmentation(&self) -> IndexerResult<f64> {
+        let file_len = self.data_file.lock().metadata()?.len() as usize;
+        let page_count = file_len.div_ceil(PAGE_SIZE);
+
+        let mut allocated = 0usize;
+        let mut empty = 0usize;
+        for page_id in 0..page_count {
+            let page = self.load_page_from_disk(page_id)?;
+            let slots = page
+                .chunks_exact(self.layout.slot_size())
+                .take(self.layout.leaves_per_page());
+            for slot in slots {
+                allocated += 1;
+                if self.layout.decode_slot(slot).is_none() {
+                    empty += 1;
+                }
+            }
+        }
+
+        if allocated == 0 {
+            return Ok(0.0);
+        }
+        Ok(empty as f64 / allocated as f64)
+    }
+
+    /// SHA-256 digest of all leaf hashes concatenated in index order.
+    ///
+    /// Replicas holding the same leaves produce the same digest, and it is
+    /// much cheaper to compute than a Merkle root, so it suits quick
+    /// equality checks between replicas.
+    pub fn leaves_digest(&self) -> IndexerResult<Hash> {
+        let len = self.len();
         let memory_tree = self.memory_tree.read();
-        memory_tree.prove(leaf_index)
+
+        let mut hasher = Sha256::new();
+        for index in 0..len {
+            let leaf_hash = memory_tree
+                .get_leaf_hash(index)
+                .ok_or(IndexerError::LeafNotAppended)?;
+            hasher.update(leaf_hash);
+        }
+        Ok(hasher.finalize().into())
     }
 
-    /// Flush all pending writes to disk
+    /// Generate a Merkle proof for a leaf.
+    ///
+    /// The proof always has exactly as many siblings as the tree's
+    /// configured depth, so it checks against a root of that depth. Levels
+    /// missing from the in-memory tree are filled with zero hashes, and any
+    /// extra levels are dropped.
+    pub fn prove(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+        let mut proof = self.memory_tree.read().prove(leaf_index)?;
+        let depth = self.metadata.read().depth;
+        if proof.siblings.len() != depth {
+            let natural = proof.siblings.len().min(depth);
+            proof.siblings.truncate(natural);
+            proof
+                .siblings
+                .extend_from_slice(&self.zero_hashes[natural..depth]);
+        }
+        Ok(proof)
+    }
+
+    /// Flush all pending writes to disk, as far as the configured
+    /// [`DurabilityLevel`] requires
     pub fn sync(&mut self) -> IndexerResult<()> {
-        // Flush memory-mapped region
-        if let Some(mmap) = &self.mmap {
-            let mmap = mmap.lock();
-            mmap.flush()
-                .map_err(|e| IndexerError::StorageError(e.to_string()))?;
+        self.sync_handles().sync()
+    }
+
+    /// Stop the background flusher, if running, and make a final sync
+    pub fn shutdown(&mut self) -> IndexerResult<()> {
+        if let Some(background_sync) = self.background_sync.take() {
+            background_sync.stop();
         }
+        self.sync()
+    }
 
-        // Flush all cached pages
-        let cache = self.cache.lock();
-        for (page_id, page) in cache.pages() {
-            self.write_page_to_disk(*page_id, page)?;
+    /// Whether a background flusher is running
+    pub fn has_background_sync(&self) -> bool {
+        self.background_sync.is_some()
+    }
+
+    fn sync_handles(&self) -> SyncHandles {
+        SyncHandles {
+            metadata: self.metadata.clone(),
+            data_file: self.data_file.clone(),
+            metadata_file: self.metadata_file.clone(),
+            wal: self.wal.clone(),
+            mmap: self.mmap.clone(),
+            cache: self.cache.clone(),
+            retry_policy: self.config.retry_policy.clone(),
+            durability: self.config.durability,
+            io: self.io.clone(),
+            root_history: self.root_history.clone(),
         }
+    }
 
-        // Flush files
-        {
-            let mut file = self.data_file.lock();
-            file.flush()?;
+    /// Make the leaves recorded in the WAL durable in the data file, then
+    /// truncate the WAL.
+    ///
+    /// Only the pages holding those leaves are flushed; unrelated cached
+    /// pages and the rest of the mapped region are left alone.
+    pub fn checkpoint_wal(&mut self) -> IndexerResult<()> {
+        let Some(wal) = &self.wal else {
+            return Ok(());
+        };
+        let entries = wal.read_entries()?;
+        if entries.is_empty() {
+            return Ok(());
         }
 
-        {
-            let mut file = self.metadata_file.lock();
-            file.flush()?;
+        let leaves_per_page = self.layout.leaves_per_page();
+        let mut page_ids: Vec<usize> = entries
+            .iter()
+            .map(|entry| entry.index / leaves_per_page)
+            .collect();
+        page_ids.sort_unstable();
+        page_ids.dedup();
+
+        let mmap_pages = (1024 * 1024) / PAGE_SIZE;
+        for page_id in page_ids {
+            if let Some(mmap) = &self.mmap {
+                if page_id < mmap_pages {
+                    mmap.lock()
+                        .flush_range(page_id * PAGE_SIZE, PAGE_SIZE)
+                        .map_err(|e| IndexerError::StorageError(e.to_string()))?;
+                    continue;
+                }
+            }
+
+            let page = self.cache.lock().get(page_id);
+            if let Some(page) = page {
+                self.write_page_to_disk(page_id, &page)?;
+            }
         }
 
-        // Truncate WAL after successful sync
-        if let Some(wal) = &self.wal {
-            wal.truncate()?;
+        self.data_file.lock().sync_data()?;
+        self.metadata_file.lock().sync_data()?;
+        wal.truncate()
+    }
+}
+
+impl Drop for PersistentMerkleTree {
+    fn drop(&mut self) {
+        if self.background_sync.is_some() {
+            if let Err(e) = self.shutdown() {
+                log::error!("failed to sync persistent tree on shutdown: {e}");
+            }
+        } else if self.config.sync_on_drop {
+            if let Err(e) = self.sync() {
+                log::error!("failed to sync persistent tree on drop: {e}");
+            }
         }
+    }
+}
 
-        Ok(())
+/// Fail if the root recomputed from the stored leaves is not the one recorded
+/// in the metadata, e.g. because they were hashed under another scheme
+fn check_root(metadata: &TreeMetadata, root: Hash) -> IndexerResult<()> {
+    if root != metadata.root_hash {
+        return Err(IndexerError::InvalidData(format!(
+            "stored leaves hash to root {} but the metadata records {}",
+            hex::encode(root),
+            hex::encode(metadata.root_hash)
+        )));
+    }
+    Ok(())
+}
+
+/// Read-only view of a persistent tree's data directory, for proof serving
+pub struct ReadOnlyPersistentTree {
+    metadata: TreeMetadata,
+    memory_tree: IncrementalMerkleTree,
+}
+
+impl ReadOnlyPersistentTree {
+    /// Get the root hash of the loaded leaves
+    pub fn root(&self) -> Hash {
+        self.memory_tree.root()
+    }
+
+    /// Get number of leaves
+    pub fn len(&self) -> usize {
+        self.metadata.next_index
+    }
+
+    /// Check if tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the hash stored at a leaf index
+    pub fn leaf_hash(&self, index: usize) -> Option<Hash> {
+        if index >= self.len() {
+            return None;
+        }
+        self.memory_tree.get_leaf_hash(index)
+    }
+
+    /// Generate a Merkle proof for a leaf
+    pub fn prove(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+        self.memory_tree.prove(leaf_index)
+    }
+}
+
+impl std::fmt::Debug for ReadOnlyPersistentTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadOnlyPersistentTree")
+            .field("len", &self.len())
+            .field("root", &hex::encode(self.root()))
+            .finish()
     }
 }
 
@@ -481,3 +1312,82 @@ impl std::fmt::Debug for PersistentMerkleTree {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+    use std::time::Duration;
+
+    /// Data file that fails a fixed number of writes before accepting data
+    struct FlakyFile {
+        failures_left: usize,
+        inner: Cursor<Vec<u8>>,
+    }
+
+    impl Write for FlakyFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "transient"));
+            }
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for FlakyFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn flaky_file(failures: usize) -> Mutex<FlakyFile> {
+        Mutex::new(FlakyFile {
+            failures_left: failures,
+            inner: Cursor::new(Vec::new()),
+        })
+    }
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy::new(
+            max_retries,
+            Duration::from_millis(1),
+            Duration::from_millis(2),
+        )
+    }
+
+    #[test]
+    fn test_write_page_retries_transient_failures() {
+        let file = flaky_file(2);
+        let io = IoCounters::default();
+        let page = Arc::new(RwLock::new(vec![7u8; PAGE_SIZE]));
+
+        write_page(&file, &fast_policy(3), &io, 1, &page).unwrap();
+
+        let file = file.into_inner();
+        assert_eq!(file.failures_left, 0);
+        let data = file.inner.into_inner();
+        assert_eq!(data.len(), 2 * PAGE_SIZE);
+        assert!(data[PAGE_SIZE..].iter().all(|&b| b == 7));
+        // Only the successful write is counted
+        assert_eq!(io.data_writes.load(Ordering::Relaxed), 1);
+        assert_eq!(io.bytes_written.load(Ordering::Relaxed), PAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn test_write_page_gives_up_after_max_retries() {
+        let file = flaky_file(3);
+        let io = IoCounters::default();
+        let page = Arc::new(RwLock::new(vec![7u8; PAGE_SIZE]));
+
+        let result = write_page(&file, &fast_policy(2), &io, 0, &page);
+
+        assert!(matches!(result, Err(IndexerError::IoError(_))));
+        assert!(file.into_inner().inner.into_inner().is_empty());
+        assert_eq!(io.data_writes.load(Ordering::Relaxed), 0);
+    }
+}
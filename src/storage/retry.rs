@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use crate::errors::{IndexerError, IndexerResult};
+
+/// Retry policy for disk operations that may fail transiently
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first failed attempt (0 = no retries)
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a new retry policy
+    pub fn new(max_retries: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// Create a policy that never retries
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Run an operation, retrying with exponential backoff on `IoError`.
+    ///
+    /// Other error kinds are returned immediately. If every attempt fails,
+    /// the error from the final attempt is returned.
+    pub fn run<T, F>(&self, mut op: F) -> IndexerResult<T>
+    where
+        F: FnMut() -> IndexerResult<T>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            match op() {
+                Err(IndexerError::IoError(err)) if attempt < self.max_retries => {
+                    attempt += 1;
+                    log::warn!(
+                        "disk operation failed (attempt {attempt}/{}): {err}",
+                        self.max_retries + 1
+                    );
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(10), Duration::from_millis(500))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Write};
+
+    /// Writer that fails a fixed number of times before accepting data
+    struct FlakyFile {
+        failures_left: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for FlakyFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "transient"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy::new(max_retries, Duration::from_millis(1), Duration::from_millis(2))
+    }
+
+    #[test]
+    fn test_retry_succeeds_after_transient_failures() {
+        let mut file = FlakyFile {
+            failures_left: 2,
+            written: Vec::new(),
+        };
+        let mut attempts = 0;
+
+        let result = fast_policy(3).run(|| {
+            attempts += 1;
+            file.write_all(b"page")?;
+            Ok(())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+        assert_eq!(file.written, b"page");
+    }
+
+    #[test]
+    fn test_retry_surfaces_final_error() {
+        let mut file = FlakyFile {
+            failures_left: 5,
+            written: Vec::new(),
+        };
+        let mut attempts = 0;
+
+        let result = fast_policy(2).run(|| {
+            attempts += 1;
+            file.write_all(b"page")?;
+            Ok(())
+        });
+
+        assert!(matches!(result, Err(IndexerError::IoError(_))));
+        assert_eq!(attempts, 3);
+        assert!(file.written.is_empty());
+    }
+
+    #[test]
+    fn test_retry_ignores_non_io_errors() {
+        let mut attempts = 0;
+
+        let result: IndexerResult<()> = fast_policy(3).run(|| {
+            attempts += 1;
+            Err(IndexerError::ChecksumError)
+        });
+
+        assert!(matches!(result, Err(IndexerError::ChecksumError)));
+        assert_eq!(attempts, 1);
+    }
+}
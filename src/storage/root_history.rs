@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use crate::errors::IndexerResult;
+use crate::utils::Hash;
+
+/// Ring buffer of the most recent tree roots, persisted to a sidecar file
+#[derive(Debug)]
+pub struct RootHistory {
+    roots: VecDeque<Hash>,
+    capacity: usize,
+    path: PathBuf,
+}
+
+impl RootHistory {
+    /// Open the history stored at `path`, keeping at most `capacity` roots.
+    ///
+    /// Starts empty if the file does not exist; if it holds more roots than
+    /// `capacity`, only the newest are kept.
+    pub fn open<P: AsRef<Path>>(path: P, capacity: usize) -> IndexerResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let roots: Vec<Hash> = if path.exists() {
+            let data = std::fs::read(&path)?;
+            if data.is_empty() {
+                Vec::new()
+            } else {
+                bincode::deserialize(&data)?
+            }
+        } else {
+            Vec::new()
+        };
+
+        let skip = roots.len().saturating_sub(capacity);
+        Ok(Self {
+            roots: roots.into_iter().skip(skip).collect(),
+            capacity,
+            path,
+        })
+    }
+
+    /// Record `root` as the newest, dropping the oldest beyond capacity.
+    /// Recording the current newest root again is a no-op.
+    pub fn record(&mut self, root: Hash) {
+        if self.capacity == 0 || self.roots.back() == Some(&root) {
+            return;
+        }
+        if self.roots.len() == self.capacity {
+            self.roots.pop_front();
+        }
+        self.roots.push_back(root);
+    }
+
+    /// Remembered roots, oldest first
+    pub fn roots(&self) -> Vec<Hash> {
+        self.roots.iter().copied().collect()
+    }
+
+    /// Check whether `root` is one of the remembered roots
+    pub fn contains(&self, root: &Hash) -> bool {
+        self.roots.contains(root)
+    }
+
+    /// Maximum number of roots remembered
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Write the history to its sidecar file
+    pub fn save(&self) -> IndexerResult<()> {
+        let data = bincode::serialize(&self.roots)?;
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_keeps_newest_roots_across_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("roots.dat");
+
+        let mut history = RootHistory::open(&path, 3).unwrap();
+        for i in 0..5u8 {
+            history.record([i; 32]);
+        }
+        history.record([4; 32]);
+        assert_eq!(history.roots(), vec![[2; 32], [3; 32], [4; 32]]);
+        assert!(!history.contains(&[1; 32]));
+        history.save().unwrap();
+
+        let reopened = RootHistory::open(&path, 2).unwrap();
+        assert_eq!(reopened.roots(), vec![[3; 32], [4; 32]]);
+    }
+}
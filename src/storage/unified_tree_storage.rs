@@ -1,10 +1,17 @@
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
 
 use crate::errors::{IndexerError, IndexerResult};
+use crate::storage::{CommitmentStore, LeafDataStore, NullifierIndex, StorageConfig};
 use crate::tree::{Commitment, IncrementalMerkleTree, MerkleProof};
 use crate::utils::Hash;
 
+/// Sidecar files kept next to the tree data
+const NULLIFIERS_FILE: &str = "nullifiers.dat";
+const COMMITMENTS_FILE: &str = "commitments.dat";
+const LEAF_DATA_FILE: &str = "leaf_data.dat";
+
 /// Unified trait for incremental merkle tree storage
 pub trait TreeStorage: Send {
     /// Get the root hash of the tree
@@ -18,15 +25,35 @@ pub trait TreeStorage: Send {
         self.len() == 0
     }
 
-    /// Insert a commitment into the tree
-    fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<()>;
+    /// Append raw leaf data to the tree, returning its leaf index
+    fn append(&mut self, leaf_data: &[u8]) -> IndexerResult<usize>;
+
+    /// Insert a commitment into the tree, returning its leaf index
+    fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<usize>;
+
+    /// Insert a commitment at a specific leaf index, returning that index
+    fn insert_commitment_at(
+        &mut self,
+        index: usize,
+        commitment: &Commitment,
+    ) -> IndexerResult<usize>;
 
     /// Get a commitment by index
     fn get_commitment(&self, commitment_index: u64) -> IndexerResult<Option<Commitment>>;
 
+    /// Check whether a commitment with this nullifier has been inserted
+    fn contains_nullifier(&self, nullifier: &Hash) -> bool;
+
     /// Generate a Merkle proof for a given index
     fn prove(&self, index: usize) -> IndexerResult<MerkleProof>;
 
+    /// Drop every leaf at or after `len`, along with the commitments and
+    /// nullifiers recorded for them
+    fn truncate(&mut self, len: usize) -> IndexerResult<()>;
+
+    /// Root the tree had when it held only its first `len` leaves
+    fn root_at(&self, len: usize) -> IndexerResult<Hash>;
+
     /// Save the tree to persistent storage
     fn save(&self) -> IndexerResult<()>;
 
@@ -53,6 +80,11 @@ pub struct IncrementalTreeStorage {
     tree: Arc<RwLock<IncrementalMerkleTree>>,
     data_path: std::path::PathBuf,
     metadata_path: std::path::PathBuf,
+    nullifiers: RwLock<NullifierIndex>,
+    commitments: RwLock<CommitmentStore>,
+    leaf_data: RwLock<LeafDataStore>,
+    // Whether raw leaf data is recorded in the sidecar on insert
+    retain_leaf_data: bool,
 }
 
 impl IncrementalTreeStorage {
@@ -63,6 +95,9 @@ impl IncrementalTreeStorage {
 
         let data_path = data_dir.join("incremental_tree.dat");
         let metadata_path = data_dir.join("incremental_tree_metadata.dat");
+        let nullifiers = NullifierIndex::open(data_dir.join(NULLIFIERS_FILE))?;
+        let commitments = CommitmentStore::open(data_dir.join(COMMITMENTS_FILE))?;
+        let leaf_data = LeafDataStore::open(data_dir.join(LEAF_DATA_FILE))?;
 
         // Try to load existing tree, otherwise create new one
         let tree = if data_path.exists() {
@@ -75,9 +110,172 @@ impl IncrementalTreeStorage {
             tree: Arc::new(RwLock::new(tree)),
             data_path,
             metadata_path,
+            nullifiers: RwLock::new(nullifiers),
+            commitments: RwLock::new(commitments),
+            leaf_data: RwLock::new(leaf_data),
+            retain_leaf_data: false,
         })
     }
 
+    /// Keep the raw data of every inserted leaf in a sidecar file so it can
+    /// be read back with `leaf_data`. Off by default since it roughly
+    /// doubles storage.
+    pub fn with_leaf_data_retention(mut self, retain: bool) -> Self {
+        self.retain_leaf_data = retain;
+        self
+    }
+
+    /// Get the raw data inserted at a leaf index, or `None` if it was not
+    /// retained
+    pub fn leaf_data(&self, index: usize) -> IndexerResult<Option<Vec<u8>>> {
+        if index >= self.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        let leaf_data = self.leaf_data.read().unwrap();
+        Ok(leaf_data.get(index).map(<[u8]>::to_vec))
+    }
+
+    /// Replace the in-memory tree with one loaded from a snapshot directory
+    /// written by another `IncrementalTreeStorage`.
+    ///
+    /// The snapshot's tree is verified against the root in its metadata
+    /// before anything is replaced. Readers see either the old tree or the
+    /// new one, never a mix. Call `save` afterwards to persist the swap.
+    pub fn load_and_swap(&self, snapshot_path: &Path) -> IndexerResult<()> {
+        let data = std::fs::read(snapshot_path.join("incremental_tree.dat"))?;
+        let mut tree: IncrementalMerkleTree = bincode::deserialize(&data)?;
+        tree.rebuild_frontier()?;
+
+        let metadata_data = std::fs::read(snapshot_path.join("incremental_tree_metadata.dat"))?;
+        let metadata: IncrementalTreeMetadata = bincode::deserialize(&metadata_data)?;
+        if tree.root() != metadata.root_hash {
+            return Err(IndexerError::InvalidData(format!(
+                "snapshot root {} does not match its metadata root {}",
+                hex::encode(tree.root()),
+                hex::encode(metadata.root_hash)
+            )));
+        }
+
+        let snapshot_nullifiers = NullifierIndex::open(snapshot_path.join(NULLIFIERS_FILE))?;
+        let snapshot_commitments = CommitmentStore::open(snapshot_path.join(COMMITMENTS_FILE))?;
+        let snapshot_leaf_data = LeafDataStore::open(snapshot_path.join(LEAF_DATA_FILE))?;
+
+        let mut current = self.tree.write().unwrap();
+        let mut nullifiers = self.nullifiers.write().unwrap();
+        let mut commitments = self.commitments.write().unwrap();
+        let mut leaf_data = self.leaf_data.write().unwrap();
+        *current = tree;
+        nullifiers.replace_with(snapshot_nullifiers);
+        commitments.replace_with(snapshot_commitments);
+        leaf_data.replace_with(snapshot_leaf_data);
+
+        Ok(())
+    }
+
+    /// Pair every stored commitment with its proof against the current root,
+    /// in leaf order.
+    ///
+    /// Leaves appended as raw data have no commitment and are not returned.
+    pub fn iter_with_proofs(&self) -> IndexerResult<Vec<(Commitment, MerkleProof)>> {
+        let tree = self.tree.read().unwrap();
+        let commitments = self.commitments.read().unwrap();
+        let proofs = tree.prove_all();
+
+        commitments
+            .iter()
+            .map(|(leaf_index, commitment)| {
+                let proof = proofs
+                    .get(leaf_index)
+                    .cloned()
+                    .ok_or(IndexerError::LeafNotAppended)?;
+                Ok((commitment.clone(), proof))
+            })
+            .collect()
+    }
+
+    /// Call `f` with each stored commitment and its leaf index, in leaf
+    /// order, stopping at the first error.
+    ///
+    /// Nothing is collected, so this suits scans over large stores.
+    pub fn for_each_commitment<F>(&self, mut f: F) -> IndexerResult<()>
+    where
+        F: FnMut(usize, &Commitment) -> IndexerResult<()>,
+    {
+        let commitments = self.commitments.read().unwrap();
+        for (leaf_index, commitment) in commitments.iter() {
+            f(leaf_index, commitment)?;
+        }
+        Ok(())
+    }
+
+    /// Check that stored commitments occupy the gapless leaf range `[0, n)`
+    /// with each `commitment_index` equal to its leaf index.
+    pub fn verify_contiguous(&self) -> IndexerResult<bool> {
+        let commitments = self.commitments.read().unwrap();
+        let contiguous =
+            commitments
+                .iter()
+                .enumerate()
+                .all(|(position, (leaf_index, commitment))| {
+                    leaf_index == position && commitment.commitment_index == leaf_index as u64
+                });
+        Ok(contiguous)
+    }
+
+    /// Average on-disk bytes per stored commitment, counting the tree data,
+    /// its metadata and every sidecar index. Returns 0.0 with no commitments.
+    ///
+    /// Reflects the files as last saved.
+    pub fn bytes_per_commitment(&self) -> f64 {
+        let count = self.commitments.read().unwrap().len();
+        if count == 0 {
+            return 0.0;
+        }
+
+        let files = [
+            self.data_path.clone(),
+            self.metadata_path.clone(),
+            self.data_path.with_file_name(NULLIFIERS_FILE),
+            self.data_path.with_file_name(COMMITMENTS_FILE),
+            self.data_path.with_file_name(LEAF_DATA_FILE),
+        ];
+        let total_bytes: u64 = files
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        total_bytes as f64 / count as f64
+    }
+
+    /// Retain a leaf's raw data if retention is enabled
+    fn record_leaf_data(&self, leaf_index: usize, leaf_data: &[u8]) {
+        if self.retain_leaf_data {
+            self.leaf_data
+                .write()
+                .unwrap()
+                .insert(leaf_index, leaf_data.to_vec());
+        }
+    }
+
+    /// Remember an inserted commitment and its nullifier
+    fn record_commitment(&self, leaf_index: usize, commitment: &Commitment) {
+        self.record_leaf_data(leaf_index, &commitment.to_bytes());
+        self.nullifiers
+            .write()
+            .unwrap()
+            .insert(commitment.nullifier);
+        self.commitments
+            .write()
+            .unwrap()
+            .insert(leaf_index, commitment.clone());
+    }
+
+    /// Number of distinct nullifiers committed so far
+    pub fn nullifier_count(&self) -> usize {
+        self.nullifiers.read().unwrap().len()
+    }
+
     /// Load a tree from disk
     fn load_tree(path: &Path, depth: usize) -> IndexerResult<IncrementalMerkleTree> {
         use bincode::deserialize;
@@ -102,11 +300,12 @@ impl IncrementalTreeStorage {
                 })?;
                 Ok(tree)
             }
-            Err(e) => {
-                eprintln!("Failed to deserialize incremental tree: {e}");
-                // If bincode fails, create a new tree
-                Ok(IncrementalMerkleTree::with_depth(depth))
-            }
+            // Never fall back to an empty tree here: the next save would
+            // overwrite the undecodable data
+            Err(e) => Err(IndexerError::StorageError(format!(
+                "failed to decode incremental tree at {}: {e}",
+                path.display()
+            ))),
         }
     }
 
@@ -144,6 +343,10 @@ impl IncrementalTreeStorage {
         metadata_writer.write_all(&metadata_data)?;
         metadata_writer.flush()?;
 
+        self.nullifiers.read().unwrap().save()?;
+        self.commitments.read().unwrap().save()?;
+        self.leaf_data.read().unwrap().save()?;
+
         Ok(())
     }
 }
@@ -159,18 +362,38 @@ impl TreeStorage for IncrementalTreeStorage {
         tree.len()
     }
 
-    fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<()> {
+    fn append(&mut self, leaf_data: &[u8]) -> IndexerResult<usize> {
         let mut tree = self.tree.write().unwrap();
-        tree.insert_commitment(commitment)?;
-        Ok(())
+        let index = tree.append(leaf_data)?;
+        self.record_leaf_data(index, leaf_data);
+        Ok(index)
+    }
+
+    fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<usize> {
+        let mut tree = self.tree.write().unwrap();
+        let index = tree.insert_commitment(commitment)?;
+        self.record_commitment(index, commitment);
+        Ok(index)
+    }
+
+    fn insert_commitment_at(
+        &mut self,
+        index: usize,
+        commitment: &Commitment,
+    ) -> IndexerResult<usize> {
+        let mut tree = self.tree.write().unwrap();
+        let index = tree.insert_commitment_at(index, commitment)?;
+        self.record_commitment(index, commitment);
+        Ok(index)
+    }
+
+    fn get_commitment(&self, commitment_index: u64) -> IndexerResult<Option<Commitment>> {
+        let commitments = self.commitments.read().unwrap();
+        Ok(commitments.get(commitment_index).cloned())
     }
 
-    fn get_commitment(&self, _commitment_index: u64) -> IndexerResult<Option<Commitment>> {
-        // For incremental trees, we need to reconstruct the commitment from the stored data
-        // This is a simplified implementation
-        Err(IndexerError::NotImplemented(
-            "Commitment retrieval not yet implemented for incremental trees".to_string(),
-        ))
+    fn contains_nullifier(&self, nullifier: &Hash) -> bool {
+        self.nullifiers.read().unwrap().contains(nullifier)
     }
 
     fn prove(&self, index: usize) -> IndexerResult<MerkleProof> {
@@ -178,6 +401,32 @@ impl TreeStorage for IncrementalTreeStorage {
         tree.prove(index)
     }
 
+    fn truncate(&mut self, len: usize) -> IndexerResult<()> {
+        let mut tree = self.tree.write().unwrap();
+        tree.truncate(len)?;
+
+        // Forget only nullifiers that no surviving commitment still uses
+        let mut commitments = self.commitments.write().unwrap();
+        let mut dropped: HashSet<Hash> = commitments
+            .truncate(len)
+            .iter()
+            .map(|commitment| commitment.nullifier)
+            .collect();
+        for (_, commitment) in commitments.iter() {
+            dropped.remove(&commitment.nullifier);
+        }
+        let mut nullifiers = self.nullifiers.write().unwrap();
+        for nullifier in &dropped {
+            nullifiers.remove(nullifier);
+        }
+        self.leaf_data.write().unwrap().truncate(len);
+        Ok(())
+    }
+
+    fn root_at(&self, len: usize) -> IndexerResult<Hash> {
+        self.tree.read().unwrap().root_at(len)
+    }
+
     fn save(&self) -> IndexerResult<()> {
         self.save_tree()
     }
@@ -212,3 +461,292 @@ pub fn create_tree_storage<P: AsRef<Path>>(
         }
     }
 }
+
+/// Create tree storage in the configured data directory with the configured
+/// depth and leaf data retention
+pub fn create_tree_storage_from_config(
+    tree_type: TreeType,
+    config: &StorageConfig,
+) -> IndexerResult<Box<dyn TreeStorage>> {
+    match tree_type {
+        TreeType::Incremental => {
+            let storage = IncrementalTreeStorage::new(&config.data_dir, Some(config.depth))?
+                .with_leaf_data_retention(config.retain_leaf_data);
+            Ok(Box::new(storage))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{DomainSeparators, HashAlgorithm};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_nullifier_index_tracks_inserts() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+
+        let commitment = Commitment::new(1, 0, [1u8; 32], [2u8; 32], [3u8; 32]);
+        storage.insert_commitment(&commitment).unwrap();
+
+        assert!(storage.contains_nullifier(&commitment.nullifier));
+        assert!(!storage.contains_nullifier(&[9u8; 32]));
+
+        // The index is persisted alongside the tree
+        storage.save().unwrap();
+        let reopened = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        assert!(reopened.contains_nullifier(&commitment.nullifier));
+        assert!(!reopened.contains_nullifier(&[9u8; 32]));
+    }
+
+    #[test]
+    fn test_nullifier_count_is_distinct() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        assert_eq!(storage.nullifier_count(), 0);
+
+        // Four commitments, two of which reuse an earlier nullifier
+        let nullifiers = [[1u8; 32], [2u8; 32], [1u8; 32], [2u8; 32]];
+        for (i, nullifier) in nullifiers.iter().enumerate() {
+            let commitment = Commitment::new(1, i as u64, [i as u8; 32], [7u8; 32], *nullifier);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+
+        assert_eq!(storage.len(), 4);
+        assert_eq!(storage.nullifier_count(), 2);
+    }
+
+    #[test]
+    fn test_truncate_keeps_nullifiers_of_surviving_leaves() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+
+        // Leaves 2 and 3 repeat the commitment index and nullifier of leaves 0 and 1
+        for i in 0..4u64 {
+            let commitment = Commitment::new(1, i % 2, [i as u8; 32], [7u8; 32], [i as u8 % 2; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+        let extra = Commitment::new(1, 5, [5u8; 32], [7u8; 32], [5u8; 32]);
+        storage.insert_commitment(&extra).unwrap();
+
+        storage.truncate(2).unwrap();
+        assert!(storage.contains_nullifier(&[0u8; 32]));
+        assert!(storage.contains_nullifier(&[1u8; 32]));
+        assert!(!storage.contains_nullifier(&[5u8; 32]));
+        assert_eq!(storage.nullifier_count(), 2);
+
+        // Commitment indices resolve to the surviving leaves
+        assert_eq!(storage.get_commitment(0).unwrap().unwrap().hash, [0u8; 32]);
+        assert_eq!(storage.get_commitment(1).unwrap().unwrap().hash, [1u8; 32]);
+        assert!(storage.get_commitment(5).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bytes_per_commitment() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        assert_eq!(storage.bytes_per_commitment(), 0.0);
+
+        for i in 0..10u64 {
+            let commitment = Commitment::new(1, i, [i as u8; 32], [3u8; 32], [i as u8; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+        storage.save().unwrap();
+
+        // Each commitment costs at least its leaf hash, plus the sidecar entries
+        let per_commitment = storage.bytes_per_commitment();
+        assert!(per_commitment >= 32.0, "got {per_commitment}");
+        assert!(per_commitment < 1024.0, "got {per_commitment}");
+    }
+
+    #[test]
+    fn test_iter_with_proofs() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        storage.append(b"raw leaf").unwrap();
+        for i in 0..3u64 {
+            let commitment = Commitment::new(1, i, [i as u8; 32], [3u8; 32], [i as u8; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+        let root = storage.root();
+
+        let pairs = storage.iter_with_proofs().unwrap();
+        assert_eq!(pairs.len(), 3);
+        for (i, (commitment, proof)) in pairs.iter().enumerate() {
+            assert_eq!(commitment.commitment_index, i as u64);
+            assert_eq!(proof.leaf_index, i + 1);
+            let leaf = commitment.leaf_hash(HashAlgorithm::Poseidon, &DomainSeparators::default());
+            assert!(proof.verify_against(&root, &leaf));
+        }
+
+        assert_eq!(
+            storage.get_commitment(2).unwrap().unwrap().nullifier,
+            [2u8; 32]
+        );
+        assert!(storage.get_commitment(7).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_for_each_commitment() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        storage.append(b"raw leaf").unwrap();
+        for i in 0..4u64 {
+            let commitment = Commitment::new(1, i, [i as u8; 32], [3u8; 32], [i as u8; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        storage
+            .for_each_commitment(|leaf_index, commitment| {
+                seen.push((leaf_index, commitment.commitment_index));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(seen, vec![(1, 0), (2, 1), (3, 2), (4, 3)]);
+
+        let mut calls = 0;
+        let result = storage.for_each_commitment(|_, commitment| {
+            calls += 1;
+            if commitment.commitment_index == 1 {
+                return Err(IndexerError::InvalidData("stop".to_string()));
+            }
+            Ok(())
+        });
+        assert!(matches!(result, Err(IndexerError::InvalidData(_))));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_verify_contiguous() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        assert!(storage.verify_contiguous().unwrap());
+
+        for i in 0..3u64 {
+            let commitment = Commitment::new(1, i, [i as u8; 32], [3u8; 32], [i as u8; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+        assert!(storage.verify_contiguous().unwrap());
+
+        // Commitment index 4 lands at leaf 3, leaving a gap
+        let gap = Commitment::new(1, 4, [4u8; 32], [3u8; 32], [4u8; 32]);
+        storage.insert_commitment(&gap).unwrap();
+        assert!(!storage.verify_contiguous().unwrap());
+    }
+
+    #[test]
+    fn test_leaf_data_retention() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8))
+            .unwrap()
+            .with_leaf_data_retention(true);
+        storage.append(b"first leaf").unwrap();
+        let commitment = Commitment::new(1, 0, [1u8; 32], [2u8; 32], [3u8; 32]);
+        storage.insert_commitment(&commitment).unwrap();
+
+        assert_eq!(storage.leaf_data(0).unwrap(), Some(b"first leaf".to_vec()));
+        assert_eq!(storage.leaf_data(1).unwrap(), Some(commitment.to_bytes()));
+        assert!(storage.leaf_data(2).is_err());
+
+        storage.save().unwrap();
+        let reopened = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        assert_eq!(reopened.leaf_data(0).unwrap(), Some(b"first leaf".to_vec()));
+
+        let other_dir = TempDir::new().unwrap();
+        let mut without = IncrementalTreeStorage::new(other_dir.path(), Some(8)).unwrap();
+        without.append(b"first leaf").unwrap();
+        assert_eq!(without.leaf_data(0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_leaf_data_retention_from_config() {
+        for retain in [true, false] {
+            let dir = TempDir::new().unwrap();
+            let config = StorageConfig::new(dir.path())
+                .with_depth(8)
+                .with_leaf_data_retention(retain);
+            let mut storage =
+                create_tree_storage_from_config(TreeType::Incremental, &config).unwrap();
+            storage.append(b"first leaf").unwrap();
+            storage.save().unwrap();
+            drop(storage);
+
+            let reopened = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+            let expected = retain.then(|| b"first leaf".to_vec());
+            assert_eq!(reopened.leaf_data(0).unwrap(), expected);
+        }
+    }
+
+    fn storage_with_leaves(dir: &TempDir, count: u64) -> IncrementalTreeStorage {
+        let mut storage = IncrementalTreeStorage::new(dir.path(), Some(8)).unwrap();
+        for i in 0..count {
+            let commitment = Commitment::new(1, i, [i as u8; 32], [3u8; 32], [i as u8; 32]);
+            storage.insert_commitment(&commitment).unwrap();
+        }
+        storage.save().unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_load_and_swap() {
+        let live_dir = TempDir::new().unwrap();
+        let live = storage_with_leaves(&live_dir, 2);
+
+        let snapshot_dir = TempDir::new().unwrap();
+        let snapshot = storage_with_leaves(&snapshot_dir, 5);
+
+        live.load_and_swap(snapshot_dir.path()).unwrap();
+        assert_eq!(live.len(), 5);
+        assert_eq!(live.root(), snapshot.root());
+        assert!(live.contains_nullifier(&[4u8; 32]));
+    }
+
+    #[test]
+    fn test_load_and_swap_rejects_mismatched_root() {
+        let live_dir = TempDir::new().unwrap();
+        let live = storage_with_leaves(&live_dir, 2);
+        let root_before = live.root();
+
+        let snapshot_dir = TempDir::new().unwrap();
+        storage_with_leaves(&snapshot_dir, 5);
+        let metadata = IncrementalTreeMetadata {
+            root_hash: [0u8; 32],
+            version: 1,
+        };
+        std::fs::write(
+            snapshot_dir.path().join("incremental_tree_metadata.dat"),
+            bincode::serialize(&metadata).unwrap(),
+        )
+        .unwrap();
+
+        assert!(live.load_and_swap(snapshot_dir.path()).is_err());
+        assert_eq!(live.len(), 2);
+        assert_eq!(live.root(), root_before);
+    }
+
+    #[test]
+    fn test_undecodable_tree_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let root = storage_with_leaves(&dir, 3).root();
+
+        let path = dir.path().join("incremental_tree.dat");
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() / 2]).unwrap();
+        assert!(matches!(
+            IncrementalTreeStorage::new(dir.path(), Some(8)),
+            Err(IndexerError::StorageError(_))
+        ));
+
+        // The data was left untouched for a later retry
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(
+            IncrementalTreeStorage::new(dir.path(), Some(8))
+                .unwrap()
+                .root(),
+            root
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
 
 use crate::errors::{IndexerError, IndexerResult};
@@ -33,6 +33,7 @@ impl WalEntry {
 }
 
 /// Write-ahead log for crash recovery
+#[derive(Clone)]
 pub struct WriteAheadLog {
     file: Arc<Mutex<File>>,
 }
@@ -60,6 +61,41 @@ impl WriteAheadLog {
         Ok(())
     }
 
+    /// Read every entry currently in the log, in write order.
+    ///
+    /// A torn final entry, left by a crash part-way through `write_entry`,
+    /// ends the log: it is skipped with a warning rather than failing.
+    pub fn read_entries(&self) -> IndexerResult<Vec<WalEntry>> {
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(0))?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < buffer.len() {
+            if offset + 4 > buffer.len() {
+                log::warn!("ignoring torn WAL length prefix at offset {offset}");
+                break;
+            }
+            let mut len_bytes = [0u8; 4];
+            len_bytes.copy_from_slice(&buffer[offset..offset + 4]);
+            let len = u32::from_le_bytes(len_bytes) as usize;
+            offset += 4;
+
+            if offset + len > buffer.len() {
+                log::warn!("ignoring torn WAL entry at offset {}", offset - 4);
+                break;
+            }
+            let entry: WalEntry = bincode::deserialize(&buffer[offset..offset + len])
+                .map_err(|e| IndexerError::SerializationError(e.to_string()))?;
+            entries.push(entry);
+            offset += len;
+        }
+
+        Ok(entries)
+    }
+
     /// Truncate the log (called after successful sync)
     pub fn truncate(&self) -> IndexerResult<()> {
         let mut file = self.file.lock();
@@ -69,6 +105,12 @@ impl WriteAheadLog {
         Ok(())
     }
 
+    /// Force the log's contents to the device
+    pub fn sync_data(&self) -> IndexerResult<()> {
+        self.file.lock().sync_data()?;
+        Ok(())
+    }
+
     /// Get a reference to the underlying file for recovery operations
     pub fn file(&self) -> Arc<Mutex<File>> {
         self.file.clone()
@@ -1,10 +1,15 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read};
 
 use crate::errors::{IndexerError, IndexerResult};
-use crate::utils::Hash;
+use crate::utils::{internal::hash_pair, DomainSeparators, Hash, HashAlgorithm};
 
-/// Represents a commitment in the Merkle tree
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Represents a commitment in the Merkle tree.
+///
+/// The Borsh encoding follows the field order, so it is byte-for-byte the
+/// same as `to_bytes`.
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Commitment {
     pub version: u32,
     pub commitment_index: u64,
@@ -14,6 +19,9 @@ pub struct Commitment {
 }
 
 impl Commitment {
+    /// Size of the binary encoding produced by `to_bytes`
+    pub const SERIALIZED_SIZE: usize = 4 + 8 + 32 + 32 + 32;
+
     /// Create a new commitment
     pub fn new(
         version: u32,
@@ -31,15 +39,35 @@ impl Commitment {
         }
     }
 
+    /// Start building a commitment whose `hash` is derived from its secrets
+    pub fn builder() -> CommitmentBuilder {
+        CommitmentBuilder::default()
+    }
+
+    /// Compute the commitment hash from the random secret and nullifier
+    pub fn compute_hash(random_secret: &Hash, nullifier: &Hash) -> Hash {
+        hash_pair(random_secret, nullifier)
+    }
+
+    /// Check that the stored hash matches the one derived from the secrets
+    pub fn validate(&self) -> IndexerResult<()> {
+        if self.hash == Self::compute_hash(&self.random_secret, &self.nullifier) {
+            Ok(())
+        } else {
+            Err(IndexerError::InvalidData(format!(
+                "commitment {} hash does not match its secret and nullifier",
+                self.commitment_index
+            )))
+        }
+    }
+
     /// Parse a commitment from raw binary data
     /// Expected format: version(4) + commitment_index(8) + hash(32) + random_secret(32) + nullifier(32)
     pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
-        const EXPECTED_SIZE: usize = 4 + 8 + 32 + 32 + 32;
-
-        if data.len() < EXPECTED_SIZE {
+        if data.len() < Self::SERIALIZED_SIZE {
             return Err(IndexerError::InvalidData(format!(
                 "insufficient data for commitment: expected {} bytes, got {}",
-                EXPECTED_SIZE,
+                Self::SERIALIZED_SIZE,
                 data.len()
             )));
         }
@@ -93,7 +121,7 @@ impl Commitment {
 
     /// Convert commitment to binary format
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut data = Vec::with_capacity(4 + 8 + 32 + 32 + 32);
+        let mut data = Vec::with_capacity(Self::SERIALIZED_SIZE);
 
         data.extend_from_slice(&self.version.to_le_bytes());
         data.extend_from_slice(&self.commitment_index.to_le_bytes());
@@ -103,4 +131,245 @@ impl Commitment {
 
         data
     }
+
+    /// Parse a commitment from its Borsh encoding, rejecting trailing bytes
+    pub fn from_borsh_slice(data: &[u8]) -> IndexerResult<Self> {
+        Self::try_from_slice(data)
+            .map_err(|e| IndexerError::InvalidData(format!("borsh parse error: {e}")))
+    }
+
+    /// Encode the commitment with Borsh
+    pub fn to_borsh_vec(&self) -> Vec<u8> {
+        borsh::to_vec(self).expect("writing to a Vec cannot fail")
+    }
+
+    /// Hash of this commitment as stored in a tree hashing with `hasher`
+    /// and `separators`, e.g. `tree.hasher()` and `tree.domain_separators()`.
+    ///
+    /// This is the canonical commitment-to-leaf mapping: the leaf hash of
+    /// the `to_bytes` encoding (identical to its bincode encoding). Tree
+    /// inserts use exactly this, so verifiers can reproduce leaves from it.
+    pub fn leaf_hash(&self, hasher: HashAlgorithm, separators: &DomainSeparators) -> Hash {
+        hasher.hash_leaf_with(&self.to_bytes(), separators)
+    }
+
+    /// Lazily parse concatenated `to_bytes` records from a reader.
+    ///
+    /// Each item is one commitment. A trailing partial record yields an
+    /// `InvalidData` error, after which the iterator ends.
+    pub fn parse_stream<R: Read>(reader: R) -> impl Iterator<Item = IndexerResult<Commitment>> {
+        CommitmentStream {
+            reader,
+            finished: false,
+        }
+    }
+}
+
+/// Iterator over fixed-size commitment records read from a stream
+struct CommitmentStream<R> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: Read> CommitmentStream<R> {
+    /// Fill `buf` completely, returning the number of bytes read before EOF
+    fn read_record(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+impl<R: Read> Iterator for CommitmentStream<R> {
+    type Item = IndexerResult<Commitment>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let mut record = [0u8; Commitment::SERIALIZED_SIZE];
+        match self.read_record(&mut record) {
+            Ok(0) => {
+                self.finished = true;
+                None
+            }
+            Ok(n) if n < record.len() => {
+                self.finished = true;
+                Some(Err(IndexerError::InvalidData(format!(
+                    "truncated commitment record: expected {} bytes, got {n}",
+                    record.len()
+                ))))
+            }
+            Ok(_) => Some(Commitment::from_bytes(&record)),
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Builder for commitments that computes `hash` instead of taking it as input
+#[derive(Debug, Clone)]
+pub struct CommitmentBuilder {
+    version: u32,
+    commitment_index: u64,
+    random_secret: Hash,
+    nullifier: Hash,
+}
+
+impl CommitmentBuilder {
+    /// Set the commitment format version (defaults to 1)
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the on-chain commitment index
+    pub fn commitment_index(mut self, commitment_index: u64) -> Self {
+        self.commitment_index = commitment_index;
+        self
+    }
+
+    /// Set the random secret
+    pub fn random_secret(mut self, random_secret: Hash) -> Self {
+        self.random_secret = random_secret;
+        self
+    }
+
+    /// Set the nullifier
+    pub fn nullifier(mut self, nullifier: Hash) -> Self {
+        self.nullifier = nullifier;
+        self
+    }
+
+    /// Build the commitment, computing its hash
+    pub fn build(self) -> Commitment {
+        let hash = Commitment::compute_hash(&self.random_secret, &self.nullifier);
+        Commitment::new(
+            self.version,
+            self.commitment_index,
+            hash,
+            self.random_secret,
+            self.nullifier,
+        )
+    }
+}
+
+impl Default for CommitmentBuilder {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            commitment_index: 0,
+            random_secret: [0u8; 32],
+            nullifier: [0u8; 32],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_computes_valid_hash() {
+        let commitment = Commitment::builder()
+            .version(2)
+            .commitment_index(7)
+            .random_secret([5u8; 32])
+            .nullifier([6u8; 32])
+            .build();
+
+        assert_eq!(commitment.version, 2);
+        assert_eq!(commitment.commitment_index, 7);
+        assert!(commitment.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_stream_with_truncated_record() {
+        let mut data = Vec::new();
+        for i in 0..3u64 {
+            data.extend(Commitment::new(1, i, [1u8; 32], [2u8; 32], [3u8; 32]).to_bytes());
+        }
+        // Drop the tail of the last record
+        data.truncate(data.len() - 10);
+
+        let results: Vec<_> = Commitment::parse_stream(std::io::Cursor::new(data)).collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().commitment_index, 0);
+        assert_eq!(results[1].as_ref().unwrap().commitment_index, 1);
+        assert!(matches!(results[2], Err(IndexerError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_validate_rejects_mismatched_hash() {
+        let commitment = Commitment::new(1, 0, [1u8; 32], [2u8; 32], [3u8; 32]);
+        assert!(commitment.validate().is_err());
+    }
+
+    #[test]
+    fn test_leaf_hash_matches_tree_insert() {
+        use crate::tree::IncrementalMerkleTree;
+
+        let commitment = Commitment::new(1, 3, [4u8; 32], [5u8; 32], [6u8; 32]);
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"other").unwrap();
+        let index = tree.insert_commitment(&commitment).unwrap();
+        let expected = commitment.leaf_hash(tree.hasher(), &tree.domain_separators());
+        assert_eq!(tree.get_leaf_hash(index), Some(expected));
+
+        let at = tree.insert_commitment_at(5, &commitment).unwrap();
+        assert_eq!(tree.get_leaf_hash(at), Some(expected));
+
+        let mut sha_tree = IncrementalMerkleTree::builder()
+            .depth(8)
+            .hasher(HashAlgorithm::Sha256)
+            .build()
+            .unwrap();
+        let index = sha_tree.insert_commitment(&commitment).unwrap();
+        assert_eq!(
+            sha_tree.get_leaf_hash(index),
+            Some(commitment.leaf_hash(HashAlgorithm::Sha256, &DomainSeparators::default()))
+        );
+
+        let separators = DomainSeparators::new(7, 9).unwrap();
+        let mut separated_tree = IncrementalMerkleTree::builder()
+            .depth(8)
+            .domain_separators(separators)
+            .build()
+            .unwrap();
+        let index = separated_tree.insert_commitment(&commitment).unwrap();
+        let separated = commitment.leaf_hash(HashAlgorithm::Poseidon, &separators);
+        assert_eq!(separated_tree.get_leaf_hash(index), Some(separated));
+        assert_ne!(separated, expected);
+
+        // The canonical bytes are also the bincode encoding of the commitment
+        assert_eq!(bincode::serialize(&commitment).unwrap(), commitment.to_bytes());
+    }
+
+    #[test]
+    fn test_borsh_round_trip_matches_to_bytes() {
+        let commitment = Commitment::new(3, 0x0102_0304_0506_0708, [4u8; 32], [5u8; 32], [6u8; 32]);
+        let encoded = commitment.to_borsh_vec();
+        assert_eq!(encoded, commitment.to_bytes());
+
+        let decoded = Commitment::from_borsh_slice(&encoded).unwrap();
+        assert_eq!(decoded.to_bytes(), commitment.to_bytes());
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Commitment::from_borsh_slice(&trailing),
+            Err(IndexerError::InvalidData(_))
+        ));
+        assert!(Commitment::from_borsh_slice(&encoded[..encoded.len() - 1]).is_err());
+    }
 }
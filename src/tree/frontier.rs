@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::tree::IncrementalMerkleTree;
+use crate::utils::{Hash, HashAlgorithm};
+
+/// Append-only tree that keeps just the frontier instead of every leaf.
+///
+/// Memory is O(depth) regardless of the leaf count, so it suits indexers
+/// that track the root but never serve proofs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FrontierOnlyTree {
+    depth: usize,
+    /// Number of leaves appended so far
+    next_index: usize,
+    /// Left node at each level still waiting for its right sibling
+    frontier: Vec<Hash>,
+    /// Precomputed zero hashes for each level
+    zero_hashes: Vec<Hash>,
+    hasher: HashAlgorithm,
+    root: Hash,
+}
+
+impl FrontierOnlyTree {
+    /// Create an empty frontier-only tree with specified depth
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth, HashAlgorithm::default())
+    }
+
+    /// Create an empty frontier-only tree using `hasher`
+    pub fn with_hasher(depth: usize, hasher: HashAlgorithm) -> Self {
+        assert!(depth > 0, "depth must be >= 1");
+        assert!(depth <= 63, "depth too large");
+
+        let zero_hashes = hasher.zero_hashes(depth);
+        Self {
+            depth,
+            next_index: 0,
+            frontier: zero_hashes[..depth].to_vec(),
+            root: zero_hashes[depth],
+            zero_hashes,
+            hasher,
+        }
+    }
+
+    /// Build the frontier of an existing tree by replaying its leaves
+    pub fn from_tree(tree: &IncrementalMerkleTree) -> IndexerResult<Self> {
+        let mut frontier = Self::with_hasher(tree.depth(), tree.hasher());
+        for index in 0..tree.len() {
+            let leaf_hash = tree
+                .get_leaf_hash(index)
+                .ok_or(IndexerError::LeafNotAppended)?;
+            frontier.append_hash(leaf_hash)?;
+        }
+        Ok(frontier)
+    }
+
+    /// Get the tree depth
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Get the number of leaves appended so far
+    pub fn len(&self) -> usize {
+        self.next_index
+    }
+
+    /// Check if the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.next_index == 0
+    }
+
+    /// Get the current root hash
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    /// Append raw leaf data, returning its leaf index
+    pub fn append(&mut self, leaf_data: &[u8]) -> IndexerResult<usize> {
+        let leaf_hash = self.hasher.hash_leaf(leaf_data);
+        self.append_hash(leaf_hash)
+    }
+
+    /// Append an already-hashed leaf, returning its leaf index
+    pub fn append_hash(&mut self, leaf_hash: Hash) -> IndexerResult<usize> {
+        let index = self.next_index;
+        if index >= 1usize << self.depth {
+            return Err(IndexerError::TreeFull);
+        }
+
+        let mut current = leaf_hash;
+        let mut idx = index;
+        for level in 0..self.depth {
+            current = if idx.is_multiple_of(2) {
+                self.frontier[level] = current;
+                self.hasher.hash_pair(&current, &self.zero_hashes[level])
+            } else {
+                self.hasher.hash_pair(&self.frontier[level], &current)
+            };
+            idx /= 2;
+        }
+
+        self.root = current;
+        self.next_index += 1;
+        Ok(index)
+    }
+
+    /// Write the frontier to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> IndexerResult<()> {
+        let data = bincode::serialize(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Restore a frontier written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> IndexerResult<Self> {
+        let tree: Self = bincode::deserialize(&std::fs::read(path)?)?;
+        if tree.depth == 0
+            || tree.depth > 63
+            || tree.frontier.len() != tree.depth
+            || tree.zero_hashes.len() != tree.depth + 1
+            || tree.next_index > 1usize << tree.depth
+        {
+            return Err(IndexerError::InvalidData(
+                "corrupt frontier-only tree".to_string(),
+            ));
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_frontier_root_matches_full_tree() {
+        let mut frontier = FrontierOnlyTree::new(20);
+        let mut full = IncrementalMerkleTree::new(20);
+        assert_eq!(frontier.root(), full.root());
+
+        for i in 0..1000u32 {
+            let leaf = i.to_le_bytes();
+            assert_eq!(frontier.append(&leaf).unwrap(), full.append(&leaf).unwrap());
+        }
+        assert_eq!(frontier.len(), 1000);
+        assert_eq!(frontier.root(), full.root());
+        assert_eq!(
+            FrontierOnlyTree::from_tree(&full).unwrap().root(),
+            full.root()
+        );
+
+        // Restored frontiers keep appending where they left off
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("frontier.dat");
+        frontier.save(&path).unwrap();
+        let mut restored = FrontierOnlyTree::load(&path).unwrap();
+        restored.append(b"next").unwrap();
+        full.append(b"next").unwrap();
+        assert_eq!(restored.root(), full.root());
+    }
+
+    #[test]
+    fn test_frontier_tree_full() {
+        let mut tree = FrontierOnlyTree::new(2);
+        for i in 0..4u8 {
+            tree.append(&[i]).unwrap();
+        }
+        assert!(matches!(tree.append(b"extra"), Err(IndexerError::TreeFull)));
+    }
+}
@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 
 use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::{write_proof_frame, SerializationOptions, TreeDelta};
+use crate::tree::common::Digest;
 use crate::tree::{Commitment, MerkleProof};
-use crate::utils::{
-    internal::{hash_bytes, hash_pair},
-    Hash,
-};
+use crate::utils::timing::{OpTimer, APPEND_SECONDS, PROVE_SECONDS, ROOT_SECONDS};
+use crate::utils::{DomainSeparators, Hash, HashAlgorithm};
 
 /// Serializable tree that stores only the bottom-most leaf nodes
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -37,6 +41,45 @@ impl Default for SerializableTree {
     }
 }
 
+/// A tree mutation used by `IncrementalMerkleTree::simulate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeOp {
+    /// Append raw leaf data
+    Append(Vec<u8>),
+    /// Replace the leaf at an index with new raw data
+    Update(usize, Vec<u8>),
+    /// Reset the leaf at an index to the empty leaf
+    Clear(usize),
+}
+
+/// Magic bytes at the start of a leaf export
+const LEAF_EXPORT_MAGIC: &[u8; 4] = b"IMTL";
+
+fn default_root_cache() -> bool {
+    true
+}
+
+/// Counter that keeps its value when cloned
+#[derive(Debug, Default)]
+struct ComputeCounter(AtomicUsize);
+
+impl ComputeCounter {
+    fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(test)]
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for ComputeCounter {
+    fn clone(&self) -> Self {
+        Self(AtomicUsize::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
 /// Full incremental Merkle tree implementation
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IncrementalMerkleTree {
@@ -48,11 +91,45 @@ pub struct IncrementalMerkleTree {
     capacity: usize,
     /// Precomputed zero hashes for each level
     zero_hashes: Vec<Hash>,
-    /// Frontier: rightmost nodes at each level (for O(depth) operations)
+    /// Frontier: the pending left node at each level, with the root at `frontier[depth]`
     #[serde(skip)]
     frontier: Vec<Hash>,
-    // Note: cached_nodes was planned but not used in current implementation
-    // Will be added when needed for optimization
+    /// Internal nodes above the leaves: `cached_nodes[l]` holds the populated
+    /// prefix of level `l + 1`
+    #[serde(skip)]
+    cached_nodes: Vec<Vec<Hash>>,
+    /// Whether the frontier and cached nodes reflect the current leaves
+    #[serde(skip)]
+    frontier_valid: bool,
+    /// When set, appending an existing leaf returns its index instead of a new leaf.
+    /// Like the other construction options it is not serialized, so the
+    /// on-disk layout stays the same; reapply it with `TreeBuilder::build_from`.
+    #[serde(skip)]
+    dedup: bool,
+    /// Leaf hash to index lookup, maintained only in dedup mode
+    #[serde(skip)]
+    leaf_positions: HashMap<Hash, usize>,
+    /// Maximum accepted leaf data length in bytes (0 = unlimited)
+    #[serde(skip)]
+    max_leaf_size: usize,
+    /// Cap on `memory_footprint` enforced by appends (0 = unlimited)
+    #[serde(skip)]
+    max_memory: usize,
+    /// Hash function used for leaves and internal nodes
+    #[serde(skip)]
+    hasher: HashAlgorithm,
+    /// Domain tags for Poseidon leaf and node hashes
+    #[serde(skip)]
+    separators: DomainSeparators,
+    /// When set, `root()` reuses the last computed root until the leaves change
+    #[serde(skip, default = "default_root_cache")]
+    root_cache_enabled: bool,
+    /// Last computed root, cleared by every leaf mutation
+    #[serde(skip)]
+    cached_root: OnceLock<Hash>,
+    /// Number of full root computations performed
+    #[serde(skip)]
+    root_computations: ComputeCounter,
 }
 
 impl IncrementalMerkleTree {
@@ -67,9 +144,9 @@ impl IncrementalMerkleTree {
         assert!(depth <= 63, "depth too large");
 
         let capacity = 1usize << depth;
-        let zero_hashes = Self::compute_zero_hashes(depth);
+        let zero_hashes = HashAlgorithm::default().zero_hashes(depth);
 
-        let frontier = vec![zero_hashes[0]; depth + 1]; // Initialize frontier with zero hashes
+        let frontier = zero_hashes.clone(); // An empty tree's frontier is all zero subtrees
 
         Self {
             serializable: SerializableTree::new(),
@@ -77,14 +154,171 @@ impl IncrementalMerkleTree {
             capacity,
             zero_hashes,
             frontier,
+            cached_nodes: vec![Vec::new(); depth],
+            frontier_valid: true,
+            dedup: false,
+            leaf_positions: HashMap::new(),
+            max_leaf_size: 0,
+            max_memory: 0,
+            hasher: HashAlgorithm::default(),
+            separators: DomainSeparators::default(),
+            root_cache_enabled: true,
+            cached_root: OnceLock::new(),
+            root_computations: ComputeCounter::default(),
+        }
+    }
+
+    /// Start configuring a tree with `TreeBuilder`
+    pub fn builder() -> TreeBuilder {
+        TreeBuilder::default()
+    }
+
+    /// Smallest depth whose capacity holds `n` leaves (at least 1).
+    ///
+    /// Fails with `InvalidData` if `n` exceeds the largest power of two a
+    /// `usize` can hold.
+    pub fn min_depth_for(n: usize) -> IndexerResult<usize> {
+        let capacity = n.checked_next_power_of_two().ok_or_else(|| {
+            IndexerError::InvalidData(format!("no tree depth can hold {n} leaves"))
+        })?;
+        Ok((capacity.trailing_zeros() as usize).max(1))
+    }
+
+    /// Create a new tree that deduplicates appended leaves.
+    ///
+    /// Appending data whose leaf hash is already present returns the index of
+    /// the existing leaf and leaves the tree unchanged.
+    pub fn with_dedup(depth: usize) -> Self {
+        let mut tree = Self::with_depth(depth);
+        tree.dedup = true;
+        tree
+    }
+
+    /// Check if the tree deduplicates appended leaves
+    pub fn is_dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// Limit the size of leaf data accepted by `append`/`update` (0 = unlimited)
+    pub fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size;
+        self
+    }
+
+    /// Get the maximum accepted leaf data length (0 = unlimited)
+    pub fn max_leaf_size(&self) -> usize {
+        self.max_leaf_size
+    }
+
+    /// Cap the estimated memory footprint; appends that would exceed it
+    /// fail with `MemoryLimitExceeded` (0 = unlimited)
+    pub fn with_max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// Get the memory cap in bytes (0 = unlimited)
+    pub fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    /// Estimated bytes held by the tree: leaves, cached internal nodes,
+    /// frontier, zero hashes and the dedup lookup
+    pub fn memory_footprint(&self) -> usize {
+        let hash = std::mem::size_of::<Hash>();
+        let nodes: usize = self.cached_nodes.iter().map(Vec::len).sum();
+        let hashes =
+            self.serializable.leaves.len() + nodes + self.frontier.len() + self.zero_hashes.len();
+        let lookup = self.leaf_positions.len() * (hash + std::mem::size_of::<usize>());
+        std::mem::size_of::<Self>() + hashes * hash + lookup
+    }
+
+    /// Fail if appending `count` leaves would take the footprint past the cap.
+    ///
+    /// Each leaf is charged its own hash plus, amortized, one cached internal
+    /// node and a dedup lookup entry when enabled.
+    fn check_memory(&self, count: usize) -> IndexerResult<()> {
+        if self.max_memory == 0 {
+            return Ok(());
+        }
+        let hash = std::mem::size_of::<Hash>();
+        let mut per_leaf = 2 * hash;
+        if self.dedup {
+            per_leaf += hash + std::mem::size_of::<usize>();
+        }
+        if self.memory_footprint() + count * per_leaf > self.max_memory {
+            return Err(IndexerError::MemoryLimitExceeded(self.max_memory));
+        }
+        Ok(())
+    }
+
+    /// Build the tree with a different hash algorithm (Poseidon by default).
+    ///
+    /// Meant for a freshly created tree: existing leaf hashes are kept as-is.
+    pub fn with_hasher(mut self, hasher: HashAlgorithm) -> Self {
+        self.hasher = hasher;
+        self.zero_hashes = self.compute_zero_hashes(self.hash_leaf(&[0u8]));
+        self.cached_root = OnceLock::new();
+        self.refresh_frontier();
+        self
+    }
+
+    /// Get the hash algorithm used by this tree
+    pub fn hasher(&self) -> HashAlgorithm {
+        self.hasher
+    }
+
+    /// Get the domain separators tagging this tree's Poseidon hashes
+    pub fn domain_separators(&self) -> DomainSeparators {
+        self.separators
+    }
+
+    /// Hash raw leaf data with the tree's hasher and separators
+    fn hash_leaf(&self, data: &[u8]) -> Hash {
+        self.hasher.hash_leaf_with(data, &self.separators)
+    }
+
+    /// Hash two children with the tree's hasher and separators
+    fn hash_pair(&self, left: &Hash, right: &Hash) -> Hash {
+        self.hasher.hash_pair_with(left, right, &self.separators)
+    }
+
+    /// Empty-subtree hashes above `zero_leaf` for this tree's hashing
+    fn compute_zero_hashes(&self, zero_leaf: Hash) -> Vec<Hash> {
+        self.hasher
+            .zero_hashes_with(zero_leaf, self.depth, &self.separators)
+    }
+
+    /// Enable or disable caching of the root between mutations (enabled by default)
+    pub fn with_root_cache(mut self, enabled: bool) -> Self {
+        self.root_cache_enabled = enabled;
+        self.cached_root = OnceLock::new();
+        self
+    }
+
+    /// Drop the cached root after the leaves change
+    fn invalidate_root(&mut self) {
+        self.cached_root.take();
+        self.frontier_valid = false;
+    }
+
+    /// Reject leaf data larger than the configured limit
+    pub(crate) fn check_leaf_size(&self, leaf_data: &[u8]) -> IndexerResult<()> {
+        if self.max_leaf_size > 0 && leaf_data.len() > self.max_leaf_size {
+            return Err(IndexerError::InvalidData(format!(
+                "leaf data too large: {} bytes exceeds limit of {}",
+                leaf_data.len(),
+                self.max_leaf_size
+            )));
         }
+        Ok(())
     }
 
     /// Create a tree from an existing serializable tree with specified depth
     pub fn from_serializable(serializable: SerializableTree, depth: usize) -> Self {
         let capacity = 1usize << depth;
-        let zero_hashes = Self::compute_zero_hashes(depth);
-        let frontier = vec![zero_hashes[0]; depth + 1];
+        let zero_hashes = HashAlgorithm::default().zero_hashes(depth);
+        let frontier = zero_hashes.clone();
 
         Self {
             serializable,
@@ -92,26 +326,20 @@ impl IncrementalMerkleTree {
             capacity,
             zero_hashes,
             frontier,
+            cached_nodes: vec![Vec::new(); depth],
+            frontier_valid: false,
+            dedup: false,
+            leaf_positions: HashMap::new(),
+            max_leaf_size: 0,
+            max_memory: 0,
+            hasher: HashAlgorithm::default(),
+            separators: DomainSeparators::default(),
+            root_cache_enabled: true,
+            cached_root: OnceLock::new(),
+            root_computations: ComputeCounter::default(),
         }
     }
 
-    /// Compute zero hashes for all levels
-    fn compute_zero_hashes(depth: usize) -> Vec<Hash> {
-        let mut zero_hashes = Vec::with_capacity(depth + 1);
-
-        // Level 0: hash of single zero byte
-        let base_zero = hash_bytes(&[0u8]);
-        zero_hashes.push(base_zero);
-
-        // Higher levels: zero_{i+1} = hash_pair(zero_i, zero_i)
-        for i in 0..depth {
-            let next = hash_pair(&zero_hashes[i], &zero_hashes[i]);
-            zero_hashes.push(next);
-        }
-
-        zero_hashes
-    }
-
     /// Get the tree's capacity (maximum number of leaves)
     pub fn capacity(&self) -> usize {
         self.capacity
@@ -142,29 +370,61 @@ impl IncrementalMerkleTree {
         self.zero_hashes.get(level).copied()
     }
 
+    /// Empty-subtree hashes for levels `0..=depth`, as used by
+    /// `MerkleProof::compress` and `CompactMerkleProof::decompress`
+    pub fn zero_hashes(&self) -> &[Hash] {
+        &self.zero_hashes
+    }
+
+    /// Get the hash of the leaf at `index`, if it has been inserted
+    pub fn leaf(&self, index: usize) -> Option<Hash> {
+        self.serializable.leaves.get(index).copied()
+    }
+
+    /// Iterate over `(index, hash)` for every leaf in insertion order
+    pub fn leaves(&self) -> impl Iterator<Item = (usize, Hash)> + '_ {
+        self.serializable.leaves.iter().copied().enumerate()
+    }
+
+    /// All leaf hashes as a slice, for encoders that need them in bulk
+    pub(crate) fn leaf_slice(&self) -> &[Hash] {
+        &self.serializable.leaves
+    }
+
     /// Get the hash of a leaf at a specific index (for internal use)
     pub fn get_leaf_hash(&self, index: usize) -> Option<Hash> {
-        self.serializable.leaves.get(index).copied()
+        self.leaf(index)
     }
 
-    /// Set a leaf hash directly (for internal use during loading)
+    /// Set a leaf hash directly, padding any gap with zero leaves.
+    ///
+    /// Updates the cached nodes along the leaf's path in O(depth) plus the
+    /// gap, unless a bulk load (e.g. after `set_next_index`) already left
+    /// them stale for a lazy rebuild.
     pub fn set_leaf_hash(&mut self, index: usize, hash: Hash) -> IndexerResult<()> {
         if index >= self.capacity {
             return Err(IndexerError::IndexOutOfBounds);
         }
 
         // Ensure we have enough space in the leaves vector
-        while self.serializable.leaves.len() <= index {
-            self.serializable.leaves.push(self.zero_hashes[0]);
+        let len = self.serializable.leaves.len();
+        if index >= len {
+            self.serializable
+                .leaves
+                .resize(index + 1, self.zero_hashes[0]);
+            if self.frontier_valid {
+                self.update_from(len);
+            }
         }
 
-        self.serializable.leaves[index] = hash;
+        self.replace_leaf(index, hash);
         Ok(())
     }
 
     /// Set the leaf count directly (for internal use during loading) - now a no-op since we use Vec::len()
     pub fn set_next_index(&mut self, leaf_count: usize) -> IndexerResult<()> {
         // Ensure the leaves vector has the right size
+        self.invalidate_root();
         self.serializable
             .leaves
             .resize(leaf_count, self.zero_hashes[0]);
@@ -176,40 +436,369 @@ impl IncrementalMerkleTree {
         if self.is_full() {
             return Err(IndexerError::TreeFull);
         }
+        self.check_leaf_size(leaf_data)?;
+
+        let leaf_hash = self.hash_leaf(leaf_data);
+        self.append_hash(leaf_hash)
+    }
+
+    /// Append a leaf whose hash was already computed, without rehashing it
+    pub fn append_hash(&mut self, hash: Hash) -> IndexerResult<usize> {
+        let _timer = OpTimer::start(APPEND_SECONDS);
+        if self.is_full() {
+            return Err(IndexerError::TreeFull);
+        }
+        self.check_memory(1)?;
+        Ok(self.push_leaf(hash))
+    }
+
+    /// Push an already-hashed leaf and update the frontier.
+    /// Callers must check capacity first.
+    fn push_leaf(&mut self, leaf_hash: Hash) -> usize {
+        if self.dedup {
+            if let Some(&existing) = self.leaf_positions.get(&leaf_hash) {
+                return existing;
+            }
+        }
 
-        let leaf_hash = hash_bytes(leaf_data);
         let index = self.serializable.leaves.len();
 
         // Add the leaf to the bottom level
+        self.cached_root.take();
         self.serializable.leaves.push(leaf_hash);
+        if self.dedup {
+            self.leaf_positions.insert(leaf_hash, index);
+        }
 
-        // True IMT: Update frontier incrementally - O(depth) operation
-        let mut current_hash = leaf_hash;
-        let mut pos = index;
+        if !self.frontier_valid {
+            // An earlier non-append mutation left the frontier stale
+            self.refresh_frontier();
+            return index;
+        }
 
-        // Bubble up the frontier path
-        for level in 0..self.depth {
-            if pos % 2 == 0 {
-                // Current node is left child, combine with right sibling (zero or frontier)
-                let right = if pos + 1 < self.serializable.leaves.len() {
-                    self.serializable.leaves[pos + 1]
-                } else {
-                    self.zero_hashes[level]
-                };
-                let parent = hash_pair(&current_hash, &right);
-                self.frontier[level + 1] = parent;
-                current_hash = parent;
-            } else {
-                // Current node is right child, combine frontier with left
-                let left = self.frontier[level];
-                let parent = hash_pair(&left, &current_hash);
-                self.frontier[level + 1] = parent;
-                current_hash = parent;
+        // True IMT: update the path above the new leaf - O(depth) operation
+        self.update_path(index);
+
+        index
+    }
+
+    /// Append many leaves at once, returning their indices in order.
+    ///
+    /// The leaves are hashed and stored in one pass, then the cached nodes
+    /// and frontier are updated once for the whole batch. Fails with
+    /// `TreeFull` before inserting anything if the batch would not fit.
+    pub fn batch_append(&mut self, leaves: &[&[u8]]) -> IndexerResult<Vec<usize>> {
+        for leaf_data in leaves {
+            self.check_leaf_size(leaf_data)?;
+        }
+        if self.len() + leaves.len() > self.capacity {
+            return Err(IndexerError::TreeFull);
+        }
+        self.check_memory(leaves.len())?;
+
+        let start = self.len();
+        let (hasher, separators) = (self.hasher, self.separators);
+        let hashes = leaves
+            .iter()
+            .map(|leaf_data| hasher.hash_leaf_with(leaf_data, &separators));
+        let indices = if self.dedup {
+            hashes
+                .map(|leaf_hash| {
+                    let next = self.serializable.leaves.len();
+                    let index = *self.leaf_positions.entry(leaf_hash).or_insert(next);
+                    if index == next {
+                        self.serializable.leaves.push(leaf_hash);
+                    }
+                    index
+                })
+                .collect()
+        } else {
+            self.serializable.leaves.extend(hashes);
+            (start..self.len()).collect()
+        };
+
+        self.update_from(start);
+        Ok(indices)
+    }
+
+    /// Build a tree of `depth` leaf by leaf, checking the root after each
+    /// append against `expected_roots`.
+    ///
+    /// Fails at the first mismatch, naming the leaf index.
+    pub fn validate_against_roots(
+        leaves: &[&[u8]],
+        expected_roots: &[Hash],
+        depth: usize,
+    ) -> IndexerResult<()> {
+        if leaves.len() != expected_roots.len() {
+            return Err(IndexerError::InvalidData(format!(
+                "got {} leaves but {} expected roots",
+                leaves.len(),
+                expected_roots.len()
+            )));
+        }
+
+        let mut tree = Self::with_depth(depth);
+        for (index, (leaf_data, expected)) in leaves.iter().zip(expected_roots).enumerate() {
+            tree.append(leaf_data)?;
+            let root = tree.root();
+            if root != *expected {
+                return Err(IndexerError::InvalidData(format!(
+                    "root mismatch after leaf {index}: expected {}, got {}",
+                    hex::encode(expected),
+                    hex::encode(root)
+                )));
             }
-            pos /= 2;
         }
+        Ok(())
+    }
 
-        Ok(index)
+    /// Append all of `other`'s leaves, in order, to this tree.
+    ///
+    /// The resulting root equals that of a single tree built from the
+    /// concatenated leaves. In dedup mode, leaves already present are
+    /// skipped as `append` would. Fails without modifying `self` if the
+    /// depths or hashers differ, the combined leaves would exceed capacity
+    /// or the memory limit.
+    pub fn extend_from(&mut self, other: &Self) -> IndexerResult<()> {
+        if other.depth != self.depth {
+            return Err(IndexerError::InvalidData(format!(
+                "cannot merge trees of different depths: {} and {}",
+                self.depth, other.depth
+            )));
+        }
+        if other.hasher != self.hasher || other.separators != self.separators {
+            return Err(IndexerError::InvalidData(format!(
+                "cannot merge trees with different hashers: {:?} and {:?}",
+                self.hasher, other.hasher
+            )));
+        }
+        if self.len() + other.len() > self.capacity {
+            return Err(IndexerError::TreeFull);
+        }
+        self.check_memory(other.len())?;
+
+        for leaf_hash in &other.serializable.leaves {
+            self.push_leaf(*leaf_hash);
+        }
+
+        Ok(())
+    }
+
+    /// Remove all leaves, keeping depth, capacity and hashing settings.
+    pub fn clear(&mut self) {
+        self.serializable.leaves.clear();
+        self.leaf_positions.clear();
+        self.invalidate_root();
+        self.frontier = self.zero_hashes.clone();
+        self.cached_nodes = vec![Vec::new(); self.depth];
+        self.frontier_valid = true;
+    }
+
+    /// Drop every leaf at or after `len`, as if they had never been appended.
+    ///
+    /// The cached nodes are rebuilt from the remaining leaves.
+    pub fn truncate(&mut self, len: usize) -> IndexerResult<()> {
+        if len > self.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        self.serializable.leaves.truncate(len);
+        self.rebuild_leaf_positions();
+        self.refresh_frontier();
+        Ok(())
+    }
+
+    /// Stream all leaf hashes to a writer.
+    ///
+    /// The output is a header (magic, depth as u32 LE, leaf count as u64 LE)
+    /// followed by each 32-byte leaf hash in order.
+    pub fn export_leaves<W: Write>(&self, mut writer: W) -> IndexerResult<()> {
+        writer.write_all(LEAF_EXPORT_MAGIC)?;
+        writer.write_all(&(self.depth as u32).to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        for leaf_hash in &self.serializable.leaves {
+            writer.write_all(leaf_hash)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Rebuild a tree from the output of `export_leaves`
+    pub fn import_leaves<R: Read>(mut reader: R) -> IndexerResult<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != LEAF_EXPORT_MAGIC {
+            return Err(IndexerError::InvalidData(
+                "not a leaf export: bad magic".to_string(),
+            ));
+        }
+
+        let mut depth_bytes = [0u8; 4];
+        reader.read_exact(&mut depth_bytes)?;
+        let depth = u32::from_le_bytes(depth_bytes) as usize;
+        if depth == 0 || depth >= usize::BITS as usize {
+            return Err(IndexerError::InvalidData(format!(
+                "invalid tree depth in leaf export: {depth}"
+            )));
+        }
+
+        let mut count_bytes = [0u8; 8];
+        reader.read_exact(&mut count_bytes)?;
+        let count = u64::from_le_bytes(count_bytes) as usize;
+
+        let mut tree = Self::with_depth(depth);
+        if count > tree.capacity {
+            return Err(IndexerError::TreeFull);
+        }
+
+        let mut leaf_hash = [0u8; 32];
+        for _ in 0..count {
+            reader.read_exact(&mut leaf_hash)?;
+            tree.push_leaf(leaf_hash);
+        }
+
+        Ok(tree)
+    }
+
+    /// Leaves that differ from the zero leaf, with their indices.
+    ///
+    /// Together with the depth this is enough to reproduce the root; see
+    /// `from_active_leaves`.
+    pub fn active_leaves(&self) -> Vec<(usize, Hash)> {
+        let zero = self.zero_hashes[0];
+        self.serializable
+            .leaves
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, leaf)| *leaf != zero)
+            .collect()
+    }
+
+    /// Rebuild a tree from the output of `active_leaves`, filling the gaps
+    /// with zero leaves.
+    ///
+    /// Fails with `InvalidData` for a depth outside `1..=63` or an index
+    /// past the tree's capacity.
+    pub fn from_active_leaves(active: &[(usize, Hash)], depth: usize) -> IndexerResult<Self> {
+        let mut tree = Self::builder().depth(depth).build()?;
+        let mut len = 0;
+        for &(index, _) in active {
+            match index.checked_add(1) {
+                Some(end) if end <= tree.capacity => len = len.max(end),
+                _ => {
+                    return Err(IndexerError::InvalidData(format!(
+                        "active leaf index {index} out of range for depth {depth}"
+                    )))
+                }
+            }
+        }
+
+        tree.serializable.leaves = vec![tree.zero_hashes[0]; len];
+        for &(index, hash) in active {
+            tree.serializable.leaves[index] = hash;
+        }
+        tree.refresh_frontier();
+        Ok(tree)
+    }
+
+    /// Compute the root after applying `ops` to a copy of this tree.
+    ///
+    /// The tree itself is left untouched.
+    pub fn simulate(&self, ops: &[TreeOp]) -> IndexerResult<Hash> {
+        let mut scratch = self.clone();
+        for op in ops {
+            match op {
+                TreeOp::Append(leaf_data) => {
+                    scratch.append(leaf_data)?;
+                }
+                TreeOp::Update(index, leaf_data) => scratch.update(*index, leaf_data)?,
+                TreeOp::Clear(index) => {
+                    if *index >= scratch.len() {
+                        return Err(IndexerError::IndexOutOfBounds);
+                    }
+                    let empty_leaf = scratch.zero_hashes[0];
+                    scratch.replace_leaf(*index, empty_leaf);
+                }
+            }
+        }
+        Ok(scratch.root())
+    }
+
+    /// Apply a delta produced by `TreeDelta::between`.
+    ///
+    /// The tree must have exactly the delta's base leaf count.
+    pub fn apply_delta(&mut self, delta: &TreeDelta) -> IndexerResult<()> {
+        self.check_delta(delta)?;
+        self.set_next_index(delta.new_len)?;
+        for (index, hash) in &delta.changes {
+            self.set_leaf_hash(*index, *hash)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `apply_delta` would accept `delta`, without changing the tree
+    pub fn check_delta(&self, delta: &TreeDelta) -> IndexerResult<()> {
+        if self.len() != delta.base_len {
+            return Err(IndexerError::InvalidData(format!(
+                "delta expects {} leaves, tree has {}",
+                delta.base_len,
+                self.len()
+            )));
+        }
+        if delta.new_len < delta.base_len || delta.new_len > self.capacity {
+            return Err(IndexerError::InvalidData(format!(
+                "invalid delta leaf count {} for base {}",
+                delta.new_len, delta.base_len
+            )));
+        }
+        if let Some((index, _)) = delta.changes.iter().find(|(i, _)| *i >= delta.new_len) {
+            return Err(IndexerError::InvalidData(format!(
+                "delta changes leaf {index} beyond its leaf count {}",
+                delta.new_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Apply a delta and check the resulting root against `expected_root`.
+    ///
+    /// On a mismatch (or any error) the tree is restored to its previous
+    /// state and an error is returned.
+    pub fn apply_delta_checked(
+        &mut self,
+        delta: &TreeDelta,
+        expected_root: &Hash,
+    ) -> IndexerResult<()> {
+        let base_len = self.len();
+        let previous: Vec<(usize, Hash)> = delta
+            .changes
+            .iter()
+            .filter_map(|(index, _)| self.get_leaf_hash(*index).map(|hash| (*index, hash)))
+            .collect();
+
+        let result = self.apply_delta(delta).and_then(|()| {
+            let root = self.root();
+            if &root == expected_root {
+                Ok(())
+            } else {
+                Err(IndexerError::InvalidData(format!(
+                    "delta produced root {}, expected {}",
+                    hex::encode(root),
+                    hex::encode(expected_root)
+                )))
+            }
+        });
+
+        if result.is_err() {
+            // Drop appended leaves, then restore overwritten ones
+            self.set_next_index(base_len)?;
+            for (index, hash) in previous {
+                self.replace_leaf(index, hash);
+            }
+            self.rebuild_leaf_positions();
+        }
+        result
     }
 
     /// Update an existing leaf
@@ -217,29 +806,194 @@ impl IncrementalMerkleTree {
         if index >= self.serializable.leaves.len() {
             return Err(IndexerError::IndexOutOfBounds);
         }
+        self.check_leaf_size(leaf_data)?;
 
-        let leaf_hash = hash_bytes(leaf_data);
-        self.serializable.leaves[index] = leaf_hash;
+        let leaf_hash = self.hash_leaf(leaf_data);
+        self.update_hash(index, leaf_hash)
+    }
 
+    /// Update an existing leaf with an already computed hash
+    pub fn update_hash(&mut self, index: usize, hash: Hash) -> IndexerResult<()> {
+        if index >= self.serializable.leaves.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        self.replace_leaf(index, hash);
         Ok(())
     }
 
+    /// Overwrite an existing leaf slot, keeping the dedup lookup and the
+    /// cached nodes in sync
+    fn replace_leaf(&mut self, index: usize, hash: Hash) {
+        if self.dedup {
+            let old = self.serializable.leaves[index];
+            if self.leaf_positions.get(&old) == Some(&index) {
+                self.leaf_positions.remove(&old);
+            }
+            self.leaf_positions.entry(hash).or_insert(index);
+        }
+        self.cached_root.take();
+        self.serializable.leaves[index] = hash;
+        if self.frontier_valid {
+            self.update_path(index);
+        }
+    }
+
+    /// Node hash at a level (0 = leaves), or that level's zero hash past the populated prefix
+    fn node(&self, level: usize, index: usize) -> Hash {
+        let nodes = if level == 0 {
+            &self.serializable.leaves
+        } else {
+            &self.cached_nodes[level - 1]
+        };
+        nodes.get(index).copied().unwrap_or(self.zero_hashes[level])
+    }
+
+    /// Recompute the cached nodes from one leaf up to the root, then the frontier
+    fn update_path(&mut self, leaf_index: usize) {
+        let mut current = self.serializable.leaves[leaf_index];
+        let mut pos = leaf_index;
+
+        for level in 0..self.depth {
+            let sibling = self.node(level, pos ^ 1);
+            current = if pos.is_multiple_of(2) {
+                self.hash_pair(&current, &sibling)
+            } else {
+                self.hash_pair(&sibling, &current)
+            };
+            pos /= 2;
+
+            let nodes = &mut self.cached_nodes[level];
+            if pos < nodes.len() {
+                nodes[pos] = current;
+            } else {
+                nodes.push(current);
+            }
+        }
+
+        self.sync_frontier();
+    }
+
+    /// Recompute the cached nodes covering leaves `start..`, then the frontier
+    fn update_from(&mut self, start: usize) {
+        self.cached_root.take();
+        if !self.frontier_valid {
+            self.refresh_frontier();
+            return;
+        }
+
+        let mut first = start;
+        for level in 0..self.depth {
+            let zero = self.zero_hashes[level];
+            let parent_start = first / 2;
+            let below = if level == 0 {
+                &self.serializable.leaves
+            } else {
+                &self.cached_nodes[level - 1]
+            };
+            let parents: Vec<Hash> = below[(parent_start * 2).min(below.len())..]
+                .chunks(2)
+                .map(|pair| self.hash_pair(&pair[0], pair.get(1).unwrap_or(&zero)))
+                .collect();
+
+            let nodes = &mut self.cached_nodes[level];
+            nodes.truncate(parent_start);
+            nodes.extend(parents);
+            first = parent_start;
+        }
+
+        self.sync_frontier();
+    }
+
+    /// Derive the frontier and root from the cached nodes
+    fn sync_frontier(&mut self) {
+        let len = self.serializable.leaves.len();
+        for level in 0..self.depth {
+            // The next append at this level lands at `pos`; if that is a right
+            // child, its complete left sibling must be remembered
+            let pos = len >> level;
+            if !pos.is_multiple_of(2) {
+                self.frontier[level] = self.node(level, pos - 1);
+            }
+        }
+        self.frontier[self.depth] = self.node(self.depth, 0);
+    }
+
+    /// Rebuild the dedup lookup from the stored leaves
+    fn rebuild_leaf_positions(&mut self) {
+        self.leaf_positions.clear();
+        if !self.dedup {
+            return;
+        }
+        for (index, hash) in self.serializable.leaves.iter().enumerate() {
+            self.leaf_positions.entry(*hash).or_insert(index);
+        }
+    }
+
+    /// Append a leaf and return its index, the new root, and its proof
+    pub fn append_and_prove(
+        &mut self,
+        leaf_data: &[u8],
+    ) -> IndexerResult<(usize, Hash, MerkleProof)> {
+        let index = self.append(leaf_data)?;
+        let proof = self.prove(index)?;
+        Ok((index, self.root(), proof))
+    }
+
     /// Insert a commitment into the tree
     pub fn insert_commitment(&mut self, commitment: &Commitment) -> IndexerResult<usize> {
-        let commitment_data = bincode::serialize(commitment)?;
-        self.append(&commitment_data)
+        self.append(&commitment.to_bytes())
+    }
+
+    /// Insert a commitment at a specific leaf index instead of appending.
+    ///
+    /// Gaps before `index` are filled with zero leaves, which later inserts
+    /// may occupy. Inserting into an already occupied slot is an error.
+    pub fn insert_commitment_at(
+        &mut self,
+        index: usize,
+        commitment: &Commitment,
+    ) -> IndexerResult<usize> {
+        if index >= self.capacity {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        if let Some(existing) = self.serializable.leaves.get(index) {
+            if *existing != self.zero_hashes[0] {
+                return Err(IndexerError::InvalidData(format!(
+                    "leaf {index} is already occupied"
+                )));
+            }
+        }
+
+        self.check_leaf_size(&commitment.to_bytes())?;
+        let leaf_hash = self.hash_leaf(&commitment.to_bytes());
+        self.set_leaf_hash(index, leaf_hash)?;
+        Ok(index)
     }
 
     /// Get the current root hash
     pub fn root(&self) -> Hash {
+        let _timer = OpTimer::start(ROOT_SECONDS);
+        if !self.root_cache_enabled {
+            return self.compute_root();
+        }
+        if self.frontier_valid {
+            return self.frontier[self.depth];
+        }
+        *self.cached_root.get_or_init(|| self.compute_root())
+    }
+
+    /// Recompute the root from the stored leaves
+    fn compute_root(&self) -> Hash {
+        self.root_computations.increment();
         if self.serializable.leaves.is_empty() {
             return self.zero_hashes[self.depth];
         }
 
         // Recompute root deterministically from leaves to match proof logic
         let mut level_nodes = self.serializable.leaves.clone();
-        for lvl in 0..self.depth {
-            let mut next_level = Vec::with_capacity((level_nodes.len() + 1) / 2);
+        let mut lvl = 0;
+        while lvl < self.depth && level_nodes.len() > 1 {
+            let mut next_level = Vec::with_capacity(level_nodes.len().div_ceil(2));
             for i in (0..level_nodes.len()).step_by(2) {
                 let left = level_nodes[i];
                 let right = if i + 1 < level_nodes.len() {
@@ -247,74 +1001,149 @@ impl IncrementalMerkleTree {
                 } else {
                     self.zero_hashes[lvl]
                 };
-                next_level.push(hash_pair(&left, &right));
+                next_level.push(self.hash_pair(&left, &right));
             }
             level_nodes = next_level;
+            lvl += 1;
         }
 
-        level_nodes
-            .get(0)
-            .copied()
-            .unwrap_or(self.zero_hashes[self.depth])
+        // Once a level collapses to a single node, everything to its right is
+        // an empty subtree, so climb the remaining levels without allocating
+        let mut node = level_nodes[0];
+        for zero in &self.zero_hashes[lvl..self.depth] {
+            node = self.hash_pair(&node, zero);
+        }
+        node
     }
 
-    /// Rebuild the frontier from leaves after deserialization
-    pub fn rebuild_frontier(&mut self) -> IndexerResult<()> {
-        // Rebuild frontier by recomputing rightmost path for each level
-        if self.serializable.leaves.is_empty() {
-            self.frontier = vec![self.zero_hashes[0]; self.depth + 1];
-            return Ok(());
-        }
+    /// Export every node hash, level by level from the leaves up to the root.
+    ///
+    /// Each level below the root holds the populated prefix of that level,
+    /// padded with the level's zero hash to an even length so every node has
+    /// its sibling present. The last level is the single root hash.
+    pub fn to_node_array(&self) -> Vec<Vec<Hash>> {
+        let mut levels = Vec::with_capacity(self.depth + 1);
+        let mut current = if self.serializable.leaves.is_empty() {
+            vec![self.zero_hashes[0]]
+        } else {
+            self.serializable.leaves.clone()
+        };
 
-        // Rebuild frontier by simulating insertions for all leaves
-        let num_leaves = self.serializable.leaves.len();
-
-        // Reset frontier to zeros
-        self.frontier = vec![self.zero_hashes[0]; self.depth + 1];
-
-        // Rebuild frontier by simulating all insertions
-        for i in 0..num_leaves {
-            let leaf_hash = self.serializable.leaves[i];
-            let mut current_hash = leaf_hash;
-            let mut pos = i;
-
-            // Bubble up the frontier path
-            for level in 0..self.depth {
-                if pos % 2 == 0 {
-                    // Current node is left child
-                    let right = if pos + 1 < num_leaves {
-                        self.serializable.leaves[pos + 1]
-                    } else {
-                        self.zero_hashes[level]
-                    };
-                    let parent = hash_pair(&current_hash, &right);
-                    self.frontier[level + 1] = parent;
-                    current_hash = parent;
-                } else {
-                    // Current node is right child
-                    let left = self.frontier[level];
-                    let parent = hash_pair(&left, &current_hash);
-                    self.frontier[level + 1] = parent;
-                    current_hash = parent;
-                }
-                pos /= 2;
+        for level in 0..self.depth {
+            if current.len() % 2 == 1 {
+                current.push(self.zero_hashes[level]);
             }
+            let next = current
+                .chunks(2)
+                .map(|pair| self.hash_pair(&pair[0], &pair[1]))
+                .collect();
+            levels.push(current);
+            current = next;
         }
 
+        levels.push(current);
+        levels
+    }
+
+    /// Rebuild the frontier from leaves after deserialization
+    pub fn rebuild_frontier(&mut self) -> IndexerResult<()> {
+        self.rebuild_leaf_positions();
+        self.refresh_frontier();
         Ok(())
     }
 
-    /// Generate a Merkle proof for a leaf at the given index
-    pub fn prove(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+    /// Recompute every cached node and the frontier in one pass over the leaves
+    fn refresh_frontier(&mut self) {
+        self.frontier = self.zero_hashes.clone();
+        self.cached_nodes = vec![Vec::new(); self.depth];
+        self.frontier_valid = true;
+        self.cached_root.take();
+
+        if self.serializable.leaves.is_empty() {
+            return;
+        }
+
+        for level in 0..self.depth {
+            let zero = self.zero_hashes[level];
+            let below = if level == 0 {
+                &self.serializable.leaves
+            } else {
+                &self.cached_nodes[level - 1]
+            };
+            let parents = below
+                .chunks(2)
+                .map(|pair| self.hash_pair(&pair[0], pair.get(1).unwrap_or(&zero)))
+                .collect();
+            self.cached_nodes[level] = parents;
+        }
+        self.sync_frontier();
+    }
+
+    /// Generate a Merkle proof for a leaf at the given index.
+    ///
+    /// Reads siblings from the cached nodes in O(depth); falls back to
+    /// `prove_slow` while they are stale after a bulk load.
+    pub fn prove(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+        let _timer = OpTimer::start(PROVE_SECONDS);
         if leaf_index >= self.serializable.leaves.len() {
             return Err(IndexerError::LeafNotAppended);
         }
+        if !self.frontier_valid {
+            return self.prove_slow(leaf_index);
+        }
+
+        let siblings = (0..self.depth)
+            .map(|level| self.node(level, (leaf_index >> level) ^ 1))
+            .collect();
+        Ok(MerkleProof::new(
+            leaf_index,
+            self.serializable.leaves[leaf_index],
+            siblings,
+        ))
+    }
 
-        // Use the working root() method to compute the correct root
-        // Then generate proof using full recomputation (correct but not O(depth))
+    /// Generate a proof by recomputing every level from the leaves
+    pub fn prove_slow(&self, leaf_index: usize) -> IndexerResult<MerkleProof> {
+        if leaf_index >= self.serializable.leaves.len() {
+            return Err(IndexerError::LeafNotAppended);
+        }
+        Ok(self.prove_prefix(self.serializable.leaves.len(), leaf_index))
+    }
+
+    /// Generate a proof against the root the tree had when it held
+    /// `as_of_len` leaves, treating every later leaf as empty.
+    ///
+    /// Leaves updated since then are proven with their current value, so
+    /// the proof only matches a past root if the prefix was not modified.
+    pub fn prove_at(&self, leaf_index: usize, as_of_len: usize) -> IndexerResult<MerkleProof> {
+        if as_of_len > self.len() || leaf_index >= as_of_len {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        Ok(self.prove_prefix(as_of_len, leaf_index))
+    }
+
+    /// Root the tree had when it held only its first `len` leaves
+    pub fn root_at(&self, len: usize) -> IndexerResult<Hash> {
+        if len > self.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        if len == 0 {
+            return Ok(self.zero_hashes[self.depth]);
+        }
+        // Leaf 0 is the left child at every level
+        let proof = self.prove_prefix(len, 0);
+        Ok(proof
+            .siblings
+            .iter()
+            .fold(proof.leaf, |node, sibling| self.hash_pair(&node, sibling)))
+    }
+
+    /// Recompute every level of the tree made of the first `len` leaves
+    /// and read the proof of `leaf_index` out of it
+    fn prove_prefix(&self, len: usize, leaf_index: usize) -> MerkleProof {
         let leaf = self.serializable.leaves[leaf_index];
         let mut siblings = Vec::with_capacity(self.depth);
-        let mut current_level = self.serializable.leaves.clone();
+        let mut current_level = self.serializable.leaves[..len].to_vec();
         let mut idx = leaf_index;
 
         // Generate siblings by computing each level
@@ -338,14 +1167,224 @@ impl IncrementalMerkleTree {
                 } else {
                     self.zero_hashes[level]
                 };
-                next_level.push(hash_pair(&left, &right));
+                next_level.push(self.hash_pair(&left, &right));
             }
 
             current_level = next_level;
             idx /= 2;
         }
 
-        Ok(MerkleProof::new(leaf_index, leaf, siblings))
+        MerkleProof::new(leaf_index, leaf, siblings)
+    }
+
+    /// Generate proofs for every leaf, in leaf order.
+    ///
+    /// The node levels are built once and shared by all proofs, instead of
+    /// being recomputed per leaf as `prove` does.
+    pub fn prove_all(&self) -> Vec<MerkleProof> {
+        let levels = self.to_node_array();
+        (0..self.len())
+            .map(|index| self.proof_from_levels(&levels, index))
+            .collect()
+    }
+
+    /// Generate proofs for a set of leaves against the current root.
+    ///
+    /// Indices are deduplicated and the proofs returned in ascending index
+    /// order. Every index is checked before any proof is built, and the node
+    /// levels are computed once for the whole batch.
+    pub fn prove_many(&self, indices: &[usize]) -> IndexerResult<Vec<MerkleProof>> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.last().is_some_and(|&index| index >= self.len()) {
+            return Err(IndexerError::LeafNotAppended);
+        }
+
+        let levels = self.to_node_array();
+        Ok(indices
+            .into_iter()
+            .map(|index| self.proof_from_levels(&levels, index))
+            .collect())
+    }
+
+    /// Stream the proof of every leaf to a writer, in leaf order.
+    ///
+    /// Each proof is written as a u32 LE length followed by the proof
+    /// encoded in `options.format`, and the whole stream is compressed with
+    /// `options.compression`. Proofs are read from levels built once and
+    /// written as they are produced; read them back with
+    /// `read_proof_stream`.
+    pub fn export_proofs<W: Write>(
+        &self,
+        writer: W,
+        options: &SerializationOptions,
+    ) -> IndexerResult<()> {
+        let levels = self.to_node_array();
+        let write_frames = |writer: &mut dyn Write| -> IndexerResult<()> {
+            for index in 0..self.len() {
+                let proof = self.proof_from_levels(&levels, index);
+                write_proof_frame(writer, &proof, &options.format)?;
+            }
+            Ok(())
+        };
+
+        options.compression.write_with(writer, write_frames)
+    }
+
+    /// Generate proofs for every leaf in parallel; same result as `prove_all`
+    #[cfg(feature = "rayon")]
+    pub fn prove_all_parallel(&self) -> Vec<MerkleProof> {
+        use rayon::prelude::*;
+
+        let levels = self.to_node_array();
+        (0..self.len())
+            .into_par_iter()
+            .map(|index| self.proof_from_levels(&levels, index))
+            .collect()
+    }
+
+    /// Read a leaf's proof out of levels produced by `to_node_array`
+    fn proof_from_levels(&self, levels: &[Vec<Hash>], leaf_index: usize) -> MerkleProof {
+        let mut idx = leaf_index;
+        let siblings = levels[..self.depth]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| {
+                let sibling = nodes
+                    .get(idx ^ 1)
+                    .copied()
+                    .unwrap_or(self.zero_hashes[level]);
+                idx /= 2;
+                sibling
+            })
+            .collect();
+
+        MerkleProof::new(leaf_index, levels[0][leaf_index], siblings)
+    }
+
+    /// Generate a proof's siblings as `Digest`s in the sparse tree's order,
+    /// from the level just below the root down to the leaf level.
+    pub fn prove_as_digests(&self, index: usize) -> IndexerResult<Vec<Digest>> {
+        let proof = self.prove(index)?;
+        Ok(proof.siblings.into_iter().rev().map(Digest).collect())
+    }
+}
+
+/// Builder for trees with non-default construction options
+#[derive(Debug, Clone)]
+pub struct TreeBuilder {
+    depth: usize,
+    hasher: HashAlgorithm,
+    zero_leaf: Option<Hash>,
+    separators: DomainSeparators,
+    dedup: bool,
+    max_leaf_size: usize,
+    max_memory: usize,
+    root_cache: bool,
+}
+
+impl TreeBuilder {
+    /// Set the tree depth (defaults to `DEFAULT_TREE_DEPTH`)
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the hash algorithm (defaults to Poseidon)
+    pub fn hasher(mut self, hasher: HashAlgorithm) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Use `zero_leaf` as the empty leaf hash instead of the hash of a
+    /// single zero byte
+    pub fn zero_leaf(mut self, zero_leaf: Hash) -> Self {
+        self.zero_leaf = Some(zero_leaf);
+        self
+    }
+
+    /// Tag Poseidon leaf and node hashes with `separators` instead of the
+    /// defaults. Proofs from such a tree verify with
+    /// `MerkleProof::verify_with_separators`; SHA-256 trees ignore this.
+    pub fn domain_separators(mut self, separators: DomainSeparators) -> Self {
+        self.separators = separators;
+        self
+    }
+
+    /// Return the existing index when appending a duplicate leaf
+    pub fn dedup(mut self, enabled: bool) -> Self {
+        self.dedup = enabled;
+        self
+    }
+
+    /// Limit the size of leaf data accepted by `append`/`update` (0 = unlimited)
+    pub fn max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size;
+        self
+    }
+
+    /// Cap the tree's estimated memory footprint in bytes (0 = unlimited)
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// Enable or disable caching of the root (enabled by default)
+    pub fn root_cache(mut self, enabled: bool) -> Self {
+        self.root_cache = enabled;
+        self
+    }
+
+    /// Build a tree holding `leaves`, e.g. ones deserialized from disk.
+    ///
+    /// Construction options are not serialized with a tree, so this is how
+    /// they are reapplied to stored leaves.
+    pub fn build_from(self, leaves: SerializableTree) -> IndexerResult<IncrementalMerkleTree> {
+        let mut tree = self.build()?;
+        if leaves.len() > tree.capacity {
+            return Err(IndexerError::TreeFull);
+        }
+        tree.serializable = leaves;
+        tree.rebuild_frontier()?;
+        Ok(tree)
+    }
+
+    /// Build the empty tree, rejecting depths outside `1..=63`
+    pub fn build(self) -> IndexerResult<IncrementalMerkleTree> {
+        if self.depth == 0 || self.depth > 63 {
+            return Err(IndexerError::InvalidData(format!(
+                "invalid tree depth: {}",
+                self.depth
+            )));
+        }
+
+        let mut tree = IncrementalMerkleTree::with_depth(self.depth)
+            .with_max_leaf_size(self.max_leaf_size)
+            .with_max_memory(self.max_memory)
+            .with_root_cache(self.root_cache);
+        tree.hasher = self.hasher;
+        tree.separators = self.separators;
+        let zero_leaf = self.zero_leaf.unwrap_or_else(|| tree.hash_leaf(&[0u8]));
+        tree.zero_hashes = tree.compute_zero_hashes(zero_leaf);
+        tree.frontier = tree.zero_hashes.clone();
+        tree.dedup = self.dedup;
+        Ok(tree)
+    }
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        Self {
+            depth: crate::tree::DEFAULT_TREE_DEPTH,
+            hasher: HashAlgorithm::default(),
+            zero_leaf: None,
+            separators: DomainSeparators::default(),
+            dedup: false,
+            max_leaf_size: 0,
+            max_memory: 0,
+            root_cache: true,
+        }
     }
 }
 
@@ -358,6 +1397,207 @@ impl Default for IncrementalMerkleTree {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::internal::hash_bytes;
+
+    #[test]
+    fn test_builder_options_take_effect() {
+        let zero_leaf = [7u8; 32];
+        let mut tree = IncrementalMerkleTree::builder()
+            .depth(6)
+            .hasher(HashAlgorithm::Sha256)
+            .zero_leaf(zero_leaf)
+            .dedup(true)
+            .max_leaf_size(8)
+            .build()
+            .unwrap();
+
+        assert_eq!(tree.depth(), 6);
+        assert_eq!(tree.capacity(), 64);
+        assert_eq!(tree.hasher(), HashAlgorithm::Sha256);
+        assert!(tree.is_dedup());
+        assert_eq!(tree.max_leaf_size(), 8);
+        assert_eq!(tree.zero_hash(0), Some(zero_leaf));
+
+        let expected_empty = HashAlgorithm::Sha256.zero_hashes_from(zero_leaf, 6)[6];
+        assert_eq!(tree.root(), expected_empty);
+
+        assert_eq!(tree.append(b"a").unwrap(), 0);
+        assert_eq!(tree.append(b"b").unwrap(), 1);
+        assert_eq!(tree.append(b"a").unwrap(), 0);
+        assert!(tree.append(b"too long!").is_err());
+        assert_eq!(tree.len(), 2);
+        // Empty positions are padded with subtrees of the custom zero leaf
+        let sha = HashAlgorithm::Sha256;
+        let zeros = sha.zero_hashes_from(zero_leaf, 6);
+        let mut expected = sha.hash_pair(&sha.hash_leaf(b"a"), &sha.hash_leaf(b"b"));
+        for zero in &zeros[1..6] {
+            expected = sha.hash_pair(&expected, zero);
+        }
+        assert_eq!(tree.root(), expected);
+        assert!(tree
+            .prove(1)
+            .unwrap()
+            .verify_with(&tree.root(), HashAlgorithm::Sha256));
+
+        assert!(IncrementalMerkleTree::builder().depth(0).build().is_err());
+    }
+
+    #[test]
+    fn test_append_hash_matches_append() {
+        let mut hashed = IncrementalMerkleTree::new(20);
+        let mut precomputed = IncrementalMerkleTree::new(20);
+
+        assert_eq!(hashed.append(b"x").unwrap(), 0);
+        assert_eq!(precomputed.append_hash(hash_bytes(b"x")).unwrap(), 0);
+        assert_eq!(hashed.root(), precomputed.root());
+
+        hashed.update(0, b"y").unwrap();
+        precomputed.update_hash(0, hash_bytes(b"y")).unwrap();
+        assert_eq!(hashed.root(), precomputed.root());
+
+        assert!(matches!(
+            precomputed.update_hash(1, [0u8; 32]),
+            Err(IndexerError::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn test_active_leaves_round_trip() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        for i in 0..10u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let zero = tree.zero_hash(0).unwrap();
+        tree.update_hash(2, zero).unwrap();
+        tree.update_hash(7, zero).unwrap();
+
+        let active = tree.active_leaves();
+        assert_eq!(active.len(), 8);
+        assert!(active.iter().all(|(index, _)| *index != 2 && *index != 7));
+
+        let rebuilt = IncrementalMerkleTree::from_active_leaves(&active, 16).unwrap();
+        assert_eq!(rebuilt.root(), tree.root());
+        assert_eq!(
+            rebuilt.prove(9).unwrap().siblings,
+            tree.prove(9).unwrap().siblings
+        );
+
+        for index in [1usize << 16, usize::MAX] {
+            let out_of_range = [(index, [1u8; 32])];
+            assert!(matches!(
+                IncrementalMerkleTree::from_active_leaves(&out_of_range, 16),
+                Err(IndexerError::InvalidData(_))
+            ));
+        }
+        assert!(matches!(
+            IncrementalMerkleTree::from_active_leaves(&[], 0),
+            Err(IndexerError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_prove_many_matches_prove() {
+        let mut tree = IncrementalMerkleTree::new(12);
+        for i in 0..300u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        let proofs = tree.prove_many(&[257, 3, 99, 3, 0, 299]).unwrap();
+        let indices: Vec<usize> = proofs.iter().map(|proof| proof.leaf_index).collect();
+        assert_eq!(indices, vec![0, 3, 99, 257, 299]);
+        for proof in &proofs {
+            let single = tree.prove(proof.leaf_index).unwrap();
+            assert_eq!(proof.leaf, single.leaf);
+            assert_eq!(proof.siblings, single.siblings);
+        }
+
+        assert!(matches!(
+            tree.prove_many(&[1, 300]),
+            Err(IndexerError::LeafNotAppended)
+        ));
+        assert!(tree.prove_many(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prove_at_past_size() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        for i in 0..5u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root_at_5 = tree.root();
+        for i in 5..8u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        let proof = tree.prove_at(2, 5).unwrap();
+        assert!(proof.verify(&root_at_5));
+        assert!(!proof.verify(&tree.root()));
+        assert_eq!(
+            tree.prove_at(2, 8).unwrap().siblings,
+            tree.prove(2).unwrap().siblings
+        );
+
+        assert!(matches!(
+            tree.prove_at(5, 5),
+            Err(IndexerError::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            tree.prove_at(2, 9),
+            Err(IndexerError::IndexOutOfBounds)
+        ));
+        assert_eq!(tree.root_at(5).unwrap(), root_at_5);
+        assert_eq!(tree.root_at(8).unwrap(), tree.root());
+        assert_eq!(
+            tree.root_at(0).unwrap(),
+            IncrementalMerkleTree::new(8).root()
+        );
+        assert!(tree.root_at(9).is_err());
+    }
+
+    #[test]
+    fn test_memory_footprint_and_cap() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        let empty = tree.memory_footprint();
+        tree.append(b"a").unwrap();
+        let one = tree.memory_footprint();
+        for i in 0..100u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        assert!(empty < one);
+        assert!(one < tree.memory_footprint());
+
+        let cap = empty + 64 * 50;
+        let mut capped = IncrementalMerkleTree::new(16).with_max_memory(cap);
+        let mut appended = 0;
+        let err = loop {
+            match capped.append(&(appended as u32).to_le_bytes()) {
+                Ok(_) => appended += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(err, IndexerError::MemoryLimitExceeded(limit) if limit == cap));
+        assert!(appended > 0);
+        assert!(capped.memory_footprint() <= cap);
+        assert!(capped.batch_append(&[b"x"]).is_err());
+    }
+
+    #[test]
+    fn test_truncate_restores_earlier_root() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        for i in 0..5u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let earlier = tree.root();
+        for i in 5..12u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        tree.truncate(5).unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.root(), earlier);
+        assert_eq!(tree.append(b"next").unwrap(), 5);
+        assert!(tree.truncate(7).is_err());
+    }
 
     #[test]
     fn test_basic_operations() {
@@ -397,4 +1637,674 @@ mod tests {
         let proof = tree.prove(idx).unwrap();
         assert!(proof.verify(&tree.root()));
     }
+
+    #[test]
+    fn test_dedup_returns_existing_index() {
+        let mut tree = IncrementalMerkleTree::with_dedup(crate::tree::DEFAULT_TREE_DEPTH);
+
+        let first = tree.append(b"same").unwrap();
+        let root = tree.root();
+        let second = tree.append(b"same").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.root(), root);
+
+        // Without dedup the same data creates distinct leaves
+        let mut plain = IncrementalMerkleTree::new(crate::tree::DEFAULT_TREE_DEPTH);
+        assert_eq!(plain.append(b"same").unwrap(), 0);
+        assert_eq!(plain.append(b"same").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_builder_domain_separators() {
+        let separators = DomainSeparators::new(7, 8).unwrap();
+        let mut tree = IncrementalMerkleTree::builder()
+            .depth(1)
+            .domain_separators(separators)
+            .build()
+            .unwrap();
+        tree.append(b"a").unwrap();
+        tree.append(b"b").unwrap();
+        assert_eq!(tree.domain_separators(), separators);
+
+        let poseidon = HashAlgorithm::Poseidon;
+        let expected = poseidon.hash_pair_with(
+            &poseidon.hash_leaf_with(b"a", &separators),
+            &poseidon.hash_leaf_with(b"b", &separators),
+            &separators,
+        );
+        assert_eq!(tree.root(), expected);
+
+        let mut default = IncrementalMerkleTree::new(1);
+        default.append(b"a").unwrap();
+        default.append(b"b").unwrap();
+        assert_ne!(tree.root(), default.root());
+
+        let proof = tree.prove(1).unwrap();
+        assert!(proof.verify_with_separators(&tree.root(), &separators));
+        assert!(!proof.verify(&tree.root()));
+        assert!(default.extend_from(&tree).is_err());
+    }
+
+    #[test]
+    fn test_dedup_restored_from_builder() {
+        let mut tree = IncrementalMerkleTree::with_dedup(crate::tree::DEFAULT_TREE_DEPTH);
+        tree.append(b"a").unwrap();
+        tree.append(b"b").unwrap();
+
+        // Construction options are not part of the serialized tree
+        let bytes = bincode::serialize(&tree).unwrap();
+        let restored: IncrementalMerkleTree = bincode::deserialize(&bytes).unwrap();
+        assert!(!restored.is_dedup());
+
+        let mut restored = IncrementalMerkleTree::builder()
+            .dedup(true)
+            .build_from(restored.serializable)
+            .unwrap();
+        assert!(restored.is_dedup());
+        assert_eq!(restored.append(b"b").unwrap(), 1);
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.root(), tree.root());
+    }
+
+    #[test]
+    fn test_serialized_layout_matches_original_format() {
+        /// Field layout written before construction options were added
+        #[derive(Serialize)]
+        struct OriginalTree {
+            serializable: SerializableTree,
+            depth: usize,
+            capacity: usize,
+            zero_hashes: Vec<Hash>,
+        }
+
+        let mut tree = IncrementalMerkleTree::builder()
+            .depth(8)
+            .dedup(true)
+            .max_leaf_size(64)
+            .build()
+            .unwrap();
+        tree.append(b"leaf").unwrap();
+
+        let original = OriginalTree {
+            serializable: tree.serializable.clone(),
+            depth: tree.depth,
+            capacity: tree.capacity,
+            zero_hashes: tree.zero_hashes.clone(),
+        };
+        assert_eq!(
+            bincode::serialize(&tree).unwrap(),
+            bincode::serialize(&original).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_max_leaf_size() {
+        let mut tree =
+            IncrementalMerkleTree::new(crate::tree::DEFAULT_TREE_DEPTH).with_max_leaf_size(64);
+
+        let err = tree.append(&[7u8; 100]).unwrap_err();
+        assert!(matches!(err, IndexerError::InvalidData(_)));
+        assert!(tree.is_empty());
+
+        assert_eq!(tree.append(&[7u8; 32]).unwrap(), 0);
+        assert!(tree.update(0, &[8u8; 100]).is_err());
+    }
+
+    #[test]
+    fn test_extend_from_matches_concatenation() {
+        let depth = crate::tree::DEFAULT_TREE_DEPTH;
+        let mut left = IncrementalMerkleTree::new(depth);
+        let mut right = IncrementalMerkleTree::new(depth);
+        let mut combined = IncrementalMerkleTree::new(depth);
+
+        for data in [b"a", b"b", b"c"] {
+            left.append(data).unwrap();
+            combined.append(data).unwrap();
+        }
+        for data in [b"d", b"e"] {
+            right.append(data).unwrap();
+            combined.append(data).unwrap();
+        }
+
+        left.extend_from(&right).unwrap();
+        assert_eq!(left.len(), 5);
+        assert_eq!(left.root(), combined.root());
+    }
+
+    #[test]
+    fn test_extend_from_rejects_incompatible_trees() {
+        let mut tree = IncrementalMerkleTree::new(2);
+        tree.append(b"a").unwrap();
+
+        let other_depth = IncrementalMerkleTree::new(3);
+        assert!(tree.extend_from(&other_depth).is_err());
+
+        let mut too_many = IncrementalMerkleTree::new(2);
+        for data in [b"b", b"c", b"d", b"e"] {
+            too_many.append(data).unwrap();
+        }
+        assert!(matches!(
+            tree.extend_from(&too_many),
+            Err(IndexerError::TreeFull)
+        ));
+        assert_eq!(tree.len(), 1);
+
+        let mut limited = IncrementalMerkleTree::new(4);
+        limited.append(b"a").unwrap();
+        let footprint = limited.memory_footprint();
+        let mut limited = limited.with_max_memory(footprint);
+        let mut more = IncrementalMerkleTree::new(4);
+        more.append(b"b").unwrap();
+        assert!(matches!(
+            limited.extend_from(&more),
+            Err(IndexerError::MemoryLimitExceeded(_))
+        ));
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_extend_from_dedup_skips_present_leaves() {
+        let mut left = IncrementalMerkleTree::with_dedup(4);
+        let mut right = IncrementalMerkleTree::new(4);
+        let mut expected = IncrementalMerkleTree::with_dedup(4);
+        for data in [b"a", b"b"] {
+            left.append(data).unwrap();
+            expected.append(data).unwrap();
+        }
+        for data in [b"b", b"c", b"c"] {
+            right.append(data).unwrap();
+            expected.append(data).unwrap();
+        }
+
+        left.extend_from(&right).unwrap();
+        assert_eq!(left.len(), 3);
+        assert_eq!(left.root(), expected.root());
+    }
+
+    #[test]
+    fn test_clear_resets_to_empty_root() {
+        let mut tree = IncrementalMerkleTree::with_dedup(4);
+        let empty_root = tree.root();
+        for i in 0..5u8 {
+            tree.append(&[i]).unwrap();
+        }
+        assert_ne!(tree.root(), empty_root);
+
+        tree.clear();
+        assert_eq!(tree.len(), 0);
+        assert_eq!(tree.depth(), 4);
+        assert!(tree.is_dedup());
+        assert_eq!(tree.root(), empty_root);
+
+        // Dedup lookup is reset too, so a previously seen leaf gets index 0
+        assert_eq!(tree.append(&[3]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_export_import_leaves_round_trip() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        for i in 0..100u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        let mut buffer = Vec::new();
+        tree.export_leaves(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 16 + 100 * 32);
+
+        let restored = IncrementalMerkleTree::import_leaves(buffer.as_slice()).unwrap();
+        assert_eq!(restored.depth(), 8);
+        assert_eq!(restored.len(), 100);
+        assert_eq!(restored.root(), tree.root());
+
+        // Truncated input and bad magic are rejected
+        assert!(IncrementalMerkleTree::import_leaves(&buffer[..buffer.len() - 1]).is_err());
+        buffer[0] = b'X';
+        assert!(IncrementalMerkleTree::import_leaves(buffer.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_simulate_matches_mutation() {
+        let mut tree = IncrementalMerkleTree::new(6);
+        tree.append(b"existing").unwrap();
+        let before = tree.root();
+
+        let ops = vec![
+            TreeOp::Append(b"a".to_vec()),
+            TreeOp::Append(b"b".to_vec()),
+            TreeOp::Update(0, b"updated".to_vec()),
+        ];
+        let simulated = tree.simulate(&ops).unwrap();
+        assert_eq!(tree.root(), before);
+        assert_eq!(tree.len(), 1);
+
+        tree.append(b"a").unwrap();
+        tree.append(b"b").unwrap();
+        tree.update(0, b"updated").unwrap();
+        assert_eq!(tree.root(), simulated);
+
+        // Clearing a leaf gives the same root as never having set it
+        let mut expected = IncrementalMerkleTree::new(6);
+        expected.append(b"updated").unwrap();
+        expected.append(b"a").unwrap();
+        expected.set_next_index(3).unwrap();
+        assert_eq!(tree.simulate(&[TreeOp::Clear(2)]).unwrap(), expected.root());
+        assert!(tree.simulate(&[TreeOp::Clear(3)]).is_err());
+    }
+
+    #[test]
+    fn test_append_and_prove() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"first").unwrap();
+
+        let (index, root, proof) = tree.append_and_prove(b"second").unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(root, tree.root());
+        assert_eq!(proof.leaf_index, 1);
+        assert!(proof.verify_against(&root, &hash_bytes(b"second")));
+    }
+
+    #[test]
+    fn test_root_fast_path_matches_full_fold() {
+        for count in [1usize, 2, 3, 4, 5, 8, 13] {
+            let mut tree = IncrementalMerkleTree::new(crate::tree::DEFAULT_TREE_DEPTH);
+            for i in 0..count {
+                tree.append(&i.to_le_bytes()).unwrap();
+            }
+
+            // to_node_array folds every level explicitly, while compute_root
+            // stops folding once a level collapses to one node. root() would
+            // answer from the frontier, so call compute_root directly.
+            let full = tree.to_node_array().last().unwrap()[0];
+            assert_eq!(tree.compute_root(), full, "mismatch with {count} leaves");
+
+            let uncached = tree.with_root_cache(false);
+            assert_eq!(uncached.root(), full, "uncached, {count} leaves");
+        }
+    }
+
+    #[test]
+    fn test_prove_as_digests_root_first() {
+        use crate::tree::common::hash_two_things;
+
+        let mut tree = IncrementalMerkleTree::new(8);
+        for i in 0..5u8 {
+            tree.append(&[i]).unwrap();
+        }
+        let index = 3;
+        let digests = tree.prove_as_digests(index).unwrap();
+        assert_eq!(digests.len(), 8);
+
+        // Sparse-style check: path bits are read from the root down, so the
+        // fold starts from the last digest
+        let mut current = Digest(tree.get_leaf_hash(index).unwrap());
+        for (level, sibling) in digests.iter().rev().enumerate() {
+            current = if (index >> level) & 1 == 0 {
+                hash_two_things("left", "right", current, sibling)
+            } else {
+                hash_two_things("left", "right", sibling, current)
+            };
+        }
+        assert_eq!(current, Digest(tree.root()));
+    }
+
+    #[test]
+    fn test_node_array_shape() {
+        let mut tree = IncrementalMerkleTree::new(2);
+        for i in 0..4u8 {
+            tree.append(&[i]).unwrap();
+        }
+
+        let nodes = tree.to_node_array();
+        let shape: Vec<usize> = nodes.iter().map(Vec::len).collect();
+        assert_eq!(shape, vec![4, 2, 1]);
+        assert_eq!(nodes[0][3], tree.get_leaf_hash(3).unwrap());
+        assert_eq!(nodes[2][0], tree.root());
+
+        // A partially filled level is padded with its zero hash
+        let mut sparse = IncrementalMerkleTree::new(2);
+        sparse.append(b"only").unwrap();
+        let nodes = sparse.to_node_array();
+        assert_eq!(nodes[0][1], sparse.zero_hash(0).unwrap());
+        assert_eq!(nodes[1][1], sparse.zero_hash(1).unwrap());
+        assert_eq!(nodes[2][0], sparse.root());
+    }
+
+    #[test]
+    fn test_root_cache_invalidated_on_mutation() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"a").unwrap();
+        let first = tree.root();
+
+        tree.append(b"b").unwrap();
+        let after_append = tree.root();
+        assert_ne!(after_append, first);
+
+        tree.update(0, b"c").unwrap();
+        let after_update = tree.root();
+        assert_ne!(after_update, after_append);
+
+        let uncached = tree.clone().with_root_cache(false);
+        assert_eq!(after_update, uncached.root());
+    }
+
+    #[test]
+    fn test_root_cache_skips_recompute() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        tree.append(b"a").unwrap();
+
+        // Appends keep the root in the frontier, so nothing is recomputed
+        tree.root();
+        tree.append(b"b").unwrap();
+        tree.root();
+        assert_eq!(tree.root_computations.get(), 0);
+
+        // Direct leaf writes update the frontier along their path
+        tree.set_leaf_hash(0, [7u8; 32]).unwrap();
+        tree.set_leaf_hash(5, [8u8; 32]).unwrap();
+        tree.root();
+        assert_eq!(tree.root_computations.get(), 0);
+
+        // Bulk loads fall back to a cached full recomputation
+        tree.set_next_index(8).unwrap();
+        tree.set_leaf_hash(7, [9u8; 32]).unwrap();
+        tree.root();
+        tree.root();
+        tree.root();
+        assert_eq!(tree.root_computations.get(), 1);
+
+        tree.set_next_index(9).unwrap();
+        tree.root();
+        tree.root();
+        assert_eq!(tree.root_computations.get(), 2);
+
+        let uncached = IncrementalMerkleTree::new(8).with_root_cache(false);
+        uncached.root();
+        uncached.root();
+        assert_eq!(uncached.root_computations.get(), 2);
+    }
+
+    #[test]
+    fn test_frontier_root_matches_recomputation() {
+        let mut tree = IncrementalMerkleTree::new(16);
+        let checkpoints = [1usize, 2, 3, 7, 64, 1000, 4097, 10_000];
+
+        for i in 0..10_000usize {
+            tree.append(&(i as u32).to_le_bytes()).unwrap();
+            if checkpoints.contains(&(i + 1)) {
+                let scratch = tree.clone().with_root_cache(false);
+                assert_eq!(tree.root(), scratch.root(), "mismatch at {} leaves", i + 1);
+            }
+        }
+        // Every root above came straight from the frontier
+        assert_eq!(tree.root_computations.get(), 0);
+
+        // Appending after an update still tracks the root correctly
+        tree.update(5, b"changed").unwrap();
+        tree.append(b"next").unwrap();
+        let scratch = tree.clone().with_root_cache(false);
+        assert_eq!(tree.root(), scratch.root());
+    }
+
+    #[test]
+    fn test_out_of_order_inserts_keep_frontier() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        for (seed, index) in [(1u64, 9usize), (2, 3), (3, 40), (4, 0), (5, 10)] {
+            let commitment = Commitment::new(1, seed, [seed as u8; 32], [2u8; 32], [3u8; 32]);
+            tree.insert_commitment_at(index, &commitment).unwrap();
+            assert!(tree.frontier_valid, "frontier dropped at leaf {index}");
+
+            let scratch = tree.clone().with_root_cache(false);
+            assert_eq!(tree.root(), scratch.root(), "mismatch at leaf {index}");
+            let proof = tree.prove(index).unwrap();
+            assert!(proof.verify(&scratch.root()));
+        }
+        assert_eq!(tree.len(), 41);
+        assert_eq!(tree.root_computations.get(), 0);
+
+        tree.append(b"next").unwrap();
+        let scratch = tree.clone().with_root_cache(false);
+        assert_eq!(tree.root(), scratch.root());
+    }
+
+    #[test]
+    fn test_rebuilt_frontier_serves_root() {
+        let mut tree = IncrementalMerkleTree::new(8);
+        for i in 0..11u8 {
+            tree.append(&[i]).unwrap();
+        }
+
+        let mut restored =
+            IncrementalMerkleTree::from_serializable(tree.serializable.clone(), tree.depth());
+        restored.rebuild_frontier().unwrap();
+        assert_eq!(restored.root(), tree.root());
+        assert_eq!(restored.root_computations.get(), 0);
+
+        restored.append(b"more").unwrap();
+        tree.append(b"more").unwrap();
+        assert_eq!(restored.root(), tree.root());
+    }
+
+    #[test]
+    fn test_prove_matches_prove_slow() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..3000u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        tree.update(1234, b"updated").unwrap();
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut indices: Vec<usize> = (0..20).map(|_| rng.gen_range(0..3000)).collect();
+        indices.extend([0, 1234, 2999]);
+        for index in indices {
+            let fast = tree.prove(index).unwrap();
+            let slow = tree.prove_slow(index).unwrap();
+            assert_eq!(fast.siblings, slow.siblings, "mismatch at leaf {index}");
+            assert_eq!(fast.leaf, slow.leaf);
+            assert!(fast.verify(&tree.root()));
+        }
+    }
+
+    #[test]
+    fn test_batch_append_matches_single_appends() {
+        let data: Vec<Vec<u8>> = (0..37u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        let leaves: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+
+        let mut single = IncrementalMerkleTree::new(8);
+        single.append(b"existing").unwrap();
+        let mut batched = single.clone();
+        for leaf in &leaves {
+            single.append(leaf).unwrap();
+        }
+
+        let indices = batched.batch_append(&leaves).unwrap();
+        assert_eq!(indices, (1..38).collect::<Vec<_>>());
+        assert_eq!(batched.root(), single.root());
+        assert_eq!(
+            batched.prove(20).unwrap().siblings,
+            single.prove(20).unwrap().siblings
+        );
+
+        // Appending continues from the batch
+        batched.append(b"after").unwrap();
+        single.append(b"after").unwrap();
+        assert_eq!(batched.root(), single.root());
+    }
+
+    #[test]
+    fn test_batch_append_all_or_nothing() {
+        let mut tree = IncrementalMerkleTree::new(2);
+        tree.append(b"a").unwrap();
+        let root = tree.root();
+
+        let leaves: Vec<&[u8]> = vec![b"b", b"c", b"d", b"e"];
+        assert!(matches!(
+            tree.batch_append(&leaves),
+            Err(IndexerError::TreeFull)
+        ));
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.root(), root);
+
+        let mut dedup = IncrementalMerkleTree::with_dedup(4);
+        let leaves: Vec<&[u8]> = vec![b"x", b"y", b"x"];
+        assert_eq!(dedup.batch_append(&leaves).unwrap(), vec![0, 1, 0]);
+        assert_eq!(dedup.len(), 2);
+    }
+
+    /// Leaves appended in order to a depth-4 Poseidon tree
+    const KNOWN_LEAVES: [&[u8]; 4] = [b"alpha", b"beta", b"gamma", b"delta"];
+
+    /// Root after each of `KNOWN_LEAVES` is appended. These are fixed
+    /// values, so any change to leaf or node hashing fails the test.
+    const KNOWN_ROOTS: [&str; 4] = [
+        "19190f2ea6ba01a8889889156f3890f477fc0033cbf932702589bb4c160e8271",
+        "227a357374895b4694ae019122145777b54afb6a3c295f985d07dc67dc2472be",
+        "1c05392a8956828d902197826b6648cff717f69467d1e0ca68f832f1d650cd8f",
+        "151c8ec1378cffee9a8032efce9a0c697d7d5b8ce97b7d296439ae416f358f0c",
+    ];
+
+    #[test]
+    fn test_validate_against_roots() {
+        let leaves = KNOWN_LEAVES.to_vec();
+        let mut roots: Vec<Hash> = KNOWN_ROOTS
+            .iter()
+            .map(|root| hex::decode(root).unwrap().try_into().unwrap())
+            .collect();
+
+        IncrementalMerkleTree::validate_against_roots(&leaves, &roots, 4).unwrap();
+
+        roots[2] = [0u8; 32];
+        let err = IncrementalMerkleTree::validate_against_roots(&leaves, &roots, 4).unwrap_err();
+        assert!(err.to_string().contains("after leaf 2"), "{err}");
+
+        assert!(IncrementalMerkleTree::validate_against_roots(&leaves, &roots[..3], 4).is_err());
+    }
+
+    #[test]
+    fn test_prove_all_matches_prove() {
+        let mut tree = IncrementalMerkleTree::new(6);
+        assert!(tree.prove_all().is_empty());
+
+        for i in 0..13u8 {
+            tree.append(&[i]).unwrap();
+        }
+        let root = tree.root();
+
+        let proofs = tree.prove_all();
+        assert_eq!(proofs.len(), 13);
+        for (index, proof) in proofs.iter().enumerate() {
+            let expected = tree.prove(index).unwrap();
+            assert_eq!(proof.leaf_index, index);
+            assert_eq!(proof.leaf, expected.leaf);
+            assert_eq!(proof.siblings, expected.siblings);
+            assert!(proof.verify(&root));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_prove_all_parallel_matches_serial() {
+        let mut tree = IncrementalMerkleTree::new(12);
+        for i in 0..1000u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+
+        let serial = tree.prove_all();
+        let parallel = tree.prove_all_parallel();
+        assert_eq!(parallel.len(), 1000);
+        for (a, b) in serial.iter().zip(&parallel) {
+            assert_eq!(a.leaf_index, b.leaf_index);
+            assert_eq!(a.leaf, b.leaf);
+            assert_eq!(a.siblings, b.siblings);
+        }
+    }
+
+    #[test]
+    fn test_apply_delta_checked() {
+        let mut base = IncrementalMerkleTree::new(8);
+        base.append(b"a").unwrap();
+        base.append(b"b").unwrap();
+        let mut target = base.clone();
+        target.update(1, b"c").unwrap();
+        target.append(b"d").unwrap();
+        let delta = TreeDelta::between(&base, &target).unwrap();
+
+        let mut replica = base.clone();
+        replica.apply_delta_checked(&delta, &target.root()).unwrap();
+        assert_eq!(replica.len(), 3);
+        assert_eq!(replica.root(), target.root());
+
+        let mut tampered = delta.clone();
+        tampered.changes[0].1 = [7u8; 32];
+        let mut replica = base.clone();
+        assert!(replica
+            .apply_delta_checked(&tampered, &target.root())
+            .is_err());
+        assert_eq!(replica.len(), 2);
+        assert_eq!(replica.get_leaf_hash(1), base.get_leaf_hash(1));
+        assert_eq!(replica.root(), base.root());
+    }
+
+    #[test]
+    fn test_check_delta_does_not_mutate() {
+        let mut base = IncrementalMerkleTree::new(8);
+        base.append(b"a").unwrap();
+        let mut target = base.clone();
+        target.append(b"b").unwrap();
+        let delta = TreeDelta::between(&base, &target).unwrap();
+
+        base.check_delta(&delta).unwrap();
+        assert_eq!(base.len(), 1);
+
+        let mut beyond = delta.clone();
+        beyond.changes.push((5, [1u8; 32]));
+        assert!(matches!(
+            base.check_delta(&beyond),
+            Err(IndexerError::InvalidData(_))
+        ));
+        assert!(target.check_delta(&delta).is_err());
+    }
+
+    #[test]
+    fn test_min_depth_for() {
+        assert_eq!(IncrementalMerkleTree::min_depth_for(0).unwrap(), 1);
+        assert_eq!(IncrementalMerkleTree::min_depth_for(1).unwrap(), 1);
+        assert_eq!(IncrementalMerkleTree::min_depth_for(5).unwrap(), 3);
+        assert_eq!(IncrementalMerkleTree::min_depth_for(8).unwrap(), 3);
+        assert_eq!(IncrementalMerkleTree::min_depth_for(9).unwrap(), 4);
+
+        let depth = IncrementalMerkleTree::min_depth_for(9).unwrap();
+        assert!(IncrementalMerkleTree::new(depth).capacity() >= 9);
+
+        let largest = 1usize << (usize::BITS - 1);
+        assert_eq!(
+            IncrementalMerkleTree::min_depth_for(largest).unwrap(),
+            usize::BITS as usize - 1
+        );
+        assert!(matches!(
+            IncrementalMerkleTree::min_depth_for(largest + 1),
+            Err(IndexerError::InvalidData(_))
+        ));
+        assert!(IncrementalMerkleTree::min_depth_for(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_leaves_iterates_in_order() {
+        let mut tree = IncrementalMerkleTree::new(4);
+        assert_eq!(tree.leaves().count(), 0);
+
+        let hashes: Vec<Hash> = (0..6u8).map(|i| [i + 1; 32]).collect();
+        for hash in &hashes {
+            tree.append_hash(*hash).unwrap();
+        }
+
+        let leaves: Vec<(usize, Hash)> = tree.leaves().collect();
+        let expected: Vec<(usize, Hash)> = hashes.iter().copied().enumerate().collect();
+        assert_eq!(leaves, expected);
+        assert_eq!(tree.leaf(3), Some(hashes[3]));
+        assert_eq!(tree.leaf(6), None);
+    }
 }
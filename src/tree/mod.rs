@@ -1,5 +1,6 @@
 pub mod commitment;
 pub mod common;
+pub mod frontier;
 pub mod incremental;
 pub mod kv_trait;
 pub mod proof;
@@ -7,6 +8,7 @@ pub mod proof;
 pub const DEFAULT_TREE_DEPTH: usize = 20; // Default depth for the Merkle tree
 
 pub use commitment::*;
+pub use frontier::*;
 pub use incremental::*;
 pub use kv_trait::*;
 pub use proof::*;
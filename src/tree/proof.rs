@@ -1,7 +1,12 @@
-use crate::utils::{internal::hash_pair, Hash};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{IndexerError, IndexerResult};
+use crate::serialization::SerializationFormat;
+use crate::utils::{internal::hash_pair, DomainSeparators, Hash, HashAlgorithm};
 
 /// Merkle proof for a leaf in the tree
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MerkleProof {
     /// Index of the leaf this proof is for
     pub leaf_index: usize,
@@ -27,13 +32,81 @@ impl MerkleProof {
         Self::verify_proof(&self.leaf, self.leaf_index, &self.siblings, root)
     }
 
+    /// Verify this proof against a root from a tree built with `hasher`
+    pub fn verify_with(&self, root: &Hash, hasher: HashAlgorithm) -> bool {
+        &self.compute_root(hasher, &DomainSeparators::default()) == root
+    }
+
+    /// Verify this proof against the root of a Poseidon tree built with
+    /// custom domain separators
+    pub fn verify_with_separators(&self, root: &Hash, separators: &DomainSeparators) -> bool {
+        self.verify_with_hashing(root, HashAlgorithm::Poseidon, separators)
+    }
+
+    /// Verify this proof against a root from a tree built with `hasher` and
+    /// `separators`, e.g. `tree.hasher()` and `tree.domain_separators()`
+    pub fn verify_with_hashing(
+        &self,
+        root: &Hash,
+        hasher: HashAlgorithm,
+        separators: &DomainSeparators,
+    ) -> bool {
+        &self.compute_root(hasher, separators) == root
+    }
+
+    /// Verify this proof against a trusted root, additionally requiring that
+    /// the proof is for the expected leaf.
+    ///
+    /// This prevents a valid proof for some other leaf from being substituted.
+    pub fn verify_against(&self, trusted_root: &Hash, leaf_expected: &Hash) -> bool {
+        &self.leaf == leaf_expected && self.verify(trusted_root)
+    }
+
+    /// Find the first of several candidate roots this proof verifies
+    /// against, e.g. while a reorg leaves more than one root plausible.
+    ///
+    /// The path is hashed once and compared with each candidate.
+    pub fn verify_any(&self, roots: &[Hash]) -> Option<usize> {
+        let computed = self.compute_root(HashAlgorithm::Poseidon, &DomainSeparators::default());
+        roots.iter().position(|root| root == &computed)
+    }
+
+    /// Check that every proof verifies against `root`, stopping at the
+    /// first failure
+    pub fn verify_batch(proofs: &[MerkleProof], root: &Hash) -> bool {
+        proofs.iter().all(|proof| proof.verify(root))
+    }
+
+    /// Verify every proof against `root`, reporting each result in input
+    /// order so failing leaves can be identified
+    pub fn verify_batch_detailed(proofs: &[MerkleProof], root: &Hash) -> Vec<bool> {
+        proofs.iter().map(|proof| proof.verify(root)).collect()
+    }
+
+    /// Hash the leaf up through its siblings to the root it implies
+    fn compute_root(&self, hasher: HashAlgorithm, separators: &DomainSeparators) -> Hash {
+        let mut computed = self.leaf;
+        let mut idx = self.leaf_index;
+
+        for sibling_hash in &self.siblings {
+            computed = if idx.is_multiple_of(2) {
+                hasher.hash_pair_with(&computed, sibling_hash, separators)
+            } else {
+                hasher.hash_pair_with(sibling_hash, &computed, separators)
+            };
+            idx /= 2;
+        }
+
+        computed
+    }
+
     /// Static method to verify a proof without creating a MerkleProof instance
     pub fn verify_proof(leaf: &Hash, leaf_index: usize, siblings: &[Hash], root: &Hash) -> bool {
         let mut computed = *leaf;
         let mut idx = leaf_index;
 
         for sibling_hash in siblings.iter() {
-            if idx % 2 == 0 {
+            if idx.is_multiple_of(2) {
                 // Current node is left child
                 computed = hash_pair(&computed, sibling_hash);
             } else {
@@ -50,4 +123,440 @@ impl MerkleProof {
     pub fn depth(&self) -> usize {
         self.siblings.len()
     }
+
+    /// Siblings for levels `from..to`, for composing proofs across a
+    /// sharded hierarchy
+    pub fn slice_levels(&self, from: usize, to: usize) -> IndexerResult<Vec<Hash>> {
+        if from > to || to > self.siblings.len() {
+            return Err(IndexerError::IndexOutOfBounds);
+        }
+        Ok(self.siblings[from..to].to_vec())
+    }
+
+    /// Drop siblings equal to the zero hash of their level.
+    ///
+    /// `zero_hashes` are the empty-subtree hashes of the tree that produced
+    /// the proof, e.g. `tree.zero_hashes()`. Proofs for sparsely filled
+    /// trees are mostly empty subtrees, so this usually removes most of
+    /// the path.
+    pub fn compress(&self, zero_hashes: &[Hash]) -> IndexerResult<CompactMerkleProof> {
+        let depth = self.siblings.len();
+        if depth > u64::BITS as usize {
+            return Err(IndexerError::InvalidData(format!(
+                "proof depth {depth} too large to compress"
+            )));
+        }
+        check_zero_hashes(zero_hashes, depth)?;
+
+        let mut zero_mask = 0u64;
+        let mut siblings = Vec::new();
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            if *sibling == zero_hashes[level] {
+                zero_mask |= 1 << level;
+            } else {
+                siblings.push(*sibling);
+            }
+        }
+
+        Ok(CompactMerkleProof {
+            leaf_index: self.leaf_index,
+            leaf: self.leaf,
+            depth: depth as u32,
+            zero_mask,
+            siblings,
+        })
+    }
+
+    /// Encode as JSON for transport to verifiers
+    pub fn to_json(&self) -> IndexerResult<String> {
+        serde_json::to_string(self).map_err(|e| IndexerError::SerializationError(e.to_string()))
+    }
+
+    /// Decode a proof produced by [`MerkleProof::to_json`]
+    pub fn from_json(json: &str) -> IndexerResult<Self> {
+        serde_json::from_str(json).map_err(|e| IndexerError::SerializationError(e.to_string()))
+    }
+
+    /// Encode as bincode
+    pub fn to_bytes(&self) -> IndexerResult<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Decode a proof produced by [`MerkleProof::to_bytes`]
+    pub fn from_bytes(data: &[u8]) -> IndexerResult<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+
+    /// Convert to the layout the on-chain verifier program expects
+    pub fn to_onchain(&self) -> OnchainProof {
+        OnchainProof {
+            siblings: self.siblings.clone(),
+            leaf_index: self.leaf_index as u64,
+        }
+    }
+
+    /// Serialized size in bytes of a proof with `depth` siblings, encoded as
+    /// `(leaf_index, leaf, siblings)` in the given format.
+    ///
+    /// Exact for Bincode. Postcard and MessagePack use variable-length
+    /// integers, so for them this is the upper bound over all leaf indices
+    /// and hash contents at that depth.
+    pub fn serialized_size(depth: usize, format: &SerializationFormat) -> usize {
+        match format {
+            SerializationFormat::Bincode => {
+                // u64 index + raw leaf + u64 length prefix + raw siblings
+                8 + 32 + 8 + 32 * depth
+            }
+            SerializationFormat::Postcard => {
+                // varint index (< 2^depth) + raw leaf + varint length + raw siblings
+                varint_len(depth.max(1)) + 32 + varint_len_of(depth as u64) + 32 * depth
+            }
+            SerializationFormat::MessagePack => {
+                // Hashes are arrays of 32 ints, each up to 2 bytes
+                const HASH_MAX: usize = 3 + 32 * 2;
+                let index_max = match depth {
+                    0..=7 => 1,
+                    8 => 2,
+                    9..=16 => 3,
+                    17..=32 => 5,
+                    _ => 9,
+                };
+                let siblings_header = if depth <= 15 { 1 } else { 3 };
+                1 + index_max + HASH_MAX + siblings_header + HASH_MAX * depth
+            }
+        }
+    }
+}
+
+/// Merkle proof with its empty-subtree siblings elided, from `MerkleProof::compress`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CompactMerkleProof {
+    /// Index of the leaf this proof is for
+    pub leaf_index: usize,
+    /// Hash of the leaf
+    pub leaf: Hash,
+    /// Number of siblings in the full proof
+    depth: u32,
+    /// Bit `i` is set when the sibling at level `i` was the zero hash
+    zero_mask: u64,
+    /// The remaining siblings, in level order
+    siblings: Vec<Hash>,
+}
+
+impl CompactMerkleProof {
+    /// Number of siblings in the full, decompressed proof
+    pub fn full_depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    /// Number of zero siblings that were elided
+    pub fn omitted_count(&self) -> usize {
+        self.zero_mask.count_ones() as usize
+    }
+
+    /// The siblings that were kept, in level order
+    pub fn siblings(&self) -> &[Hash] {
+        &self.siblings
+    }
+
+    /// Rebuild the full proof with exactly `full_depth()` siblings, filling
+    /// elided levels from the same `zero_hashes` passed to `compress`
+    pub fn decompress(&self, zero_hashes: &[Hash]) -> IndexerResult<MerkleProof> {
+        let depth = self.full_depth();
+        let stored_mask = if depth >= u64::BITS as usize {
+            u64::MAX
+        } else {
+            (1u64 << depth) - 1
+        };
+        if depth > u64::BITS as usize
+            || self.zero_mask & !stored_mask != 0
+            || self.omitted_count() + self.siblings.len() != depth
+        {
+            return Err(IndexerError::InvalidData(format!(
+                "compact proof for leaf {} does not describe {depth} siblings",
+                self.leaf_index
+            )));
+        }
+
+        check_zero_hashes(zero_hashes, depth)?;
+        let mut stored = self.siblings.iter();
+        let siblings = (0..depth)
+            .map(|level| {
+                if self.zero_mask & (1 << level) != 0 {
+                    zero_hashes[level]
+                } else {
+                    *stored.next().expect("sibling count checked")
+                }
+            })
+            .collect();
+
+        Ok(MerkleProof::new(self.leaf_index, self.leaf, siblings))
+    }
+}
+
+/// Check that `zero_hashes` covers every level of a `depth`-sibling proof
+fn check_zero_hashes(zero_hashes: &[Hash], depth: usize) -> IndexerResult<()> {
+    if zero_hashes.len() < depth {
+        return Err(IndexerError::InvalidData(format!(
+            "{} zero hashes cannot cover a proof of depth {depth}",
+            zero_hashes.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Merkle proof in the Borsh layout read by the on-chain verifier
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OnchainProof {
+    /// Sibling hashes from leaf level up to root
+    pub siblings: Vec<[u8; 32]>,
+    /// Index of the proven leaf
+    pub leaf_index: u64,
+}
+
+/// Bytes needed for a LEB128 varint holding values below `2^bits`
+fn varint_len(bits: usize) -> usize {
+    bits.div_ceil(7)
+}
+
+/// Bytes needed for a LEB128 varint holding `value`
+fn varint_len_of(value: u64) -> usize {
+    varint_len((64 - value.leading_zeros() as usize).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshDeserialize;
+
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    #[test]
+    fn test_verify_against_rejects_wrong_leaf() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        tree.append(b"leaf0").unwrap();
+        tree.append(b"leaf1").unwrap();
+        let root = tree.root();
+
+        let proof = tree.prove(0).unwrap();
+        let expected = tree.get_leaf_hash(0).unwrap();
+        let other = tree.get_leaf_hash(1).unwrap();
+
+        assert!(proof.verify(&root));
+        assert!(proof.verify_against(&root, &expected));
+        assert!(!proof.verify_against(&root, &other));
+    }
+
+    #[test]
+    fn test_serialized_size_matches_encoding() {
+        use crate::serialization::SerializationFormat;
+        use crate::tree::MerkleProof;
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        tree.append(b"leaf").unwrap();
+        let proof = tree.prove(0).unwrap();
+        // Worst case for varint formats: the largest index at depth 20
+        let fields = ((1usize << 20) - 1, [0xffu8; 32], proof.siblings.clone());
+
+        let bincode_len = bincode::serialize(&fields).unwrap().len();
+        assert_eq!(
+            bincode_len,
+            MerkleProof::serialized_size(20, &SerializationFormat::Bincode)
+        );
+
+        let mut buffer = vec![0u8; 4096];
+        let postcard_len = postcard::to_slice(&fields, &mut buffer).unwrap().len();
+        assert_eq!(
+            postcard_len,
+            MerkleProof::serialized_size(20, &SerializationFormat::Postcard)
+        );
+
+        let msgpack_len = rmp_serde::to_vec(&fields).unwrap().len();
+        assert!(msgpack_len <= MerkleProof::serialized_size(20, &SerializationFormat::MessagePack));
+    }
+
+    #[test]
+    fn test_verify_any_finds_matching_root() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        tree.append(b"leaf0").unwrap();
+        let stale = tree.root();
+        tree.append(b"leaf1").unwrap();
+        let current = tree.root();
+
+        let proof = tree.prove(1).unwrap();
+        assert_eq!(proof.verify_any(&[stale, current, [0u8; 32]]), Some(1));
+        assert_eq!(proof.verify_any(&[stale, [0u8; 32]]), None);
+        assert_eq!(proof.verify_any(&[]), None);
+    }
+
+    #[test]
+    fn test_proof_json_round_trip() {
+        use crate::tree::MerkleProof;
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..5u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root = tree.root();
+        let proof = tree.prove(3).unwrap();
+
+        let json = proof.to_json().unwrap();
+        let decoded = MerkleProof::from_json(&json).unwrap();
+        assert_eq!(decoded.leaf_index, 3);
+        assert_eq!(decoded.leaf, proof.leaf);
+        assert_eq!(decoded.siblings, proof.siblings);
+        assert!(decoded.verify(&root));
+
+        assert!(MerkleProof::from_json("{\"leaf_index\":3}").is_err());
+    }
+
+    #[test]
+    fn test_proof_bincode_round_trip() {
+        use crate::serialization::SerializationFormat;
+        use crate::tree::MerkleProof;
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..5u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root = tree.root();
+        let proof = tree.prove(4).unwrap();
+
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(
+            bytes.len(),
+            MerkleProof::serialized_size(20, &SerializationFormat::Bincode)
+        );
+        let decoded = MerkleProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.leaf_index, 4);
+        assert_eq!(decoded.siblings, proof.siblings);
+        assert!(decoded.verify(&root));
+    }
+
+    #[test]
+    fn test_verify_batch_flags_tampered_proof() {
+        use crate::tree::MerkleProof;
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..8u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let root = tree.root();
+
+        let mut proofs = tree.prove_all();
+        assert!(MerkleProof::verify_batch(&proofs, &root));
+        assert!(MerkleProof::verify_batch_detailed(&proofs, &root)
+            .iter()
+            .all(|ok| *ok));
+
+        proofs[5].siblings[3][0] ^= 1;
+        assert!(!MerkleProof::verify_batch(&proofs, &root));
+        let detailed = MerkleProof::verify_batch_detailed(&proofs, &root);
+        let failed: Vec<usize> = detailed
+            .iter()
+            .enumerate()
+            .filter(|(_, ok)| !**ok)
+            .map(|(index, _)| proofs[index].leaf_index)
+            .collect();
+        assert_eq!(failed, vec![5]);
+
+        assert!(MerkleProof::verify_batch(&[], &root));
+    }
+
+    #[test]
+    fn test_slice_levels() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..40u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        let proof = tree.prove(13).unwrap();
+
+        let slice = proof.slice_levels(2, 5).unwrap();
+        assert_eq!(slice, proof.siblings[2..5].to_vec());
+        assert!(proof.slice_levels(7, 7).unwrap().is_empty());
+        assert_eq!(proof.slice_levels(0, 20).unwrap(), proof.siblings);
+
+        assert!(proof.slice_levels(5, 2).is_err());
+        assert!(proof.slice_levels(0, 21).is_err());
+    }
+
+    #[test]
+    fn test_compact_proof_omitted_count() {
+        let mut tree = IncrementalMerkleTree::new(20);
+        tree.append(b"only").unwrap();
+        let proof = tree.prove(0).unwrap();
+
+        let compact = proof.compress(tree.zero_hashes()).unwrap();
+        assert_eq!(compact.full_depth(), 20);
+        assert_eq!(compact.omitted_count(), 20);
+        assert_eq!(compact.omitted_count() + compact.siblings().len(), 20);
+
+        let restored = compact.decompress(tree.zero_hashes()).unwrap();
+        assert_eq!(restored.siblings, proof.siblings);
+        assert!(restored.verify(&tree.root()));
+
+        for i in 1..5u8 {
+            tree.append(&[i]).unwrap();
+        }
+        let proof = tree.prove(4).unwrap();
+        let compact = proof.compress(tree.zero_hashes()).unwrap();
+        assert_eq!(compact.siblings().len(), 1);
+        assert_eq!(compact.omitted_count() + compact.siblings().len(), 20);
+        assert_eq!(
+            compact.decompress(tree.zero_hashes()).unwrap().siblings,
+            proof.siblings
+        );
+        assert!(compact.decompress(&tree.zero_hashes()[..10]).is_err());
+    }
+
+    #[test]
+    fn test_compact_proof_uses_tree_zero_hashes() {
+        let separators = DomainSeparators::new(7, 9).unwrap();
+        let trees = [
+            IncrementalMerkleTree::new(12).with_hasher(HashAlgorithm::Sha256),
+            IncrementalMerkleTree::builder()
+                .depth(12)
+                .domain_separators(separators)
+                .build()
+                .unwrap(),
+        ];
+        for mut tree in trees {
+            tree.append(b"only").unwrap();
+            let proof = tree.prove(0).unwrap();
+
+            let compact = proof.compress(tree.zero_hashes()).unwrap();
+            assert_eq!(compact.omitted_count(), 12);
+            let restored = compact.decompress(tree.zero_hashes()).unwrap();
+            assert_eq!(restored.siblings, proof.siblings);
+
+            // Default Poseidon zero hashes neither elide nor rebuild this path
+            let poseidon = HashAlgorithm::Poseidon.zero_hashes(12);
+            assert_eq!(proof.compress(&poseidon).unwrap().omitted_count(), 0);
+            assert_ne!(
+                compact.decompress(&poseidon).unwrap().siblings,
+                proof.siblings
+            );
+        }
+    }
+
+    #[test]
+    fn test_onchain_proof_borsh_round_trip() {
+        use crate::tree::OnchainProof;
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        for i in 0..6u8 {
+            tree.append(&[i]).unwrap();
+        }
+        let proof = tree.prove(5).unwrap();
+
+        let onchain = proof.to_onchain();
+        let bytes = borsh::to_vec(&onchain).unwrap();
+        // u32 length prefix + siblings + u64 leaf index
+        assert_eq!(bytes.len(), 4 + 32 * 20 + 8);
+
+        let decoded = OnchainProof::try_from_slice(&bytes).unwrap();
+        assert_eq!(decoded, onchain);
+        assert_eq!(decoded.siblings, proof.siblings);
+        assert_eq!(decoded.leaf_index, 5);
+    }
 }
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::{internal, DomainSeparators, Hash, PoseidonHasher};
+
+/// Hash functions used to build and verify a Merkle tree
+pub trait MerkleHasher {
+    /// Hash raw leaf data
+    fn hash_leaf(data: &[u8]) -> Hash;
+
+    /// Hash two child nodes into their parent
+    fn hash_pair(left: &Hash, right: &Hash) -> Hash;
+}
+
+/// SHA-256 with distinct leaf (`0x00`) and node (`0x01`) prefixes
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    fn hash_leaf(data: &[u8]) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update([0x00]);
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+
+    fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update([0x01]);
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().into()
+    }
+}
+
+impl MerkleHasher for PoseidonHasher {
+    fn hash_leaf(data: &[u8]) -> Hash {
+        internal::hash_bytes(data)
+    }
+
+    fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+        internal::hash_pair(left, right)
+    }
+}
+
+/// Hash algorithm chosen when a tree is created and recorded with its data
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    /// Poseidon over BN254, matching on-chain verification
+    #[default]
+    Poseidon,
+    /// SHA-256
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Hash raw leaf data
+    pub fn hash_leaf(self, data: &[u8]) -> Hash {
+        match self {
+            HashAlgorithm::Poseidon => <PoseidonHasher as MerkleHasher>::hash_leaf(data),
+            HashAlgorithm::Sha256 => Sha256Hasher::hash_leaf(data),
+        }
+    }
+
+    /// Hash two child nodes into their parent
+    pub fn hash_pair(self, left: &Hash, right: &Hash) -> Hash {
+        match self {
+            HashAlgorithm::Poseidon => <PoseidonHasher as MerkleHasher>::hash_pair(left, right),
+            HashAlgorithm::Sha256 => Sha256Hasher::hash_pair(left, right),
+        }
+    }
+
+    /// Hash raw leaf data, tagging Poseidon hashes with `separators`.
+    /// SHA-256 has fixed prefixes and ignores them.
+    pub fn hash_leaf_with(self, data: &[u8], separators: &DomainSeparators) -> Hash {
+        match self {
+            HashAlgorithm::Poseidon => {
+                PoseidonHasher::hash_leaf_with(data, separators).expect("Poseidon hashing failed")
+            }
+            HashAlgorithm::Sha256 => Sha256Hasher::hash_leaf(data),
+        }
+    }
+
+    /// Hash two child nodes into their parent, tagging Poseidon hashes with
+    /// `separators`. SHA-256 has fixed prefixes and ignores them.
+    pub fn hash_pair_with(self, left: &Hash, right: &Hash, separators: &DomainSeparators) -> Hash {
+        match self {
+            HashAlgorithm::Poseidon => PoseidonHasher::hash_node_with(left, right, separators)
+                .expect("Poseidon hashing failed"),
+            HashAlgorithm::Sha256 => Sha256Hasher::hash_pair(left, right),
+        }
+    }
+
+    /// Empty-subtree hashes for levels `0..=depth`
+    pub fn zero_hashes(self, depth: usize) -> Vec<Hash> {
+        // Level 0: hash of single zero byte
+        self.zero_hashes_from(self.hash_leaf(&[0u8]), depth)
+    }
+
+    /// Empty-subtree hashes for levels `0..=depth` above a custom empty leaf
+    pub fn zero_hashes_from(self, zero_leaf: Hash, depth: usize) -> Vec<Hash> {
+        self.zero_hashes_with(zero_leaf, depth, &DomainSeparators::default())
+    }
+
+    /// Empty-subtree hashes for levels `0..=depth` above `zero_leaf`, with
+    /// Poseidon nodes tagged by `separators`
+    pub fn zero_hashes_with(
+        self,
+        zero_leaf: Hash,
+        depth: usize,
+        separators: &DomainSeparators,
+    ) -> Vec<Hash> {
+        let mut zero_hashes = Vec::with_capacity(depth + 1);
+        zero_hashes.push(zero_leaf);
+
+        // Higher levels: zero_{i+1} = hash_pair(zero_i, zero_i)
+        for i in 0..depth {
+            let next = self.hash_pair_with(&zero_hashes[i], &zero_hashes[i], separators);
+            zero_hashes.push(next);
+        }
+
+        zero_hashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    fn build(hasher: HashAlgorithm) -> IncrementalMerkleTree {
+        let mut tree = IncrementalMerkleTree::new(8).with_hasher(hasher);
+        for i in 0..13u32 {
+            tree.append(&i.to_le_bytes()).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn test_proofs_round_trip_under_each_hasher() {
+        for hasher in [HashAlgorithm::Poseidon, HashAlgorithm::Sha256] {
+            let tree = build(hasher);
+            let root = tree.root();
+            assert_eq!(tree.hasher(), hasher);
+            assert_eq!(
+                tree.prove_slow(5).unwrap().siblings,
+                tree.prove(5).unwrap().siblings
+            );
+            for index in 0..tree.len() {
+                assert!(tree.prove(index).unwrap().verify_with(&root, hasher));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mixing_hashers_fails_verification() {
+        let sha_tree = build(HashAlgorithm::Sha256);
+        let poseidon_tree = build(HashAlgorithm::Poseidon);
+        assert_ne!(sha_tree.root(), poseidon_tree.root());
+
+        let proof = sha_tree.prove(3).unwrap();
+        assert!(!proof.verify(&sha_tree.root()));
+        assert!(!proof.verify_with(&sha_tree.root(), HashAlgorithm::Poseidon));
+        assert!(!poseidon_tree
+            .prove(3)
+            .unwrap()
+            .verify_with(&poseidon_tree.root(), HashAlgorithm::Sha256));
+
+        let mut merged = IncrementalMerkleTree::new(8);
+        assert!(merged.extend_from(&sha_tree).is_err());
+    }
+
+    #[test]
+    fn test_sha256_separates_leaves_and_nodes() {
+        let left = Sha256Hasher::hash_leaf(b"a");
+        let right = Sha256Hasher::hash_leaf(b"b");
+        let mut concatenated = left.to_vec();
+        concatenated.extend_from_slice(&right);
+        assert_ne!(
+            Sha256Hasher::hash_pair(&left, &right),
+            Sha256Hasher::hash_leaf(&concatenated)
+        );
+    }
+}
@@ -1,6 +1,85 @@
+pub mod hasher;
 pub mod poseidon_hash;
+pub mod timing;
 
-pub use poseidon_hash::{internal, PoseidonHasher};
+pub use hasher::{HashAlgorithm, MerkleHasher, Sha256Hasher};
+pub use poseidon_hash::{internal, DomainSeparators, PoseidonHasher};
 
 // Re-export the Hash type for convenience
 pub type Hash = [u8; 32];
+
+/// Find the first leaf index at which two leaf lists differ.
+///
+/// Positions past the end of the shorter list are compared as `zero_leaf`,
+/// the same padding the trees use when computing a root, so `None` means
+/// both lists produce the same root at any depth. Pass the tree's empty
+/// leaf, e.g. `tree.zero_hash(0)`, since it depends on the tree's hash
+/// algorithm and configuration. Use this for reconciliation diagnostics
+/// once `IncrementalMerkleTree::root` reports a mismatch, to locate the
+/// first leaf that needs attention.
+pub fn first_divergence(a: &[Hash], b: &[Hash], zero_leaf: Hash) -> Option<usize> {
+    (0..a.len().max(b.len()))
+        .find(|&i| a.get(i).unwrap_or(&zero_leaf) != b.get(i).unwrap_or(&zero_leaf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| internal::hash_bytes(&[i + 1])).collect()
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        let zero_leaf = HashAlgorithm::Poseidon.hash_leaf(&[0u8]);
+        let a = leaves(8);
+        let mut b = a.clone();
+        assert_eq!(first_divergence(&a, &b, zero_leaf), None);
+
+        b[4] = internal::hash_bytes(b"other");
+        assert_eq!(first_divergence(&a, &b, zero_leaf), Some(4));
+
+        b.truncate(6);
+        assert_eq!(first_divergence(&a[..6], &b, zero_leaf), Some(4));
+        assert_eq!(first_divergence(&a, &a[..6], zero_leaf), Some(6));
+    }
+
+    #[test]
+    fn test_first_divergence_matches_root_equality() {
+        for hasher in [HashAlgorithm::Poseidon, HashAlgorithm::Sha256] {
+            let new_tree = || {
+                IncrementalMerkleTree::builder()
+                    .depth(4)
+                    .hasher(hasher)
+                    .build()
+                    .unwrap()
+            };
+            let zero_leaf = new_tree().zero_hash(0).unwrap();
+
+            let a = leaves(3);
+            let mut b = a.clone();
+            b.push(zero_leaf);
+            assert_eq!(first_divergence(&a, &b, zero_leaf), None);
+
+            let root_of = |leaves: &[Hash]| {
+                let mut tree = new_tree();
+                for (i, leaf) in leaves.iter().enumerate() {
+                    tree.set_leaf_hash(i, *leaf).unwrap();
+                }
+                tree.root()
+            };
+            assert_eq!(root_of(&a), root_of(&b));
+
+            // Padding with another algorithm's empty leaf changes the root
+            let other = match hasher {
+                HashAlgorithm::Poseidon => HashAlgorithm::Sha256,
+                HashAlgorithm::Sha256 => HashAlgorithm::Poseidon,
+            };
+            b[3] = other.hash_leaf(&[0u8]);
+            assert_eq!(first_divergence(&a, &b, zero_leaf), Some(3));
+            assert_ne!(root_of(&a), root_of(&b));
+        }
+    }
+}
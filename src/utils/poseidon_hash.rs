@@ -1,56 +1,134 @@
+use crate::errors::{IndexerError, IndexerResult};
 use crate::utils::Hash;
 use solana_poseidon::{hashv, Endianness, Parameters, PoseidonSyscallError};
 
+/// Domain tags mixed into leaf and internal-node hashes.
+///
+/// Each tag is hashed as an extra leading field element, so a leaf hash can
+/// never equal an internal node hash even when their inputs coincide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DomainSeparators {
+    leaf: u64,
+    node: u64,
+}
+
+impl DomainSeparators {
+    /// Create separators with custom tags, which must differ
+    pub fn new(leaf: u64, node: u64) -> IndexerResult<Self> {
+        if leaf == node {
+            return Err(IndexerError::InvalidData(
+                "leaf and node domain tags must differ".to_string(),
+            ));
+        }
+        Ok(Self { leaf, node })
+    }
+
+    /// Tag used for leaf hashes
+    pub fn leaf(&self) -> u64 {
+        self.leaf
+    }
+
+    /// Tag used for internal node hashes
+    pub fn node(&self) -> u64 {
+        self.node
+    }
+}
+
+impl Default for DomainSeparators {
+    fn default() -> Self {
+        Self { leaf: 1, node: 2 }
+    }
+}
+
+/// Bytes absorbed per field element; 31 bytes always fit below the BN254 modulus
+const CHUNK_SIZE: usize = 31;
+
+/// Chunk elements absorbed per permutation, leaving room for the tag and state
+/// within the 12-input limit
+const CHUNKS_PER_CALL: usize = 10;
+
 /// Poseidon hashing utilities for both sparse and incremental merkle trees
 pub struct PoseidonHasher;
 
 impl PoseidonHasher {
-    /// Hash arbitrary bytes using Poseidon
+    /// Hash arbitrary bytes using Poseidon, as a leaf
     pub fn hash_bytes(input: &[u8]) -> Result<Hash, PoseidonSyscallError> {
-        // For Poseidon, we need to ensure the input is within the field modulus
-        // We'll truncate the input to fit within the field bounds
-        let mut field_input = [0u8; 31]; // Use 31 bytes to ensure we're within field bounds
-        let copy_len = std::cmp::min(input.len(), 31);
-        field_input[..copy_len].copy_from_slice(&input[..copy_len]);
-
-        // Add a domain separator to avoid collisions
-        field_input[0] = field_input[0].wrapping_add(0x01);
-
-        let result = hashv(Parameters::Bn254X5, Endianness::BigEndian, &[&field_input])?;
+        Self::hash_leaf_with(input, &DomainSeparators::default())
+    }
 
-        Ok(result.to_bytes())
+    /// Hash arbitrary bytes as a leaf under the given domain separators.
+    ///
+    /// The whole input is absorbed sponge-style: it is split into 31-byte
+    /// field elements and folded in groups into a state seeded with the
+    /// input length, so inputs of any size hash without truncation.
+    pub fn hash_leaf_with(
+        input: &[u8],
+        separators: &DomainSeparators,
+    ) -> Result<Hash, PoseidonSyscallError> {
+        let chunks: Vec<&[u8]> = input.chunks(CHUNK_SIZE).collect();
+
+        let mut state = [0u8; 32];
+        state[24..].copy_from_slice(&(input.len() as u64).to_be_bytes());
+
+        if chunks.is_empty() {
+            return Self::hash_tagged(separators.leaf, &[&state]);
+        }
+        for group in chunks.chunks(CHUNKS_PER_CALL) {
+            let mut elements = Vec::with_capacity(group.len() + 1);
+            elements.push(&state[..]);
+            elements.extend_from_slice(group);
+            state = Self::hash_tagged(separators.leaf, &elements)?;
+        }
+        Ok(state)
     }
 
-    /// Hash a pair of hashes together using Poseidon
+    /// Hash a pair of hashes together using Poseidon, as an internal node
     pub fn hash_pair(a: &Hash, b: &Hash) -> Result<Hash, PoseidonSyscallError> {
-        // For Poseidon, we need to ensure inputs are within the field modulus
-        // We'll hash each 32-byte input separately and then combine them
-        let hash_a = Self::hash_bytes(a)?;
-        let hash_b = Self::hash_bytes(b)?;
-
-        // Now combine the two hashed results
-        let result = hashv(
-            Parameters::Bn254X5,
-            Endianness::BigEndian,
-            &[&hash_a, &hash_b],
-        )?;
+        Self::hash_node_with(a, b, &DomainSeparators::default())
+    }
 
-        Ok(result.to_bytes())
+    /// Hash two children into an internal node under the given domain separators.
+    ///
+    /// 32-byte children are absorbed in full as two 16-byte halves each, in a
+    /// single permutation. Other inputs are first hashed as leaves.
+    pub fn hash_node_with(
+        a: &[u8],
+        b: &[u8],
+        separators: &DomainSeparators,
+    ) -> Result<Hash, PoseidonSyscallError> {
+        let (a, b) = if a.len() == 32 && b.len() == 32 {
+            (Self::to_hash(a), Self::to_hash(b))
+        } else {
+            (
+                Self::hash_leaf_with(a, separators)?,
+                Self::hash_leaf_with(b, separators)?,
+            )
+        };
+
+        Self::hash_tagged(separators.node, &[&a[..16], &a[16..], &b[..16], &b[16..]])
+    }
+
+    fn to_hash(bytes: &[u8]) -> Hash {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(bytes);
+        hash
     }
 
     /// Hash two arbitrary byte slices using Poseidon
     pub fn hash_two_slices(a: &[u8], b: &[u8]) -> Result<Hash, PoseidonSyscallError> {
-        // Hash each slice separately first
-        let hash_a = Self::hash_bytes(a)?;
-        let hash_b = Self::hash_bytes(b)?;
+        Self::hash_node_with(a, b, &DomainSeparators::default())
+    }
 
-        // Then combine the two hashed results
-        let result = hashv(
-            Parameters::Bn254X5,
-            Endianness::BigEndian,
-            &[&hash_a, &hash_b],
-        )?;
+    /// Hash field elements prefixed with a domain tag element
+    fn hash_tagged(tag: u64, inputs: &[&[u8]]) -> Result<Hash, PoseidonSyscallError> {
+        let mut tag_element = [0u8; 32];
+        tag_element[24..].copy_from_slice(&tag.to_be_bytes());
 
+        let mut elements = Vec::with_capacity(inputs.len() + 1);
+        elements.push(&tag_element[..]);
+        elements.extend_from_slice(inputs);
+
+        let result = hashv(Parameters::Bn254X5, Endianness::BigEndian, &elements)?;
         Ok(result.to_bytes())
     }
 
@@ -84,3 +162,75 @@ pub mod internal {
         PoseidonHasher::hash_slice(input).expect("Poseidon hashing failed")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::HashAlgorithm;
+
+    #[test]
+    fn test_leaf_and_node_hashes_are_separated() {
+        let left = internal::hash_bytes(b"left");
+        let right = internal::hash_bytes(b"right");
+        // Leaf data that is exactly the two children of a node
+        let children = [left, right].concat();
+
+        let poseidon = HashAlgorithm::Poseidon;
+        assert_ne!(
+            poseidon.hash_leaf(&children),
+            poseidon.hash_pair(&left, &right)
+        );
+
+        let custom = DomainSeparators::new(7, 8).unwrap();
+        assert_ne!(
+            PoseidonHasher::hash_leaf_with(&children, &custom).unwrap(),
+            PoseidonHasher::hash_node_with(&left, &right, &custom).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_bytes_absorbs_full_input() {
+        // Differ only past byte 31
+        let mut a = vec![7u8; 40];
+        let mut b = a.clone();
+        b[35] = 8;
+        assert_ne!(internal::hash_bytes(&a), internal::hash_bytes(&b));
+
+        // Differ only in the last byte of a long, multi-permutation input
+        a = vec![1u8; 1000];
+        b = a.clone();
+        b[999] = 2;
+        assert_ne!(internal::hash_bytes(&a), internal::hash_bytes(&b));
+
+        // Trailing zeros are not padding
+        assert_ne!(internal::hash_bytes(b"ab"), internal::hash_bytes(b"ab\0"));
+        assert_ne!(internal::hash_bytes(&[]), internal::hash_bytes(&[0]));
+    }
+
+    #[test]
+    fn test_hash_pair_uses_full_hashes() {
+        assert_ne!(
+            internal::hash_pair(&[1u8; 32], &[2u8; 32]),
+            internal::hash_pair(&[2u8; 32], &[1u8; 32])
+        );
+
+        // Children differing only in their final byte
+        let mut c = [3u8; 32];
+        let left = internal::hash_pair(&c, &[4u8; 32]);
+        c[31] = 9;
+        assert_ne!(left, internal::hash_pair(&c, &[4u8; 32]));
+
+        // Values above the field modulus are accepted
+        internal::hash_pair(&[0xff; 32], &[0xff; 32]);
+    }
+
+    #[test]
+    fn test_custom_domain_separators() {
+        assert!(DomainSeparators::new(7, 7).is_err());
+
+        let custom = DomainSeparators::new(7, 8).unwrap();
+        let default_leaf = internal::hash_bytes(b"leaf");
+        let custom_leaf = PoseidonHasher::hash_leaf_with(b"leaf", &custom).unwrap();
+        assert_ne!(default_leaf, custom_leaf);
+    }
+}
@@ -0,0 +1,106 @@
+//! Latency histograms for tree operations, recorded through the `metrics`
+//! crate when the `metrics` feature is enabled. Without the feature the
+//! timer is a zero-sized no-op.
+
+/// Histogram of `IncrementalMerkleTree::append` durations, in seconds
+pub const APPEND_SECONDS: &str = "imt_append_seconds";
+/// Histogram of `IncrementalMerkleTree::prove` durations, in seconds
+pub const PROVE_SECONDS: &str = "imt_prove_seconds";
+/// Histogram of `IncrementalMerkleTree::root` durations, in seconds
+pub const ROOT_SECONDS: &str = "imt_root_seconds";
+
+/// Records the time until it is dropped into a histogram
+pub(crate) struct OpTimer {
+    #[cfg(feature = "metrics")]
+    name: &'static str,
+    #[cfg(feature = "metrics")]
+    start: std::time::Instant,
+}
+
+impl OpTimer {
+    /// Start timing an operation reported under `name`
+    #[inline(always)]
+    pub(crate) fn start(_name: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            name: _name,
+            #[cfg(feature = "metrics")]
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        metrics::histogram!(self.name).record(self.start.elapsed().as_secs_f64());
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use crate::tree::IncrementalMerkleTree;
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString,
+        Unit,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Collects the name of every histogram sample
+    #[derive(Default)]
+    struct SampleRecorder {
+        samples: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Sample {
+        name: String,
+        samples: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl HistogramFn for Sample {
+        fn record(&self, _: f64) {
+            self.samples.lock().unwrap().push(self.name.clone());
+        }
+    }
+
+    impl Recorder for SampleRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::noop()
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(Arc::new(Sample {
+                name: key.name().to_string(),
+                samples: self.samples.clone(),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_timings_emitted_per_operation() {
+        let recorder = SampleRecorder::default();
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut tree = IncrementalMerkleTree::new(8);
+            tree.append(b"a").unwrap();
+            tree.append(b"b").unwrap();
+            tree.prove(1).unwrap();
+            tree.root();
+        });
+
+        let samples = recorder.samples.lock().unwrap();
+        let count = |name: &str| samples.iter().filter(|s| *s == name).count();
+        assert_eq!(count(APPEND_SECONDS), 2);
+        assert_eq!(count(PROVE_SECONDS), 1);
+        assert!(count(ROOT_SECONDS) >= 1);
+    }
+}
@@ -1,6 +1,5 @@
 use umbra_indexer::{
-    serialization::{SerializationFormat, SerializationOptions},
-    Commitment, IncrementalMerkleTree, PersistentMerkleTree, StorageConfig,
+    Commitment, HashAlgorithm, IncrementalMerkleTree, PersistentMerkleTree, StorageConfig,
 };
 use tempfile::TempDir;
 
@@ -186,3 +185,694 @@ fn test_zero_hashes() {
     // Beyond max level should return None
     assert!(tree.zero_hash(tree.depth() + 1).is_none());
 }
+
+#[test]
+fn test_persistent_tree_without_mmap() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path())
+        .with_cache_size(64 * 1024)
+        .use_mmap(false);
+
+    let original_root;
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        tree.append(b"buffered1").unwrap();
+        tree.append(b"buffered2").unwrap();
+        original_root = tree.root();
+        tree.sync().unwrap();
+    }
+
+    // Without mmap the data file is only as large as the pages written
+    let data_len = std::fs::metadata(temp_dir.path().join("leaves.dat"))
+        .unwrap()
+        .len();
+    assert!(data_len < 1024 * 1024);
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.root(), original_root);
+    let proof = tree.prove(1).unwrap();
+    assert!(proof.verify(&original_root));
+}
+
+#[test]
+fn test_persistent_tree_max_leaf_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_max_leaf_size(64);
+    let mut tree = PersistentMerkleTree::new(config).unwrap();
+
+    assert!(tree.append(&[1u8; 100]).is_err());
+    assert_eq!(tree.len(), 0);
+    assert_eq!(tree.append(&[1u8; 32]).unwrap(), 0);
+}
+
+#[test]
+fn test_persistent_tree_fragmentation() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).use_mmap(false);
+    let mut tree = PersistentMerkleTree::new(config).unwrap();
+
+    assert_eq!(tree.fragmentation().unwrap(), 0.0);
+
+    // A handful of leaves only partially fills the first page
+    for i in 0..3u8 {
+        tree.append(&[i]).unwrap();
+    }
+    tree.sync().unwrap();
+
+    let ratio = tree.fragmentation().unwrap();
+    assert!(ratio > 0.0 && ratio < 1.0);
+}
+
+#[test]
+fn test_persistent_tree_recovers_wal_on_open() {
+    use umbra_indexer::storage::{WalEntry, WriteAheadLog};
+    use umbra_indexer::utils::internal::hash_bytes;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_wal(true);
+
+    // A clean shutdown leaves nothing to recover
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        tree.append(b"synced").unwrap();
+        tree.sync().unwrap();
+    }
+    {
+        let tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        assert!(!tree.was_recovered());
+        assert_eq!(tree.recovered_entry_count(), 0);
+    }
+
+    // Plant entries that never made it past the WAL
+    let planted = [hash_bytes(b"lost1"), hash_bytes(b"lost2")];
+    {
+        let wal_file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(temp_dir.path().join("wal.log"))
+            .unwrap();
+        let wal = WriteAheadLog::new(wal_file);
+        wal.write_entry(&WalEntry::new(1, planted[0])).unwrap();
+        wal.write_entry(&WalEntry::new(2, planted[1])).unwrap();
+    }
+
+    let tree = PersistentMerkleTree::new(config.clone()).unwrap();
+    assert!(tree.was_recovered());
+    assert_eq!(tree.recovered_entry_count(), 2);
+    assert_eq!(tree.len(), 3);
+    assert_eq!(tree.prove(2).unwrap().leaf, planted[1]);
+    drop(tree);
+
+    // Recovery truncates the WAL, so a second open has nothing to replay
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert!(!tree.was_recovered());
+    assert_eq!(tree.len(), 3);
+}
+
+#[test]
+fn test_persistent_tree_recovery_stops_at_torn_entry() {
+    use std::io::Write;
+    use umbra_indexer::storage::{WalEntry, WriteAheadLog};
+    use umbra_indexer::utils::internal::hash_bytes;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_wal(true);
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        tree.append(b"synced").unwrap();
+        tree.sync().unwrap();
+    }
+
+    let planted = [hash_bytes(b"lost1"), hash_bytes(b"lost2")];
+    let wal_path = temp_dir.path().join("wal.log");
+    {
+        let wal_file = std::fs::OpenOptions::new().append(true).open(&wal_path).unwrap();
+        let wal = WriteAheadLog::new(wal_file);
+        wal.write_entry(&WalEntry::new(1, planted[0])).unwrap();
+        wal.write_entry(&WalEntry::new(2, planted[1])).unwrap();
+
+        // A crash mid-write leaves a length prefix with a short body
+        let mut file = std::fs::OpenOptions::new().append(true).open(&wal_path).unwrap();
+        file.write_all(&52u32.to_le_bytes()).unwrap();
+        file.write_all(&[0xab; 10]).unwrap();
+    }
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(tree.recovered_entry_count(), 2);
+    assert_eq!(tree.len(), 3);
+
+    let mut expected = IncrementalMerkleTree::new(20);
+    expected.append(b"synced").unwrap();
+    expected.set_leaf_hash(1, planted[0]).unwrap();
+    expected.set_leaf_hash(2, planted[1]).unwrap();
+    assert_eq!(tree.root(), expected.root());
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+}
+
+#[test]
+fn test_persistent_tree_open_read_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path());
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..5 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        tree.root()
+    };
+    let metadata_before = std::fs::read(temp_dir.path().join("metadata.dat")).unwrap();
+
+    let tree = PersistentMerkleTree::open_read_only(config).unwrap();
+    assert_eq!(tree.len(), 5);
+    assert_eq!(tree.root(), expected_root);
+
+    let proof = tree.prove(3).unwrap();
+    assert!(proof.verify(&expected_root));
+    assert_eq!(tree.leaf_hash(3), Some(proof.leaf));
+    assert_eq!(tree.leaf_hash(5), None);
+
+    let metadata_after = std::fs::read(temp_dir.path().join("metadata.dat")).unwrap();
+    assert_eq!(metadata_before, metadata_after);
+}
+
+#[test]
+fn test_persistent_tree_peek_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path());
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config).unwrap();
+        for i in 0..4 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        tree.root()
+    };
+
+    let (len, root) = PersistentMerkleTree::peek_metadata(temp_dir.path()).unwrap();
+    assert_eq!(len, 4);
+    assert_eq!(root, expected_root);
+
+    let missing = TempDir::new().unwrap();
+    assert!(PersistentMerkleTree::peek_metadata(missing.path()).is_err());
+}
+
+#[test]
+fn test_persistent_tree_packed_leaves() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path())
+        .use_mmap(false)
+        .with_pack_leaves(true);
+
+    let (expected_root, expected_leaf) = {
+        let mut tree = PersistentMerkleTree::new(config).unwrap();
+        for i in 0..127 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        (tree.root(), tree.prove(100).unwrap().leaf)
+    };
+
+    // 127 packed leaves and the page checksum fill exactly one 4KB page;
+    // flagged leaves fit only 124
+    let data_len = std::fs::metadata(temp_dir.path().join("leaves.dat"))
+        .unwrap()
+        .len();
+    assert_eq!(data_len, 4096);
+
+    // The layout comes from metadata, not from the config used to reopen
+    let reopened =
+        PersistentMerkleTree::new(StorageConfig::new(temp_dir.path()).use_mmap(false)).unwrap();
+    assert_eq!(reopened.len(), 127);
+    assert_eq!(reopened.root(), expected_root);
+    let proof = reopened.prove(100).unwrap();
+    assert_eq!(proof.leaf, expected_leaf);
+    assert!(proof.verify(&expected_root));
+}
+
+#[test]
+fn test_persistent_tree_bulk_load() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).use_mmap(false);
+    let leaves: Vec<Vec<u8>> = (0..500).map(|i: u32| i.to_le_bytes().to_vec()).collect();
+
+    let mut expected = IncrementalMerkleTree::new(20);
+    expected.append(b"existing").unwrap();
+    for leaf in &leaves {
+        expected.append(leaf).unwrap();
+    }
+
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        tree.append(b"existing").unwrap();
+        let writes_before = tree.io_stats().data_writes;
+
+        // 500 leaves span five pages but go out in a single write
+        assert_eq!(tree.bulk_load(&leaves).unwrap(), 1);
+        assert_eq!(tree.io_stats().data_writes, writes_before + 1);
+        assert_eq!(tree.len(), 501);
+        assert_eq!(tree.root(), expected.root());
+        tree.sync().unwrap();
+    }
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(tree.len(), 501);
+    assert_eq!(tree.root(), expected.root());
+    assert_eq!(tree.prove(0).unwrap().leaf, expected.get_leaf_hash(0).unwrap());
+    assert_eq!(tree.prove(400).unwrap().leaf, expected.get_leaf_hash(400).unwrap());
+}
+
+#[test]
+fn test_persistent_tree_leaves_digest() {
+    let dir_a = TempDir::new().unwrap();
+    let dir_b = TempDir::new().unwrap();
+    let mut a = PersistentMerkleTree::new(StorageConfig::new(dir_a.path())).unwrap();
+    let mut b = PersistentMerkleTree::new(StorageConfig::new(dir_b.path())).unwrap();
+
+    for i in 0..5 {
+        let leaf = format!("leaf{i}");
+        a.append(leaf.as_bytes()).unwrap();
+        b.append(leaf.as_bytes()).unwrap();
+    }
+    assert_eq!(a.leaves_digest().unwrap(), b.leaves_digest().unwrap());
+
+    b.update(3, b"changed").unwrap();
+    assert_ne!(a.leaves_digest().unwrap(), b.leaves_digest().unwrap());
+}
+
+#[test]
+fn test_persistent_tree_sync_on_drop() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path())
+        .with_wal(true)
+        .with_sync_on_drop(true);
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..3 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.root()
+        // Dropped here without an explicit sync
+    };
+
+    // The drop-time sync truncated the WAL, so nothing needs recovering
+    let wal_len = std::fs::metadata(temp_dir.path().join("wal.log"))
+        .unwrap()
+        .len();
+    assert_eq!(wal_len, 0);
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert!(!tree.was_recovered());
+    assert_eq!(tree.len(), 3);
+    assert_eq!(tree.root(), expected_root);
+}
+
+#[test]
+fn test_persistent_tree_sha256_reopen() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_hash_algorithm(HashAlgorithm::Sha256);
+
+    let (expected_root, proof) = {
+        let mut tree = PersistentMerkleTree::new(config).unwrap();
+        for i in 0..4 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        (tree.root(), tree.prove(2).unwrap())
+    };
+    assert!(proof.verify_with(&expected_root, HashAlgorithm::Sha256));
+    assert!(!proof.verify(&expected_root));
+
+    // The recorded algorithm wins over the default in a fresh config
+    let tree = PersistentMerkleTree::new(StorageConfig::new(temp_dir.path())).unwrap();
+    assert_eq!(tree.hash_algorithm(), HashAlgorithm::Sha256);
+    assert_eq!(tree.root(), expected_root);
+}
+
+#[test]
+fn test_persistent_tree_configured_depth() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_depth(16);
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..5 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        tree.root()
+    };
+
+    let mut reference = IncrementalMerkleTree::new(16);
+    for i in 0..5 {
+        reference.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+    assert_eq!(expected_root, reference.root());
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(tree.len(), 5);
+    assert_eq!(tree.root(), expected_root);
+    drop(tree);
+
+    // Reopening at another depth would silently change every root
+    let mismatched = StorageConfig::new(temp_dir.path()).with_depth(24);
+    assert!(matches!(
+        PersistentMerkleTree::new(mismatched),
+        Err(umbra_indexer::IndexerError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn test_persistent_tree_checkpoint_wal() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_wal(true);
+    let wal_path = temp_dir.path().join("wal.log");
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..4 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        assert!(std::fs::metadata(&wal_path).unwrap().len() > 0);
+
+        tree.checkpoint_wal().unwrap();
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+        tree.root()
+    };
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert!(!tree.was_recovered());
+    assert_eq!(tree.len(), 4);
+    assert_eq!(tree.root(), expected_root);
+}
+
+#[test]
+fn test_persistent_tree_sync_at_each_durability_level() {
+    use umbra_indexer::storage::DurabilityLevel;
+
+    for durability in [DurabilityLevel::None, DurabilityLevel::Flush, DurabilityLevel::Fsync] {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig::new(temp_dir.path())
+            .with_wal(true)
+            .with_durability(durability);
+
+        let expected_root = {
+            let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+            for i in 0..10 {
+                tree.append(format!("leaf{i}").as_bytes()).unwrap();
+            }
+            tree.sync().unwrap();
+            tree.root()
+        };
+
+        // Only a sync that wrote the data back may truncate the WAL
+        let wal_len = std::fs::metadata(temp_dir.path().join("wal.log"))
+            .unwrap()
+            .len();
+        assert_eq!(
+            wal_len == 0,
+            durability != DurabilityLevel::None,
+            "{durability:?}"
+        );
+
+        let tree = PersistentMerkleTree::new(config).unwrap();
+        assert_eq!(tree.len(), 10, "{durability:?}");
+        assert_eq!(tree.root(), expected_root, "{durability:?}");
+    }
+}
+
+#[test]
+fn test_persistent_tree_recovery_truncates_wal_without_durability() {
+    use umbra_indexer::storage::DurabilityLevel;
+
+    let temp_dir = TempDir::new().unwrap();
+    let wal_path = temp_dir.path().join("wal.log");
+    let config = StorageConfig::new(temp_dir.path())
+        .with_wal(true)
+        .with_durability(DurabilityLevel::None);
+
+    let expected_root = {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..5 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+        tree.root()
+    };
+    assert!(std::fs::metadata(&wal_path).unwrap().len() > 0);
+
+    {
+        let tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        assert!(tree.was_recovered());
+        assert_eq!(tree.root(), expected_root);
+    }
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+
+    let tree = PersistentMerkleTree::new(config).unwrap();
+    assert!(!tree.was_recovered());
+    assert_eq!(tree.len(), 5);
+    assert_eq!(tree.root(), expected_root);
+}
+
+#[test]
+fn test_persistent_tree_leaf_reads_stored_hash() {
+    use umbra_indexer::utils::internal::hash_bytes;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path());
+    let mut tree = PersistentMerkleTree::new(config).unwrap();
+
+    let inputs: [&[u8]; 3] = [b"alpha", b"beta", b"gamma"];
+    for input in inputs {
+        tree.append(input).unwrap();
+    }
+
+    for (index, input) in inputs.iter().enumerate() {
+        assert_eq!(tree.leaf(index).unwrap(), Some(hash_bytes(input)));
+    }
+    assert!(matches!(
+        tree.leaf(3),
+        Err(umbra_indexer::IndexerError::IndexOutOfBounds)
+    ));
+}
+
+#[test]
+fn test_persistent_tree_proofs_match_configured_depth() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_depth(20);
+    let mut tree = PersistentMerkleTree::new(config).unwrap();
+
+    for count in [1usize, 2, 3, 17, 64] {
+        while tree.len() < count {
+            tree.append(format!("leaf{}", tree.len()).as_bytes()).unwrap();
+        }
+        let root = tree.root();
+        for index in 0..count {
+            let proof = tree.prove(index).unwrap();
+            assert_eq!(proof.siblings.len(), 20);
+            assert!(proof.verify(&root));
+        }
+    }
+}
+
+#[test]
+fn test_persistent_tree_cache_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).use_mmap(false);
+    let mut tree = PersistentMerkleTree::new(config).unwrap();
+    for i in 0..4 {
+        tree.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+
+    let before = tree.cache_stats();
+    for index in 0..4 {
+        tree.leaf(index).unwrap();
+    }
+    let after = tree.cache_stats();
+
+    // Every leaf shares the first page, so at most the first read misses
+    assert_eq!(after.hits + after.misses, before.hits + before.misses + 4);
+    assert!(after.hits >= before.hits + 3);
+    assert!(after.hit_ratio > 0.0);
+}
+
+#[test]
+fn test_persistent_tree_background_sync() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path())
+        .with_wal(true)
+        .with_sync_interval(Duration::from_millis(20))
+        .with_background_sync(true);
+    let wal_path = temp_dir.path().join("wal.log");
+
+    let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+    assert!(tree.has_background_sync());
+    for i in 0..5 {
+        tree.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+    let expected_root = tree.root();
+    assert!(std::fs::metadata(&wal_path).unwrap().len() > 0);
+
+    // The flusher syncs without being asked, which empties the WAL
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while std::fs::metadata(&wal_path).unwrap().len() > 0 {
+        assert!(Instant::now() < deadline, "background sync never ran");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    tree.shutdown().unwrap();
+    assert!(!tree.has_background_sync());
+    drop(tree);
+
+    let reopened = PersistentMerkleTree::new(config.with_background_sync(false)).unwrap();
+    assert!(!reopened.was_recovered());
+    assert_eq!(reopened.len(), 5);
+    assert_eq!(reopened.root(), expected_root);
+}
+
+#[test]
+fn test_persistent_tree_detects_corrupted_page() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path())
+        .use_mmap(false)
+        .with_wal(false);
+
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..3 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+    }
+
+    // Reopening an intact tree verifies every page it reads
+    assert_eq!(PersistentMerkleTree::new(config.clone()).unwrap().len(), 3);
+
+    let data_path = temp_dir.path().join("leaves.dat");
+    let mut data = std::fs::read(&data_path).unwrap();
+    data[40] ^= 0xff;
+    std::fs::write(&data_path, data).unwrap();
+
+    assert!(matches!(
+        PersistentMerkleTree::new(config),
+        Err(umbra_indexer::IndexerError::ChecksumError)
+    ));
+}
+
+#[test]
+fn test_persistent_tree_apply_delta_from_leader() {
+    use umbra_indexer::serialization::TreeDelta;
+
+    let mut leader = IncrementalMerkleTree::new(20);
+    for i in 0..5 {
+        leader.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_wal(true);
+    let mut follower = PersistentMerkleTree::new(config.clone()).unwrap();
+    for i in 0..5 {
+        follower.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+    assert_eq!(follower.root(), leader.root());
+
+    let base = leader.clone();
+    leader.update(1, b"changed").unwrap();
+    for i in 5..9 {
+        leader.append(format!("leaf{i}").as_bytes()).unwrap();
+    }
+    let delta = TreeDelta::between(&base, &leader).unwrap();
+
+    follower.apply_delta(&delta).unwrap();
+    assert_eq!(follower.len(), 9);
+    assert_eq!(follower.root(), leader.root());
+
+    // The base no longer matches, so the same delta is rejected
+    assert!(matches!(
+        follower.apply_delta(&delta),
+        Err(umbra_indexer::IndexerError::InvalidData(_))
+    ));
+    assert_eq!(follower.root(), leader.root());
+
+    follower.sync().unwrap();
+    drop(follower);
+    let reopened = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(reopened.len(), 9);
+    assert_eq!(reopened.root(), leader.root());
+}
+
+#[test]
+fn test_persistent_tree_root_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path()).with_root_history_size(8);
+
+    let mut roots = Vec::new();
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..20 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+            roots.push(tree.root());
+        }
+        tree.update(3, b"changed").unwrap();
+        roots.push(tree.root());
+
+        assert_eq!(tree.known_roots(), roots[roots.len() - 8..].to_vec());
+        for root in &roots[roots.len() - 8..] {
+            assert!(tree.is_known_root(root));
+        }
+        assert!(!tree.is_known_root(&roots[roots.len() - 9]));
+        tree.sync().unwrap();
+    }
+
+    // The history is persisted with the tree
+    let reopened = PersistentMerkleTree::new(config).unwrap();
+    assert_eq!(reopened.known_roots(), roots[roots.len() - 8..].to_vec());
+    assert!(!reopened.is_known_root(&roots[0]));
+}
+
+#[test]
+fn test_persistent_tree_rejects_mismatched_hashing() {
+    use umbra_indexer::storage::{TreeMetadata, PAGE_CHECKSUM_VERSION};
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::new(temp_dir.path());
+    {
+        let mut tree = PersistentMerkleTree::new(config.clone()).unwrap();
+        for i in 0..3 {
+            tree.append(format!("leaf{i}").as_bytes()).unwrap();
+        }
+        tree.sync().unwrap();
+    }
+
+    let metadata_path = temp_dir.path().join("metadata.dat");
+    let original = std::fs::read(&metadata_path).unwrap();
+    let metadata = TreeMetadata::from_bytes(&original).unwrap();
+
+    // A Poseidon tree from before domain tags hashes differently
+    let mut untagged = metadata.clone();
+    untagged.version = PAGE_CHECKSUM_VERSION;
+    untagged.update_checksum();
+    std::fs::write(&metadata_path, bincode::serialize(&untagged).unwrap()).unwrap();
+    assert!(matches!(
+        PersistentMerkleTree::new(config.clone()),
+        Err(umbra_indexer::IndexerError::InvalidData(_))
+    ));
+
+    // Leaves that do not hash to the recorded root are rejected too
+    let mut wrong_root = metadata;
+    wrong_root.update(wrong_root.next_index, [9u8; 32]);
+    std::fs::write(&metadata_path, bincode::serialize(&wrong_root).unwrap()).unwrap();
+    assert!(matches!(
+        PersistentMerkleTree::new(config.clone()),
+        Err(umbra_indexer::IndexerError::InvalidData(_))
+    ));
+    assert!(PersistentMerkleTree::open_read_only(config.clone()).is_err());
+
+    std::fs::write(&metadata_path, original).unwrap();
+    assert_eq!(PersistentMerkleTree::new(config).unwrap().len(), 3);
+}